@@ -0,0 +1,152 @@
+//!
+//! Conversions of JS primitives to the Rust value asked for, kept apart from the
+//! deserializer so they can be tested without a JS runtime
+//!
+
+use crate::errors::{self, Result as LibResult};
+use snafu::{ensure, OptionExt};
+
+/// 2^63, the first f64 above `i64::MAX`
+const I64_BOUND: f64 = 9_223_372_036_854_775_808.0;
+/// 2^64, the first f64 above `u64::MAX`
+const U64_BOUND: f64 = 18_446_744_073_709_551_616.0;
+
+/// A JS number as the Rust number it's visited as
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub(crate) enum Number {
+    I64(i64),
+    U64(u64),
+    F64(f64),
+}
+
+/// Classifies a JS number, which has a single type for integers and floats
+///
+/// A number without a fractional part that fits in an i64 or u64 is an integer. Past
+/// `Number.MAX_SAFE_INTEGER` that is the already rounded JS value, `2 ** 53 + 1`
+/// is `2 ** 53` before it gets here
+pub(crate) fn number(v: f64) -> Number {
+    #[allow(clippy::float_cmp)]
+    let integral = v.fract() == 0.0;
+    if integral && (-I64_BOUND..I64_BOUND).contains(&v) {
+        #[allow(clippy::cast_possible_truncation)]
+        Number::I64(v as _)
+    } else if integral && (0.0..U64_BOUND).contains(&v) {
+        #[allow(clippy::cast_possible_truncation, clippy::cast_sign_loss)]
+        Number::U64(v as _)
+    } else {
+        Number::F64(v)
+    }
+}
+
+/// A number as an integer of type `T` for `from_value_scalar`, failing with
+/// `UnableToCoerce` if it has a fractional part or is out of range for `to_type`
+pub(crate) fn integer<T: num::NumCast>(v: f64, to_type: &'static str) -> LibResult<T> {
+    #[allow(clippy::float_cmp)]
+    let integral = v.fract() == 0.0;
+    ensure!(integral, errors::UnableToCoerceSnafu { to_type });
+    num::cast(v).context(errors::UnableToCoerceSnafu { to_type })
+}
+
+/// A number as a byte, if it's an integer from 0 to 255
+pub(crate) fn byte(v: f64) -> Option<u8> {
+    #[allow(clippy::float_cmp)]
+    let integral = v.fract() == 0.0;
+    #[allow(clippy::cast_possible_truncation, clippy::cast_sign_loss)]
+    (integral && (0.0..=255.0).contains(&v)).then_some(v as u8)
+}
+
+/// The finite number in a string, for `coerce_numeric_strings`, ignoring surrounding
+/// whitespace and with a single `,` as the decimal separator for `decimal_comma`
+pub(crate) fn numeric_string(text: &str, decimal_comma: bool) -> Option<f64> {
+    let text = text.trim();
+    let v: f64 = if decimal_comma && text.contains(',') {
+        if text.contains('.') || text.matches(',').count() > 1 {
+            return None;
+        }
+        text.replacen(',', ".", 1).parse().ok()?
+    } else {
+        text.parse().ok()?
+    };
+    v.is_finite().then_some(v)
+}
+
+/// A number as an `f32` for `strict_f32`, failing with `PrecisionLoss` unless it's exact
+pub(crate) fn exact_f32(v: f64) -> LibResult<f32> {
+    #[allow(clippy::cast_possible_truncation)]
+    let narrowed = v as f32;
+    #[allow(clippy::float_cmp)]
+    let exact = f64::from(narrowed) == v || v.is_nan();
+    ensure!(exact, errors::PrecisionLossSnafu { value: v });
+    Ok(narrowed)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{byte, exact_f32, integer, number, numeric_string, Number};
+    use crate::errors::Error;
+
+    #[test]
+    fn test_number() {
+        assert_eq!(number(3.0), Number::I64(3));
+        assert_eq!(number(-0.0), Number::I64(0));
+        assert_eq!(number(-9_223_372_036_854_775_808.0), Number::I64(i64::MIN));
+        assert_eq!(number(9_223_372_036_854_775_808.0), Number::U64(1 << 63));
+        assert_eq!(
+            number(18_446_744_073_709_551_616.0),
+            Number::F64(2f64.powi(64))
+        );
+        assert_eq!(number(1.5), Number::F64(1.5));
+        assert!(matches!(number(f64::NAN), Number::F64(v) if v.is_nan()));
+        assert_eq!(number(f64::INFINITY), Number::F64(f64::INFINITY));
+    }
+
+    #[test]
+    fn test_integer() {
+        assert_eq!(integer::<u8>(255.0, "u8").ok(), Some(255));
+        assert_eq!(integer::<i8>(-128.0, "i8").ok(), Some(-128));
+        assert_eq!(integer::<u32>(-0.0, "u32").ok(), Some(0));
+        assert_eq!(
+            integer::<i64>(-9_007_199_254_740_991.0, "i64").ok(),
+            Some(-9_007_199_254_740_991)
+        );
+        for v in [256.0, -1.0, 1.5, 1e-17, f64::NAN, f64::INFINITY] {
+            assert!(matches!(
+                integer::<u8>(v, "u8"),
+                Err(Error::UnableToCoerce { to_type: "u8", .. })
+            ));
+        }
+        assert!(integer::<i16>(32_768.0, "i16").is_err());
+        assert!(integer::<u64>(18_446_744_073_709_551_616.0, "u64").is_err());
+    }
+
+    #[test]
+    fn test_byte() {
+        assert_eq!(byte(0.0), Some(0));
+        assert_eq!(byte(255.0), Some(255));
+        assert_eq!(byte(256.0), None);
+        assert_eq!(byte(-1.0), None);
+        assert_eq!(byte(0.5), None);
+        assert_eq!(byte(f64::NAN), None);
+    }
+
+    #[test]
+    fn test_numeric_string() {
+        assert_eq!(numeric_string("2.5", false), Some(2.5));
+        assert_eq!(numeric_string(" 1e3 ", false), Some(1000.0));
+        assert_eq!(numeric_string("2,5", false), None);
+        assert_eq!(numeric_string("2,5", true), Some(2.5));
+        assert_eq!(numeric_string("1,000.5", true), None);
+        assert_eq!(numeric_string("1,000,5", true), None);
+        assert_eq!(numeric_string("inf", false), None);
+        assert_eq!(numeric_string("NaN", false), None);
+        assert_eq!(numeric_string("", false), None);
+    }
+
+    #[test]
+    fn test_exact_f32() {
+        assert_eq!(exact_f32(1.5).ok(), Some(1.5));
+        assert!(exact_f32(f64::NAN).is_ok_and(f32::is_nan));
+        assert_eq!(exact_f32(f64::INFINITY).ok(), Some(f32::INFINITY));
+        assert!(matches!(exact_f32(0.1), Err(Error::PrecisionLoss { .. })));
+    }
+}
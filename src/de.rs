@@ -2,30 +2,330 @@
 //! Deserialize a `JsValue` into a Rust data structure
 //!
 
-use crate::errors::{self, Error as LibError, Result as LibResult};
-use neon::{prelude::*, types::buffer::TypedArray};
+use crate::{
+    coerce::{self, Number},
+    errors::{self, Error as LibError, Result as LibResult},
+    ser::{EnumStyle, RootedValue},
+    table, template,
+};
+use neon::{
+    prelude::*,
+    types::{buffer::TypedArray, JsBigInt, JsDate},
+};
 use serde::{
     self,
     de::{
-        DeserializeOwned, DeserializeSeed, EnumAccess, MapAccess, SeqAccess, Unexpected,
-        VariantAccess, Visitor,
+        value::{MapDeserializer, SeqDeserializer},
+        Deserialize, DeserializeOwned, DeserializeSeed, EnumAccess, IntoDeserializer, MapAccess,
+        SeqAccess, Unexpected, VariantAccess, Visitor,
     },
 };
-use snafu::ensure;
+use snafu::{ensure, OptionExt};
+use std::{
+    cell::{Cell, RefCell},
+    collections::{BTreeSet, HashMap},
+    fmt::Write,
+    hash::BuildHasher,
+};
+
+/// Options to customize how values are deserialized
+///
+/// Use with [`from_value_with`], the [`Default`] matches the behaviour of [`from_value`]
+#[derive(Debug, Clone)]
+#[allow(clippy::struct_excessive_bools)]
+pub struct DeserializeOptions {
+    /// Stop at the first array element that fails to deserialize, defaults to `true`
+    ///
+    /// When `false` every failing element is reported in an `Error::Multiple`.
+    /// This is meant for validation, each failing element costs another pass over the input
+    /// and getters run again on every pass. Otherwise each property is read exactly once
+    pub fail_fast: bool,
+    /// Rewrite serde's error messages to use JS type names
+    ///
+    /// `invalid type: integer `5`, expected a string` becomes
+    /// `expected a string but got a number`
+    pub friendly_errors: bool,
+    /// Accept any object implementing `Symbol.iterator` (generators, `Set`, ...)
+    /// where a sequence is expected, not only arrays
+    ///
+    /// Iterators are consumed as they are read, so they won't be read again
+    /// by the extra passes of `fail_fast: false`.
+    /// A `Map` is read as a sequence of `[key, value]` pairs even without this option,
+    /// so it can be read in order, duplicates included, into a `Vec<(K, V)>`
+    pub accept_iterables: bool,
+    /// Value of `Deserializer::is_human_readable`, defaults to `true`
+    ///
+    /// Set to `false` to read the compact form of types like `uuid::Uuid` (a `Buffer`)
+    pub human_readable: bool,
+    /// Accept arrays of `[key, value]` pairs where a map is expected
+    ///
+    /// `[["a", 1], ["b", 2]]` reads the same as `{ a: 1, b: 2 }`
+    pub pair_arrays_as_maps: bool,
+    /// Fail with `Error::PrecisionLoss` instead of rounding when a number read as an `f32`
+    /// doesn't convert back to the same `f64`, or is out of the `f32` range
+    ///
+    /// Also applies to the strings read with `coerce_numeric_strings`
+    pub strict_f32: bool,
+    /// Read structs by getting each of their fields from the object
+    /// instead of enumerating all of its keys
+    ///
+    /// Faster for wide objects when only a few fields are needed. Each name in
+    /// `fields` is looked up, which includes `#[serde(alias)]` names. Unknown keys are
+    /// never seen, so `#[serde(deny_unknown_fields)]` can't reject them,
+    /// and properties that are `undefined` count as missing
+    pub lookup_struct_fields: bool,
+    /// Match enum variant names ignoring ASCII case, `"red"` reads as a `Red` variant
+    ///
+    /// An exact match always wins, otherwise a name matching several variants is an error
+    pub case_insensitive_variants: bool,
+    /// Shape of enum variants, see [`EnumStyle`]
+    ///
+    /// A unit variant can always be given as just its name
+    pub enum_style: EnumStyle,
+    /// Treat struct fields set to `null` as missing
+    ///
+    /// Fields with `#[serde(default)]` get their default and `Option` fields are `None`,
+    /// other fields fail with a missing field error. Map entries keep their `null`s
+    pub null_as_default: bool,
+    /// Read tuples, tuple structs and tuple variants from objects with the keys `"0"` to `"n - 1"`,
+    /// like `{ "0": 1, "1": "a" }`, as well as from arrays
+    pub numeric_key_objects_as_seq: bool,
+    /// Read a `Date` given where a string is expected as its `toISOString()`,
+    /// like `"2020-01-01T00:00:00.000Z"`
+    pub date_to_iso_string: bool,
+    /// Accept an empty object `{}` for `()` and unit structs, as well as `null` and `undefined`
+    pub empty_object_as_unit: bool,
+    /// With `case_insensitive_variants`, also match variant names in screaming snake case,
+    /// `"IN_PROGRESS"` reads as an `InProgress` variant
+    ///
+    /// Names are converted like `#[serde(rename_all = "SCREAMING_SNAKE_CASE")]` does
+    pub screaming_snake_variants: bool,
+    /// Read a `BigInt` given where a string is expected as its decimal digits,
+    /// `123n` reads as `"123"` with every digit kept
+    pub bigint_as_string: bool,
+    /// Fill the missing trailing elements of a tuple read from a shorter array with
+    /// the empty value of their type, `[1]` reads as `(1, 0)` for an `(i32, i32)`
+    ///
+    /// The empty values are `0`, `false`, `""`, `None` and empty collections, which is
+    /// the `Default` of the standard types. Other types fail, except structs whose
+    /// fields all have a `#[serde(default)]`
+    pub tuple_fill_defaults: bool,
+    /// Read strings holding a number, like `"3.14"` or `" 1e3 "`, where an `f32` or `f64` is expected
+    ///
+    /// Strings that aren't a finite number still fail with an invalid type error
+    pub coerce_numeric_strings: bool,
+    /// With `coerce_numeric_strings`, also read a comma as the decimal separator, `"3,14"` is `3.14`
+    ///
+    /// Thousands separators aren't supported since they're ambiguous: both `"1,234"` and
+    /// `"1.234"` are `1.234`, and `"1.234,5"` or `"1,234.5"` fail
+    pub decimal_comma: bool,
+    /// Fail with a missing field error when an object has no own property for a struct field,
+    /// even an `Option` or `#[serde(default)]` one
+    ///
+    /// A field with `#[serde(alias)]` names is set when any one of its names is
+    pub require_all_fields: bool,
+    /// Called once when deserializing fails, with a rendering of the innermost array element,
+    /// property or input that failed, like `{a: 1, b: [1, 2, …]}`
+    ///
+    /// The rendering is JSON-like, up to 3 levels deep and cut after 256 characters,
+    /// so huge and circular values are fine. It isn't called for exceptions thrown by getters
+    pub on_error_dump: Option<fn(&str)>,
+    /// Read a `Date` given where a number is expected as its `getTime()`, the milliseconds
+    /// since the epoch, checked against the target type like any other number
+    ///
+    /// An invalid date fails with an invalid date error
+    pub dates_as_millis: bool,
+    /// Read the own properties of an object that aren't fields of the struct into the field
+    /// with this name, as an object, so the field can be a `HashMap<String, serde_json::Value>`
+    ///
+    /// Applies to the outermost struct, which fails with `MissingCaptureField` if it doesn't
+    /// have the field, and to the structs nested in it that have the field, so nested types
+    /// without it are read as usual. A field that can't be read from an object fails with an
+    /// invalid type error. A property with the name of the field itself is captured like the
+    /// unknown ones
+    pub capture_unknown_into: Option<&'static str>,
+    /// Read an `ArrayBuffer` given where a sequence is expected as the `f64`s in its bytes,
+    /// so the `buffer` of a `Float64Array` reads as a `Vec<f64>` without going through JS numbers
+    ///
+    /// The bytes are read as little-endian, which is how a `Float64Array` lays them out on
+    /// the little-endian platforms Node runs on. A length that isn't a multiple of 8 fails
+    /// with `BufferLength`
+    pub reinterpret_buffers: bool,
+    /// Read a string given where a sequence is expected as its Unicode scalar values,
+    /// so `"abc😀"` reads as a `Vec<char>` of 4 chars, as well as from an array
+    ///
+    /// Elements that can't be read from a `char` fail with an invalid type error
+    pub string_as_chars: bool,
+    /// Read a `Buffer` given where any value is accepted as its base64 string
+    ///
+    /// Without it a `Buffer` read that way, e.g. into a `serde_json::Value` or an untagged
+    /// enum, is a sequence of its bytes, so a `Value::Array` of numbers. Targets asking for
+    /// bytes or a sequence still get the bytes
+    pub buffers_as_base64: bool,
+    /// The variant names accepted for enums, failing with `UnexpectedVariant` for any other
+    /// tag before serde matches it to a variant of the Rust enum
+    ///
+    /// Applies to every enum read, after `case_insensitive_variants`. It gives a clearer
+    /// error than serde's unknown variant one when only some variants are allowed at runtime
+    pub expected_variants: Option<Vec<String>>,
+    /// Read a tuple from the leading elements of a longer object keyed `"0"` to `"n - 1"`,
+    /// with `numeric_key_objects_as_seq`, rather than failing with an invalid length error
+    ///
+    /// Arrays longer than a tuple are always read this way, serde's tuples stop reading
+    /// after their last element
+    pub ignore_extra_tuple_elements: bool,
+    /// Read a `NaN` given where an `Option` is expected as `None`, so `NaN` reads
+    /// as `None` for an `Option<f64>` rather than `Some(NaN)`
+    ///
+    /// The target type isn't known at that point, so an `Option` of any other type
+    /// also reads `NaN` as `None` rather than failing. A `NaN` read as a plain `f64` stays `NaN`
+    pub nan_as_none: bool,
+    /// Read `undefined` array elements, including holes like in `[1, , 3]`, as the empty
+    /// value of the element type, so `[1, undefined, 3]` reads as `[1, 0, 3]` for a `Vec<i32>`
+    ///
+    /// The empty values are the ones of `tuple_fill_defaults`. `null` elements aren't affected
+    pub undefined_as_default: bool,
+    /// Read a value serialized with `SerializeOptions::string_table`, replacing the indices
+    /// of its `data` with the strings of its `strings` array before reading it
+    ///
+    /// Fails with `InvalidStringTable` if the value isn't such a table
+    pub string_table: bool,
+}
+
+impl Default for DeserializeOptions {
+    fn default() -> Self {
+        DeserializeOptions {
+            fail_fast: true,
+            friendly_errors: false,
+            accept_iterables: false,
+            human_readable: true,
+            pair_arrays_as_maps: false,
+            strict_f32: false,
+            lookup_struct_fields: false,
+            case_insensitive_variants: false,
+            enum_style: EnumStyle::External,
+            null_as_default: false,
+            numeric_key_objects_as_seq: false,
+            date_to_iso_string: false,
+            empty_object_as_unit: false,
+            screaming_snake_variants: false,
+            bigint_as_string: false,
+            tuple_fill_defaults: false,
+            coerce_numeric_strings: false,
+            decimal_comma: false,
+            require_all_fields: false,
+            on_error_dump: None,
+            dates_as_millis: false,
+            capture_unknown_into: None,
+            reinterpret_buffers: false,
+            string_as_chars: false,
+            buffers_as_base64: false,
+            expected_variants: None,
+            ignore_extra_tuple_elements: false,
+            nan_as_none: false,
+            undefined_as_default: false,
+            string_table: false,
+        }
+    }
+}
 
 /// Deserialize an instance of type `T` from a `Handle<JsValue>`
 ///
+/// Object keys are read in the engine's order, the same as `Reflect.ownKeys`: integer-like
+/// keys first in ascending order, then the other keys in insertion order. An ordered map
+/// like `IndexMap` keeps that order rather than the order the object was written in
+///
 /// # Errors
 ///
 /// Can fail for various reasons see `ErrorKind`
 pub fn from_value<'j, C, T>(cx: &mut C, value: Handle<'j, JsValue>) -> LibResult<T>
 where
     C: Context<'j>,
-    T: DeserializeOwned + ?Sized,
+    T: DeserializeOwned,
+{
+    from_value_with(cx, value, &DeserializeOptions::default())
+}
+
+/// Deserialize an instance of type `T` from a `Handle<JsValue>` using the given `DeserializeOptions`
+///
+/// # Errors
+///
+/// See [`from_value`] errors, with `fail_fast` disabled element errors are
+/// collected into `Error::Multiple`
+pub fn from_value_with<'j, C, T>(
+    cx: &mut C,
+    value: Handle<'j, JsValue>,
+    options: &DeserializeOptions,
+) -> LibResult<T>
+where
+    C: Context<'j>,
+    T: DeserializeOwned,
+{
+    let value = if options.string_table {
+        table::decode(cx, value)?
+    } else {
+        value
+    };
+    deserialize_with(cx, &Shared::new(options), value)
+}
+
+fn deserialize_with<'j, C, T>(
+    cx: &mut C,
+    shared: &Shared,
+    value: Handle<'j, JsValue>,
+) -> LibResult<T>
+where
+    C: Context<'j>,
+    T: DeserializeOwned,
+{
+    let result = deserialize_passes(cx, shared, value);
+    shared.dump_on_error(cx, value, result).map_err(|err| {
+        if shared.opts.friendly_errors {
+            err.into_friendly()
+        } else {
+            err
+        }
+    })
+}
+
+fn deserialize_passes<'j, C, T>(
+    cx: &mut C,
+    shared: &Shared,
+    value: Handle<'j, JsValue>,
+) -> LibResult<T>
+where
+    C: Context<'j>,
+    T: DeserializeOwned,
 {
-    let mut deserializer: Deserializer<C> = Deserializer::new(cx, value);
-    let t = T::deserialize(&mut deserializer)?;
-    Ok(t)
+    if shared.opts.fail_fast {
+        shared.struct_seen.set(false);
+        let mut deserializer: Deserializer<C> = Deserializer::new(cx, shared, value);
+        let t = T::deserialize(&mut deserializer)?;
+        return Ok(t);
+    }
+
+    // A failed element can't be resumed (its seed is consumed) so run again
+    // skipping it, until a pass fails outside of any array element or succeeds
+    shared.failures.clear();
+    let mut errors = Vec::new();
+    loop {
+        shared.failures.start_pass();
+        shared.struct_seen.set(false);
+        let mut deserializer: Deserializer<C> = Deserializer::new(cx, shared, value);
+        match T::deserialize(&mut deserializer) {
+            Ok(t) if errors.is_empty() => return Ok(t),
+            Ok(_) => break,
+            // the exception is pending, it must get back to JS untouched
+            Err(err @ LibError::Js { .. }) => return Err(err),
+            Err(err) => {
+                errors.push(err);
+                if !shared.failures.skip_failed() {
+                    break;
+                }
+            }
+        }
+    }
+    errors::MultipleSnafu { errors }.fail()
 }
 
 /// Deserialize an instance of type `T` from a `Handle<JsValue>`
@@ -39,7 +339,7 @@ pub fn from_value_js<'j, C, T>(
 ) -> Result<T, neon::result::Throw>
 where
     C: Context<'j>,
-    T: DeserializeOwned + ?Sized,
+    T: DeserializeOwned,
 {
     from_value(cx, value).or_else(|err| cx.throw_error(err.to_string()))
 }
@@ -50,29 +350,659 @@ where
 pub fn from_value_opt<'j, C, T>(cx: &mut C, value: Option<Handle<'j, JsValue>>) -> LibResult<T>
 where
     C: Context<'j>,
-    T: DeserializeOwned + ?Sized,
+    T: DeserializeOwned,
 {
     let unwrapped = value.unwrap_or_else(|| cx.undefined().upcast());
     from_value(cx, unwrapped)
 }
 
+/// Deserialize an instance of type `T` from a value kept by [`to_rooted`](crate::to_rooted),
+/// in the current context
+///
+/// The value stays rooted. A released root can't be read since [`RootedValue::drop`] and
+/// [`RootedValue::into_inner`] take it by value
+///
+/// # Errors
+///
+/// See [`from_value`] errors
+pub fn from_root<'j, C, T>(cx: &mut C, root: &RootedValue) -> LibResult<T>
+where
+    C: Context<'j>,
+    T: DeserializeOwned,
+{
+    let value = root.to_inner(cx)?;
+    from_value(cx, value)
+}
+
+/// Reads an object as a partial update, with a `Some` for each property set to a value
+/// and a `None` for each property set to `null`
+///
+/// Absent properties and properties set to `undefined` aren't in the map,
+/// so `{ a: 1, b: null }` gives `{ "a": Some(1), "b": None }`
+///
+/// # Errors
+///
+/// * `UnableToCoerce` if `value` isn't an object
+/// * See [`from_value`] errors for the property values
+pub fn from_value_patch<'j, C, T>(
+    cx: &mut C,
+    value: Handle<'j, JsValue>,
+) -> LibResult<HashMap<String, Option<T>>>
+where
+    C: Context<'j>,
+    T: DeserializeOwned,
+{
+    let object = value
+        .downcast::<JsObject, C>(cx)
+        .ok()
+        .context(errors::UnableToCoerceSnafu { to_type: "object" })?;
+    let names = object.get_own_property_names(cx)?;
+    let len = names.len(cx);
+    let mut patch = HashMap::with_capacity(as_usize(len)?);
+    for i in 0..len {
+        let name: Handle<JsValue> = names.get(cx, i)?;
+        let name = name.to_string(cx)?;
+        let field = object.get_value(cx, name)?;
+        if field.is_a::<JsUndefined, C>(cx) {
+            continue;
+        }
+        let field = if field.is_a::<JsNull, C>(cx) {
+            None
+        } else {
+            Some(from_value(cx, field)?)
+        };
+        patch.insert(name.value(cx), field);
+    }
+    Ok(patch)
+}
+
+/// Reads the elements of an array one at a time until `predicate` returns `true`,
+/// the elements after that one aren't read at all
+///
+/// The element that matched is the last of the returned prefix, all of the elements
+/// are returned when none match
+///
+/// # Errors
+///
+/// * `UnableToCoerce` if `value` isn't an array
+/// * See [`from_value`] errors for the elements read
+pub fn from_value_seq_until<'j, C, T, P>(
+    cx: &mut C,
+    value: Handle<'j, JsValue>,
+    mut predicate: P,
+) -> LibResult<Vec<T>>
+where
+    C: Context<'j>,
+    T: DeserializeOwned,
+    P: FnMut(&T) -> bool,
+{
+    let array = value
+        .downcast::<JsArray, C>(cx)
+        .ok()
+        .context(errors::UnableToCoerceSnafu { to_type: "array" })?;
+    let len = array.len(cx);
+    let mut prefix = Vec::new();
+    for i in 0..len {
+        let element = array.get_value(cx, i)?;
+        let element: T = from_value(cx, element)?;
+        let done = predicate(&element);
+        prefix.push(element);
+        if done {
+            break;
+        }
+    }
+    Ok(prefix)
+}
+
+/// Reads the value for one `type` of [`from_value_dynamic`], usually with [`from_value`]
+pub type DynamicDeserializer<'j, C, T> = fn(&mut C, Handle<'j, JsValue>) -> LibResult<Box<T>>;
+
+/// Reads an object with a `type` property, like `{ type: "circle", r: 1 }`,
+/// with the function registered for that type, to get a trait object
+///
+/// The whole object is given to the function, `type` included
+///
+/// ```
+/// # use std::collections::HashMap;
+/// # use neon::prelude::*;
+/// # use neon_serde::{errors::Result, from_value, from_value_dynamic, DynamicDeserializer};
+/// # trait Shape {}
+/// # #[derive(serde::Deserialize)]
+/// # struct Circle { r: f64 }
+/// # impl Shape for Circle {}
+/// fn read_shape<'j>(
+///     cx: &mut FunctionContext<'j>,
+///     value: Handle<'j, JsValue>,
+/// ) -> Result<Box<dyn Shape>> {
+///     let mut registry: HashMap<String, DynamicDeserializer<_, dyn Shape>> = HashMap::new();
+///     registry.insert("circle".into(), |cx, value| {
+///         Ok(Box::new(from_value::<_, Circle>(cx, value)?))
+///     });
+///     from_value_dynamic(cx, value, &registry)
+/// }
+/// ```
+///
+/// # Errors
+///
+/// * `UnableToCoerce` if `value` isn't an object
+/// * `InvalidKeyType` if the object has no string `type`
+/// * `UnknownDiscriminant` if no function is registered for the `type`
+/// * The errors of the registered function
+pub fn from_value_dynamic<'j, C, T, S>(
+    cx: &mut C,
+    value: Handle<'j, JsValue>,
+    registry: &HashMap<String, DynamicDeserializer<'j, C, T>, S>,
+) -> LibResult<Box<T>>
+where
+    C: Context<'j>,
+    T: ?Sized,
+    S: BuildHasher,
+{
+    let object = value
+        .downcast::<JsObject, C>(cx)
+        .ok()
+        .context(errors::UnableToCoerceSnafu { to_type: "object" })?;
+    let discriminant = object
+        .get_value(cx, "type")?
+        .downcast::<JsString, C>(cx)
+        .ok()
+        .context(errors::InvalidKeyTypeSnafu {
+            key: "object without a string `type`",
+        })?
+        .value(cx);
+    let deserialize = registry
+        .get(&discriminant)
+        .context(errors::UnknownDiscriminantSnafu { discriminant })?;
+    deserialize(cx, value)
+}
+
+/// Reads a primitive directly from a `Handle<JsValue>`, skipping serde
+///
+/// Faster than [`from_value`] for hot paths reading single numbers, booleans or strings.
+/// Numbers must be integers in range to read as an integer type
+///
+/// # Errors
+///
+/// * `UnableToCoerce` if the value isn't of the matching JS type or doesn't fit in `T`
+pub fn from_value_scalar<'j, C, T>(cx: &mut C, value: Handle<'j, JsValue>) -> LibResult<T>
+where
+    C: Context<'j>,
+    T: FromJsScalar,
+{
+    T::from_js(cx, value)
+}
+
+mod sealed {
+    pub trait Sealed {}
+}
+
+/// Primitive types that can be read with [`from_value_scalar`]
+///
+/// This trait is sealed, it's implemented for `bool`, `String`, the floats
+/// and the integers up to 64 bits
+pub trait FromJsScalar: sealed::Sealed + Sized {
+    #[doc(hidden)]
+    fn from_js<'j, C: Context<'j>>(cx: &mut C, value: Handle<'j, JsValue>) -> LibResult<Self>;
+}
+
+fn scalar<'j, C, V>(
+    cx: &mut C,
+    value: Handle<'j, JsValue>,
+    to_type: &'static str,
+) -> LibResult<Handle<'j, V>>
+where
+    C: Context<'j>,
+    V: Value,
+{
+    value
+        .downcast::<V, C>(cx)
+        .ok()
+        .context(errors::UnableToCoerceSnafu { to_type })
+}
+
+impl sealed::Sealed for bool {}
+impl FromJsScalar for bool {
+    fn from_js<'j, C: Context<'j>>(cx: &mut C, value: Handle<'j, JsValue>) -> LibResult<Self> {
+        Ok(scalar::<_, JsBoolean>(cx, value, "bool")?.value(cx))
+    }
+}
+
+impl sealed::Sealed for String {}
+impl FromJsScalar for String {
+    fn from_js<'j, C: Context<'j>>(cx: &mut C, value: Handle<'j, JsValue>) -> LibResult<Self> {
+        Ok(scalar::<_, JsString>(cx, value, "String")?.value(cx))
+    }
+}
+
+impl sealed::Sealed for f64 {}
+impl FromJsScalar for f64 {
+    fn from_js<'j, C: Context<'j>>(cx: &mut C, value: Handle<'j, JsValue>) -> LibResult<Self> {
+        Ok(scalar::<_, JsNumber>(cx, value, "f64")?.value(cx))
+    }
+}
+
+impl sealed::Sealed for f32 {}
+impl FromJsScalar for f32 {
+    fn from_js<'j, C: Context<'j>>(cx: &mut C, value: Handle<'j, JsValue>) -> LibResult<Self> {
+        #[allow(clippy::cast_possible_truncation)]
+        Ok(scalar::<_, JsNumber>(cx, value, "f32")?.value(cx) as f32)
+    }
+}
+
+macro_rules! impl_from_js_scalar_int {
+    ($($ty:ident)*) => {$(
+        impl sealed::Sealed for $ty {}
+        impl FromJsScalar for $ty {
+            fn from_js<'j, C: Context<'j>>(
+                cx: &mut C,
+                value: Handle<'j, JsValue>,
+            ) -> LibResult<Self> {
+                let to_type = stringify!($ty);
+                let v = scalar::<_, JsNumber>(cx, value, to_type)?.value(cx);
+                coerce::integer(v, to_type)
+            }
+        }
+    )*};
+}
+
+impl_from_js_scalar_int!(i8 i16 i32 i64 u8 u16 u32 u64);
+
+/// A deserializer that can be pointed at many values in turn with [`reset`](Self::reset)
+///
+/// Saves setting up the options and per-call state for each value when reading
+/// many values one by one, the result is the same as calling [`from_value_with`]
+pub struct ReusableDeserializer<'a, 'j, C: Context<'j>> {
+    cx: &'a mut C,
+    shared: Shared<'a>,
+    input: Handle<'j, JsValue>,
+}
+
+impl<'a, 'j, C: Context<'j>> ReusableDeserializer<'a, 'j, C> {
+    /// Creates a deserializer reading `undefined` until [`reset`](Self::reset) is called
+    pub fn new(cx: &'a mut C, options: &'a DeserializeOptions) -> Self {
+        let input = cx.undefined().upcast();
+        ReusableDeserializer {
+            cx,
+            shared: Shared::new(options),
+            input,
+        }
+    }
+
+    /// Points the deserializer at the next value
+    pub fn reset(&mut self, value: Handle<'j, JsValue>) {
+        self.input = value;
+    }
+
+    /// Deserialize an instance of type `T` from the current value
+    ///
+    /// # Errors
+    ///
+    /// See [`from_value_with`] errors
+    pub fn deserialize<T>(&mut self) -> LibResult<T>
+    where
+        T: DeserializeOwned,
+    {
+        deserialize_with(self.cx, &self.shared, self.input)
+    }
+}
+
+/// Resolves the variant name to use with `case_insensitive_variants`,
+/// checking it against `expected_variants`
+///
+/// Names without a match are returned as is, so serde reports the unknown variant
+fn match_variant(
+    opts: &DeserializeOptions,
+    variant: String,
+    variants: &'static [&'static str],
+) -> LibResult<String> {
+    let variant = resolve_variant(opts, variant, variants)?;
+    if let Some(expected) = &opts.expected_variants {
+        ensure!(
+            expected.contains(&variant),
+            errors::UnexpectedVariantSnafu {
+                got: variant,
+                expected: expected.clone(),
+            }
+        );
+    }
+    Ok(variant)
+}
+
+/// The variant `variant` matches with `case_insensitive_variants`
+fn resolve_variant(
+    opts: &DeserializeOptions,
+    variant: String,
+    variants: &'static [&'static str],
+) -> LibResult<String> {
+    if !opts.case_insensitive_variants || variants.contains(&variant.as_str()) {
+        return Ok(variant);
+    }
+
+    let mut matches = variants.iter().filter(|name| {
+        name.eq_ignore_ascii_case(&variant)
+            || (opts.screaming_snake_variants && screaming_snake(name) == variant)
+    });
+    match (matches.next(), matches.next()) {
+        (Some(name), None) => Ok((*name).to_string()),
+        (Some(_), Some(_)) => errors::AmbiguousVariantSnafu { variant, variants }.fail(),
+        (None, _) => Ok(variant),
+    }
+}
+
+/// Visits a `BigInt` as the narrowest of `i64`, `u64`, `i128` and `u128` that holds it,
+/// the visitor then checks it fits the integer type being read, like it does for numbers
+fn visit_bigint<'j, 'x, C, V>(
+    cx: &mut C,
+    value: Handle<'j, JsBigInt>,
+    visitor: V,
+) -> LibResult<V::Value>
+where
+    C: Context<'j>,
+    V: Visitor<'x>,
+{
+    if let Ok(v) = value.to_i64(cx) {
+        visitor.visit_i64(v)
+    } else if let Ok(v) = value.to_u64(cx) {
+        visitor.visit_u64(v)
+    } else if let Ok(v) = value.to_i128(cx) {
+        visitor.visit_i128(v)
+    } else if let Ok(v) = value.to_u128(cx) {
+        visitor.visit_u128(v)
+    } else {
+        let value = value.to_string(cx)?.value(cx);
+        errors::BigIntTooWideSnafu { value }.fail()
+    }
+}
+
+/// Visits a number as an integer when it has no fractional part and fits in an `i64` or `u64`
+fn visit_number<'x, V>(v: f64, visitor: V) -> LibResult<V::Value>
+where
+    V: Visitor<'x>,
+{
+    match coerce::number(v) {
+        Number::I64(v) => visitor.visit_i64(v),
+        Number::U64(v) => visitor.visit_u64(v),
+        Number::F64(v) => visitor.visit_f64(v),
+    }
+}
+
+/// `InProgress` as `IN_PROGRESS`, for `screaming_snake_variants`
+fn screaming_snake(name: &str) -> String {
+    let mut screaming = String::with_capacity(name.len() + 4);
+    for (i, c) in name.char_indices() {
+        if i > 0 && c.is_uppercase() {
+            screaming.push('_');
+        }
+        screaming.extend(c.to_uppercase());
+    }
+    screaming
+}
+
+/// State shared by all the nested deserializers of a single `from_value_with` call
+#[doc(hidden)]
+struct Shared<'s> {
+    opts: &'s DeserializeOptions,
+    failures: ElementFailures,
+    /// Whether `on_error_dump` was called
+    dumped: Cell<bool>,
+    /// Whether a struct was read in this pass, so the next ones aren't the outermost
+    struct_seen: Cell<bool>,
+    /// The bytes of the last identifier read, kept to read the next one without allocating
+    #[cfg(feature = "key_buffer")]
+    key_buffer: RefCell<Vec<u8>>,
+}
+
+#[doc(hidden)]
+impl<'s> Shared<'s> {
+    fn new(opts: &'s DeserializeOptions) -> Self {
+        Shared {
+            opts,
+            failures: ElementFailures::default(),
+            dumped: Cell::new(false),
+            struct_seen: Cell::new(false),
+            #[cfg(feature = "key_buffer")]
+            key_buffer: RefCell::default(),
+        }
+    }
+
+    /// Calls `on_error_dump` with `value` if `result` is the first error
+    ///
+    /// Called as errors bubble up, so the first value dumped is the innermost one
+    fn dump_on_error<'j, C, T>(
+        &self,
+        cx: &mut C,
+        value: Handle<'j, JsValue>,
+        result: LibResult<T>,
+    ) -> LibResult<T>
+    where
+        C: Context<'j>,
+    {
+        let Some(dump) = self.opts.on_error_dump else {
+            return result;
+        };
+        // a pending exception must get back to JS before calling into it again
+        if matches!(result, Err(LibError::Js { .. })) || result.is_ok() || self.dumped.get() {
+            return result;
+        }
+        self.dumped.set(true);
+        let rendered = cx
+            .try_catch(|cx| {
+                let mut out = String::new();
+                render(cx, value, DUMP_DEPTH, &mut out)?;
+                Ok(out)
+            })
+            .unwrap_or_else(|_| String::from("<unreadable>"));
+        dump(&rendered);
+        result
+    }
+}
+
+/// Levels of nesting rendered for `on_error_dump`
+const DUMP_DEPTH: u8 = 3;
+/// Characters rendered for `on_error_dump`, before the `…`
+const DUMP_LIMIT: usize = 256;
+
+/// Appends a JSON-like rendering of `value`, up to `depth` levels deep and about `DUMP_LIMIT` characters
+fn render<'j, C: Context<'j>>(
+    cx: &mut C,
+    value: Handle<'j, JsValue>,
+    depth: u8,
+    out: &mut String,
+) -> NeonResult<()> {
+    if out.len() > DUMP_LIMIT {
+        return Ok(());
+    }
+    if let Ok(string) = value.downcast::<JsString, C>(cx) {
+        let _ = write!(out, "{:?}", string.value(cx));
+    } else if value.is_a::<JsFunction, C>(cx) {
+        out.push_str("[Function]");
+    } else if let Ok(array) = value.downcast::<JsArray, C>(cx) {
+        if depth == 0 {
+            out.push_str("[…]");
+        } else {
+            out.push('[');
+            for i in 0..array.len(cx) {
+                if i > 0 {
+                    out.push_str(", ");
+                }
+                let element = array.get_value(cx, i)?;
+                render(cx, element, depth - 1, out)?;
+                if out.len() > DUMP_LIMIT {
+                    break;
+                }
+            }
+            out.push(']');
+        }
+    } else if let Ok(object) = value.downcast::<JsObject, C>(cx) {
+        if depth == 0 {
+            out.push_str("{…}");
+        } else {
+            out.push('{');
+            for (i, key) in object
+                .get_own_property_names(cx)?
+                .to_vec(cx)?
+                .into_iter()
+                .enumerate()
+            {
+                if i > 0 {
+                    out.push_str(", ");
+                }
+                out.push_str(&key.to_string(cx)?.value(cx));
+                out.push_str(": ");
+                let property = object.get_value(cx, key)?;
+                render(cx, property, depth - 1, out)?;
+                if out.len() > DUMP_LIMIT {
+                    break;
+                }
+            }
+            out.push('}');
+        }
+    } else {
+        out.push_str(&value.to_string(cx)?.value(cx));
+    }
+    if out.len() > DUMP_LIMIT {
+        let mut end = DUMP_LIMIT;
+        while !out.is_char_boundary(end) {
+            end -= 1;
+        }
+        out.truncate(end);
+        out.push('…');
+    }
+    Ok(())
+}
+
+/// Tracks failing array elements when `fail_fast` is disabled
+///
+/// Elements are numbered in visiting order, which stays the same between
+/// passes up to the first element that newly failed
+#[doc(hidden)]
+#[derive(Default)]
+struct ElementFailures {
+    next_id: Cell<u64>,
+    failed: Cell<Option<u64>>,
+    skip: RefCell<BTreeSet<u64>>,
+}
+
+#[doc(hidden)]
+impl ElementFailures {
+    fn clear(&self) {
+        self.skip.borrow_mut().clear();
+    }
+
+    fn start_pass(&self) {
+        self.next_id.set(0);
+        self.failed.set(None);
+    }
+
+    /// Numbers the next element, returns the id and if it failed in a previous pass
+    fn next(&self) -> (u64, bool) {
+        let id = self.next_id.get();
+        self.next_id.set(id + 1);
+        (id, self.skip.borrow().contains(&id))
+    }
+
+    /// Records the failure of an element, only the innermost element is kept
+    fn fail(&self, id: u64) {
+        if self.failed.get().is_none() {
+            self.failed.set(Some(id));
+        }
+    }
+
+    /// Marks the element that failed in this pass to be skipped in the next,
+    /// returns `false` when the failure wasn't in an array element
+    fn skip_failed(&self) -> bool {
+        match self.failed.take() {
+            Some(id) => self.skip.borrow_mut().insert(id),
+            None => false,
+        }
+    }
+}
+
 #[doc(hidden)]
 pub struct Deserializer<'a, 'j, C: Context<'j> + 'a> {
     cx: &'a mut C,
+    shared: &'a Shared<'a>,
     input: Handle<'j, JsValue>,
 }
 
 #[doc(hidden)]
 impl<'a, 'j, C: Context<'j>> Deserializer<'a, 'j, C> {
-    fn new(cx: &'a mut C, input: Handle<'j, JsValue>) -> Self {
-        Deserializer { cx, input }
+    fn new(cx: &'a mut C, shared: &'a Shared<'a>, input: Handle<'j, JsValue>) -> Self {
+        Deserializer { cx, shared, input }
+    }
+
+    /// The number in a string, with `coerce_numeric_strings`
+    fn numeric_string(&mut self) -> Option<f64> {
+        let opts = self.shared.opts;
+        if !opts.coerce_numeric_strings {
+            return None;
+        }
+        let text = self
+            .input
+            .downcast::<JsString, C>(self.cx)
+            .ok()?
+            .value(self.cx);
+        coerce::numeric_string(&text, opts.decimal_comma)
+    }
+
+    /// The `getTime()` of a `Date`, with `dates_as_millis`
+    fn date_millis(&mut self) -> LibResult<Option<f64>> {
+        if !self.shared.opts.dates_as_millis {
+            return Ok(None);
+        }
+        let Ok(date) = self.input.downcast::<JsDate, C>(self.cx) else {
+            return Ok(None);
+        };
+        ensure!(date.is_valid(self.cx), errors::InvalidDateSnafu);
+        Ok(Some(date.value(self.cx)))
+    }
+
+    /// Visits a struct once `deserialize_struct` applied `capture_unknown_into`
+    /// and `require_all_fields`
+    fn visit_struct<'x, V>(
+        &mut self,
+        fields: &'static [&'static str],
+        visitor: V,
+    ) -> LibResult<V::Value>
+    where
+        V: Visitor<'x>,
+    {
+        if self.shared.opts.lookup_struct_fields && !self.input.is_a::<JsArray, C>(self.cx) {
+            if let Ok(val) = self.input.downcast::<JsObject, C>(self.cx) {
+                let mut deserializer = JsStructAccess::new(self.cx, self.shared, val, fields)?;
+                return visitor.visit_map(&mut deserializer);
+            }
+        }
+        if self.shared.opts.null_as_default
+            && !self.input.is_a::<JsArray, C>(self.cx)
+            && !self.input.is_a::<JsBuffer, C>(self.cx)
+        {
+            if let Ok(val) = self.input.downcast::<JsObject, C>(self.cx) {
+                let mut deserializer = JsObjectAccess::for_struct(self.cx, self.shared, val)?;
+                return visitor.visit_map(&mut deserializer);
+            }
+        }
+        serde::de::Deserializer::deserialize_any(self, visitor)
     }
 }
 
+/// Integer targets, which read a `Date` as a number with `dates_as_millis`
+macro_rules! deserialize_integer {
+    ($($method:ident)*) => {$(
+        fn $method<V>(self, visitor: V) -> Result<V::Value, Self::Error>
+        where
+            V: Visitor<'x>,
+        {
+            if let Some(millis) = self.date_millis()? {
+                return visit_number(millis, visitor);
+            }
+            self.deserialize_any(visitor)
+        }
+    )*};
+}
+
 #[doc(hidden)]
-impl<'x, 'd, 'a, 'j, C: Context<'j>> serde::de::Deserializer<'x>
-    for &'d mut Deserializer<'a, 'j, C>
-{
+impl<'x, 'j, C: Context<'j>> serde::de::Deserializer<'x> for &mut Deserializer<'_, 'j, C> {
     type Error = LibError;
 
     fn deserialize_any<V>(self, visitor: V) -> Result<V::Value, Self::Error>
@@ -88,22 +1018,29 @@ impl<'x, 'd, 'a, 'j, C: Context<'j>> serde::de::Deserializer<'x>
         } else if let Ok(val) = self.input.downcast::<JsString, C>(self.cx) {
             visitor.visit_string(val.value(self.cx))
         } else if let Ok(val) = self.input.downcast::<JsNumber, C>(self.cx) {
-            let v = val.value(self.cx);
-            //v64 == v (if trucated v == v)
-            // then it doesn't have a decimal part
-            if (v.trunc() - v).abs() < f64::EPSILON {
-                #[allow(clippy::cast_possible_truncation)]
-                visitor.visit_i64(v as _)
-            } else {
-                visitor.visit_f64(v)
+            visit_number(val.value(self.cx), visitor)
+        } else if let Ok(val) = self.input.downcast::<JsBigInt, C>(self.cx) {
+            visit_bigint(self.cx, val, visitor)
+        } else if let Ok(val) = self.input.downcast::<JsBuffer, C>(self.cx) {
+            if self.shared.opts.buffers_as_base64 {
+                let to_string: Handle<JsFunction> = val.get(self.cx, "toString")?;
+                let encoding = self.cx.string("base64");
+                let encoded: Handle<JsString> = to_string
+                    .call_with(self.cx)
+                    .this(val)
+                    .arg(encoding)
+                    .apply(self.cx)?;
+                return visitor.visit_string(encoded.value(self.cx));
             }
-        } else if let Ok(_val) = self.input.downcast::<JsBuffer, C>(self.cx) {
-            self.deserialize_bytes(visitor)
+            // a sequence rather than `visit_bytes`, which `serde_json::Value`
+            // and a `Vec<u8>` in an untagged enum don't accept
+            let bytes = Vec::from(val.as_slice(self.cx));
+            visitor.visit_seq(SeqDeserializer::new(bytes.into_iter()))
         } else if let Ok(val) = self.input.downcast::<JsArray, C>(self.cx) {
-            let mut deserializer = JsArrayAccess::new(self.cx, val);
+            let mut deserializer = JsArrayAccess::new(self.cx, self.shared, val);
             visitor.visit_seq(&mut deserializer)
         } else if let Ok(val) = self.input.downcast::<JsObject, C>(self.cx) {
-            let mut deserializer = JsObjectAccess::new(self.cx, val)?;
+            let mut deserializer = JsObjectAccess::new(self.cx, self.shared, val)?;
             visitor.visit_map(&mut deserializer)
         } else {
             errors::NotImplemented {
@@ -117,8 +1054,15 @@ impl<'x, 'd, 'a, 'j, C: Context<'j>> serde::de::Deserializer<'x>
     where
         V: Visitor<'x>,
     {
-        if self.input.downcast::<JsNull, C>(self.cx).is_ok()
-            || self.input.downcast::<JsUndefined, C>(self.cx).is_ok()
+        // one check per layer of `Option<Option<..>>`, `null` is always the outermost `None`
+        let nan = self.shared.opts.nan_as_none
+            && self
+                .input
+                .downcast::<JsNumber, C>(self.cx)
+                .is_ok_and(|number| number.value(self.cx).is_nan());
+        if nan
+            || self.input.is_a::<JsNull, C>(self.cx)
+            || self.input.is_a::<JsUndefined, C>(self.cx)
         {
             visitor.visit_none()
         } else {
@@ -129,16 +1073,62 @@ impl<'x, 'd, 'a, 'j, C: Context<'j>> serde::de::Deserializer<'x>
     fn deserialize_enum<V>(
         self,
         _name: &'static str,
-        _variants: &'static [&'static str],
+        variants: &'static [&'static str],
         visitor: V,
     ) -> Result<V::Value, Self::Error>
     where
         V: Visitor<'x>,
     {
         if let Ok(val) = self.input.downcast::<JsString, C>(self.cx) {
-            let val = val.value(self.cx);
-            visitor.visit_enum(JsEnumAccess::new(self.cx, val, None))
+            let val = match_variant(self.shared.opts, val.value(self.cx), variants)?;
+            visitor.visit_enum(JsEnumAccess::new(self.cx, self.shared, val, None))
         } else if let Ok(val) = self.input.downcast::<JsObject, C>(self.cx) {
+            if self.shared.opts.enum_style == EnumStyle::TypeValue {
+                let prop_names = val.get_own_property_names(self.cx)?;
+                for i in 0..prop_names.len(self.cx) {
+                    let name: Handle<JsValue> = prop_names.get(self.cx, i)?;
+                    let name = name.to_string(self.cx)?.value(self.cx);
+                    ensure!(
+                        name == "type" || name == "value",
+                        errors::InvalidKeyTypeSnafu {
+                            key: format!("{name}, expected only `type` and `value`")
+                        }
+                    );
+                }
+                let key = val
+                    .get_value(self.cx, "type")?
+                    .downcast::<JsString, C>(self.cx)
+                    .ok()
+                    .context(errors::InvalidKeyTypeSnafu {
+                        key: "object without a string `type`",
+                    })?;
+                let key = match_variant(self.shared.opts, key.value(self.cx), variants)?;
+                let enum_value = val.get_value(self.cx, "value")?;
+                let enum_value =
+                    (!enum_value.is_a::<JsUndefined, C>(self.cx)).then_some(enum_value);
+                return visitor.visit_enum(JsEnumAccess::new(
+                    self.cx,
+                    self.shared,
+                    key,
+                    enum_value,
+                ));
+            }
+            if let EnumStyle::InternallyTagged { tag } = self.shared.opts.enum_style {
+                let key = val
+                    .get_value(self.cx, tag)?
+                    .downcast::<JsString, C>(self.cx)
+                    .ok()
+                    .with_context(|| errors::InvalidKeyTypeSnafu {
+                        key: format!("object without a string `{tag}`"),
+                    })?;
+                let key = match_variant(self.shared.opts, key.value(self.cx), variants)?;
+                return visitor.visit_enum(JsEnumAccess::new(
+                    self.cx,
+                    self.shared,
+                    key,
+                    Some(self.input),
+                ));
+            }
             let prop_names = val.get_own_property_names(self.cx)?;
             let len = prop_names.len(self.cx);
             ensure!(
@@ -152,134 +1142,845 @@ impl<'x, 'd, 'a, 'j, C: Context<'j>> serde::de::Deserializer<'x>
 
             // let key = prop_names.get(self.cx, 0)?.downcast::<JsString>().or_throw(self.cx)?;
             let enum_value = val.get(self.cx, key)?;
-            let key = key.value(self.cx);
-            visitor.visit_enum(JsEnumAccess::new(self.cx, key, Some(enum_value)))
+            let key = match_variant(self.shared.opts, key.value(self.cx), variants)?;
+            visitor.visit_enum(JsEnumAccess::new(
+                self.cx,
+                self.shared,
+                key,
+                Some(enum_value),
+            ))
         } else {
             let m = self.input.to_string(self.cx)?.value(self.cx);
             Err(errors::InvalidKeyTypeSnafu { key: m }.build())
         }
     }
 
-    fn deserialize_bytes<V>(self, visitor: V) -> Result<V::Value, Self::Error>
+    fn deserialize_bytes<V>(self, visitor: V) -> Result<V::Value, Self::Error>
+    where
+        V: Visitor<'x>,
+    {
+        if let Ok(val) = self.input.downcast::<JsArray, C>(self.cx) {
+            return visitor.visit_byte_buf(byte_array(self.cx, val)?);
+        }
+        let buff = self
+            .input
+            .downcast::<JsBuffer, C>(self.cx)
+            .or_throw(self.cx)?;
+        let copy = Vec::from(buff.as_slice(self.cx));
+        visitor.visit_bytes(&copy)
+    }
+
+    fn deserialize_byte_buf<V>(self, visitor: V) -> Result<V::Value, Self::Error>
+    where
+        V: Visitor<'x>,
+    {
+        if let Ok(val) = self.input.downcast::<JsArray, C>(self.cx) {
+            return visitor.visit_byte_buf(byte_array(self.cx, val)?);
+        }
+        let buff = self
+            .input
+            .downcast::<JsBuffer, C>(self.cx)
+            .or_throw(self.cx)?;
+        let copy = Vec::from(buff.as_slice(self.cx));
+        visitor.visit_byte_buf(copy)
+    }
+
+    fn deserialize_ignored_any<V>(self, visitor: V) -> Result<V::Value, Self::Error>
+    where
+        V: Visitor<'x>,
+    {
+        visitor.visit_unit()
+    }
+
+    fn deserialize_seq<V>(self, visitor: V) -> Result<V::Value, Self::Error>
+    where
+        V: Visitor<'x>,
+    {
+        if self.shared.opts.reinterpret_buffers {
+            if let Ok(val) = self.input.downcast::<JsArrayBuffer, C>(self.cx) {
+                let bytes = val.as_slice(self.cx);
+                ensure!(
+                    bytes.len() % 8 == 0,
+                    errors::BufferLengthSnafu { len: bytes.len() }
+                );
+                let values: Vec<f64> = bytes
+                    .chunks_exact(8)
+                    .map(|chunk| f64::from_le_bytes(chunk.try_into().unwrap_or_default()))
+                    .collect();
+                return visitor.visit_seq(SeqDeserializer::new(values.into_iter()));
+            }
+        }
+        if self.shared.opts.string_as_chars {
+            if let Ok(val) = self.input.downcast::<JsString, C>(self.cx) {
+                let chars: Vec<char> = val.value(self.cx).chars().collect();
+                return visitor.visit_seq(SeqDeserializer::new(chars.into_iter()));
+            }
+        }
+        let iterable = if self.input.is_a::<JsArray, C>(self.cx) {
+            false
+        } else {
+            self.shared.opts.accept_iterables || is_map(self.cx, self.input)?
+        };
+        if iterable {
+            if let Some(iterator) = iterator_of(self.cx, self.input)? {
+                let mut deserializer = JsIteratorAccess::new(self.cx, self.shared, iterator)?;
+                return visitor.visit_seq(&mut deserializer);
+            }
+        }
+        self.deserialize_any(visitor)
+    }
+
+    fn deserialize_map<V>(self, visitor: V) -> Result<V::Value, Self::Error>
+    where
+        V: Visitor<'x>,
+    {
+        if self.shared.opts.pair_arrays_as_maps {
+            if let Ok(val) = self.input.downcast::<JsArray, C>(self.cx) {
+                let mut deserializer = JsPairArrayAccess::new(self.cx, self.shared, val);
+                return visitor.visit_map(&mut deserializer);
+            }
+        }
+        self.deserialize_any(visitor)
+    }
+
+    fn deserialize_struct<V>(
+        self,
+        _name: &'static str,
+        fields: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value, Self::Error>
+    where
+        V: Visitor<'x>,
+    {
+        if let Some(into) = self.shared.opts.capture_unknown_into {
+            let outermost = !self.shared.struct_seen.replace(true);
+            if (outermost || fields.contains(&into)) && !self.input.is_a::<JsArray, C>(self.cx) {
+                if let Ok(val) = self.input.downcast::<JsObject, C>(self.cx) {
+                    self.input = capture_unknown(self.cx, val, fields, into)?.upcast();
+                }
+            }
+        }
+        if self.shared.opts.require_all_fields && !self.input.is_a::<JsArray, C>(self.cx) {
+            if let Ok(val) = self.input.downcast::<JsObject, C>(self.cx) {
+                let visitor = RequireFields {
+                    inner: visitor,
+                    fields,
+                    present: own_keys(self.cx, val)?,
+                };
+                return self.visit_struct(fields, visitor);
+            }
+        }
+        self.visit_struct(fields, visitor)
+    }
+
+    fn deserialize_unit<V>(self, visitor: V) -> Result<V::Value, Self::Error>
+    where
+        V: Visitor<'x>,
+    {
+        if self.shared.opts.empty_object_as_unit
+            && !self.input.is_a::<JsArray, C>(self.cx)
+            && !self.input.is_a::<JsBuffer, C>(self.cx)
+        {
+            if let Ok(val) = self.input.downcast::<JsObject, C>(self.cx) {
+                if val.get_own_property_names(self.cx)?.len(self.cx) == 0 {
+                    return visitor.visit_unit();
+                }
+            }
+        }
+        self.deserialize_any(visitor)
+    }
+
+    fn deserialize_unit_struct<V>(
+        self,
+        _name: &'static str,
+        visitor: V,
+    ) -> Result<V::Value, Self::Error>
+    where
+        V: Visitor<'x>,
+    {
+        self.deserialize_unit(visitor)
+    }
+
+    fn deserialize_str<V>(self, visitor: V) -> Result<V::Value, Self::Error>
+    where
+        V: Visitor<'x>,
+    {
+        self.deserialize_string(visitor)
+    }
+
+    fn deserialize_string<V>(self, visitor: V) -> Result<V::Value, Self::Error>
+    where
+        V: Visitor<'x>,
+    {
+        if self.shared.opts.date_to_iso_string {
+            if let Ok(date) = self.input.downcast::<JsDate, C>(self.cx) {
+                ensure!(date.is_valid(self.cx), errors::InvalidDateSnafu);
+                let to_iso_string: Handle<JsFunction> = date.get(self.cx, "toISOString")?;
+                let iso: Handle<JsString> =
+                    to_iso_string.call_with(self.cx).this(date).apply(self.cx)?;
+                return visitor.visit_string(iso.value(self.cx));
+            }
+        }
+        if self.shared.opts.bigint_as_string {
+            if let Ok(val) = self.input.downcast::<JsBigInt, C>(self.cx) {
+                return visitor.visit_string(val.to_string(self.cx)?.value(self.cx));
+            }
+        }
+        self.deserialize_any(visitor)
+    }
+
+    /// Property names are strings, so they skip the type checks of `deserialize_any`
+    ///
+    /// With the `key_buffer` feature the name is read into a buffer kept between names and
+    /// lent to the visitor with `visit_str`, so struct fields and enum variants are matched
+    /// without allocating
+    fn deserialize_identifier<V>(self, visitor: V) -> Result<V::Value, Self::Error>
+    where
+        V: Visitor<'x>,
+    {
+        if let Ok(val) = self.input.downcast::<JsString, C>(self.cx) {
+            #[cfg(feature = "key_buffer")]
+            {
+                // taken rather than borrowed, in case the visitor reads another identifier
+                let mut buffer = self.shared.key_buffer.take();
+                let key = read_utf8(self.cx, val, &mut buffer);
+                let result = visit_key(visitor, key, || val.value(self.cx));
+                self.shared.key_buffer.replace(buffer);
+                return result;
+            }
+            #[cfg(not(feature = "key_buffer"))]
+            return visit_key(visitor, None, || val.value(self.cx));
+        }
+        self.deserialize_any(visitor)
+    }
+
+    fn deserialize_tuple<V>(self, len: usize, visitor: V) -> Result<V::Value, Self::Error>
+    where
+        V: Visitor<'x>,
+    {
+        if let Some(mut deserializer) =
+            tuple_access(self.cx, self.shared, self.input, len, &visitor)?
+        {
+            return visitor.visit_seq(&mut deserializer);
+        }
+        self.deserialize_any(visitor)
+    }
+
+    fn deserialize_tuple_struct<V>(
+        self,
+        _name: &'static str,
+        len: usize,
+        visitor: V,
+    ) -> Result<V::Value, Self::Error>
+    where
+        V: Visitor<'x>,
+    {
+        self.deserialize_tuple(len, visitor)
+    }
+
+    fn deserialize_f64<V>(self, visitor: V) -> Result<V::Value, Self::Error>
+    where
+        V: Visitor<'x>,
+    {
+        if let Some(v) = self.numeric_string() {
+            return visitor.visit_f64(v);
+        }
+        if let Some(millis) = self.date_millis()? {
+            return visitor.visit_f64(millis);
+        }
+        self.deserialize_any(visitor)
+    }
+
+    fn deserialize_f32<V>(self, visitor: V) -> Result<V::Value, Self::Error>
+    where
+        V: Visitor<'x>,
+    {
+        if let Some(v) = self.numeric_string() {
+            if self.shared.opts.strict_f32 {
+                return visitor.visit_f32(coerce::exact_f32(v)?);
+            }
+            return visitor.visit_f64(v);
+        }
+        let millis = self.date_millis()?;
+        if self.shared.opts.strict_f32 {
+            let number = millis.or_else(|| {
+                let val = self.input.downcast::<JsNumber, C>(self.cx).ok()?;
+                Some(val.value(self.cx))
+            });
+            if let Some(v) = number {
+                return visitor.visit_f32(coerce::exact_f32(v)?);
+            }
+        }
+        if let Some(millis) = millis {
+            return visitor.visit_f64(millis);
+        }
+        self.deserialize_any(visitor)
+    }
+
+    fn is_human_readable(&self) -> bool {
+        self.shared.opts.human_readable
+    }
+
+    deserialize_integer! {
+        deserialize_i8 deserialize_i16 deserialize_i32 deserialize_i64 deserialize_i128
+        deserialize_u8 deserialize_u16 deserialize_u32 deserialize_u64 deserialize_u128
+    }
+
+    serde::forward_to_deserialize_any! {
+       <V: Visitor<'x>>
+        bool char
+        newtype_struct
+    }
+}
+
+#[doc(hidden)]
+struct JsArrayAccess<'a, 'j, C: Context<'j> + 'a> {
+    cx: &'a mut C,
+    shared: &'a Shared<'a>,
+    input: Handle<'j, JsObject>,
+    idx: u32,
+    len: u32,
+    /// Empty elements given after the last one, with `tuple_fill_defaults`
+    padded: u32,
+}
+
+#[doc(hidden)]
+impl<'a, 'j, C: Context<'j>> JsArrayAccess<'a, 'j, C> {
+    fn new(cx: &'a mut C, shared: &'a Shared<'a>, input: Handle<'j, JsArray>) -> Self {
+        let len = input.len(cx);
+        JsArrayAccess::with_len(cx, shared, input.upcast(), len)
+    }
+
+    /// Reads the keys `0` to `len - 1` of any object, like an array
+    fn with_len(
+        cx: &'a mut C,
+        shared: &'a Shared<'a>,
+        input: Handle<'j, JsObject>,
+        len: u32,
+    ) -> Self {
+        JsArrayAccess {
+            cx,
+            shared,
+            input,
+            idx: 0,
+            len,
+            padded: 0,
+        }
+    }
+
+    /// Gives empty elements after the last one, up to `len` elements
+    fn pad_to(&mut self, len: usize) -> LibResult<()> {
+        let len: u32 = num::cast(len).context(errors::CastSnafu)?;
+        self.padded = len.saturating_sub(self.len);
+        Ok(())
+    }
+}
+
+#[doc(hidden)]
+impl<'x, 'j, C: Context<'j>> SeqAccess<'x> for JsArrayAccess<'_, 'j, C> {
+    type Error = LibError;
+
+    fn next_element_seed<T>(&mut self, seed: T) -> LibResult<Option<T::Value>>
+    where
+        T: DeserializeSeed<'x>,
+    {
+        loop {
+            if self.idx >= self.len {
+                if self.padded == 0 {
+                    return Ok(None);
+                }
+                self.padded -= 1;
+                return seed.deserialize(EmptyDeserializer).map(Some);
+            }
+            let idx = self.idx;
+            self.idx += 1;
+
+            let failures = &self.shared.failures;
+            let id = if self.shared.opts.fail_fast {
+                None
+            } else {
+                match failures.next() {
+                    (_, true) => continue,
+                    (id, false) => Some(id),
+                }
+            };
+
+            let v: Handle<JsValue> = self.input.get(self.cx, idx)?;
+            if self.shared.opts.undefined_as_default && v.is_a::<JsUndefined, C>(self.cx) {
+                return seed.deserialize(EmptyDeserializer).map(Some);
+            }
+            let mut de = Deserializer::new(self.cx, self.shared, v);
+            let result = seed.deserialize(&mut de).map(Some);
+            return self
+                .shared
+                .dump_on_error(self.cx, v, result)
+                .inspect_err(|_| {
+                    if let Some(id) = id {
+                        failures.fail(id);
+                    }
+                });
+        }
+    }
+
+    /// Lets collections like `Vec` allocate once, serde caps the hint
+    /// it trusts to about 1MiB of elements
+    fn size_hint(&self) -> Option<usize> {
+        num::cast(self.len - self.idx)
+    }
+}
+
+/// The elements of a tuple of `len` elements given as a positional object with
+/// `numeric_key_objects_as_seq`, or as an array padded with `tuple_fill_defaults`,
+/// `None` to read `value` as usual
+///
+/// Shared by tuples and tuple variants so `ignore_extra_tuple_elements` and
+/// `tuple_fill_defaults` apply to both
+fn tuple_access<'a, 'j, C: Context<'j>>(
+    cx: &'a mut C,
+    shared: &'a Shared<'a>,
+    value: Handle<'j, JsValue>,
+    len: usize,
+    expected: &dyn serde::de::Expected,
+) -> LibResult<Option<JsArrayAccess<'a, 'j, C>>> {
+    let fill = shared.opts.tuple_fill_defaults;
+    if shared.opts.numeric_key_objects_as_seq {
+        if let Some((val, count)) = positional_object(cx, value)? {
+            let found = as_usize(count)?;
+            let extra = found > len && !shared.opts.ignore_extra_tuple_elements;
+            if extra || (found < len && !fill) {
+                return Err(serde::de::Error::invalid_length(found, expected));
+            }
+            let mut access = JsArrayAccess::with_len(cx, shared, val, count);
+            access.pad_to(len)?;
+            return Ok(Some(access));
+        }
+    }
+    if fill {
+        if let Ok(val) = value.downcast::<JsArray, C>(cx) {
+            let mut access = JsArrayAccess::new(cx, shared, val);
+            access.pad_to(len)?;
+            return Ok(Some(access));
+        }
+    }
+    Ok(None)
+}
+
+/// The number of keys of an object keyed `"0"` to `"n - 1"`,
+/// `None` for arrays and objects with other keys
+fn positional_object<'j, C: Context<'j>>(
+    cx: &mut C,
+    value: Handle<'j, JsValue>,
+) -> LibResult<Option<(Handle<'j, JsObject>, u32)>> {
+    if value.is_a::<JsArray, C>(cx) || value.is_a::<JsBuffer, C>(cx) {
+        return Ok(None);
+    }
+    let Ok(object) = value.downcast::<JsObject, C>(cx) else {
+        return Ok(None);
+    };
+    // own integer-like keys are always listed first, in ascending order
+    let keys = object.get_own_property_names(cx)?;
+    let len = keys.len(cx);
+    for i in 0..len {
+        let key: Handle<JsValue> = keys.get(cx, i)?;
+        let Ok(key) = key.downcast::<JsString, C>(cx) else {
+            return Ok(None);
+        };
+        if key.value(cx) != i.to_string() {
+            return Ok(None);
+        }
+    }
+    Ok(Some((object, len)))
+}
+
+/// Reads an array of numbers as bytes, failing with `InvalidByte` rather than truncating
+fn byte_array<'j, C: Context<'j>>(cx: &mut C, array: Handle<'j, JsArray>) -> LibResult<Vec<u8>> {
+    let len = array.len(cx);
+    let mut bytes = Vec::with_capacity(as_usize(len)?);
+    for i in 0..len {
+        let element: Handle<JsValue> = array.get(cx, i)?;
+        let byte = element
+            .downcast::<JsNumber, C>(cx)
+            .ok()
+            .and_then(|n| coerce::byte(n.value(cx)));
+        let Some(byte) = byte else {
+            let value = element.to_string(cx)?.value(cx);
+            return errors::InvalidByteSnafu { value }.fail();
+        };
+        bytes.push(byte);
+    }
+    Ok(bytes)
+}
+
+/// Lends `key` to the visitor with `visit_str`, or gives it the `String` of `owned` when
+/// the name couldn't be read into the key buffer
+fn visit_key<'x, V, F>(visitor: V, key: Option<&str>, owned: F) -> LibResult<V::Value>
+where
+    V: Visitor<'x>,
+    F: FnOnce() -> String,
+{
+    match key {
+        Some(key) => visitor.visit_str(key),
+        None => visitor.visit_string(owned()),
+    }
+}
+
+/// Reads `string` into `buffer`, reusing its allocation, `None` if Node-API fails
+#[cfg(feature = "key_buffer")]
+#[allow(clippy::borrow_as_ptr)]
+fn read_utf8<'b, 'j, C: Context<'j>>(
+    cx: &mut C,
+    string: Handle<'j, JsString>,
+    buffer: &'b mut Vec<u8>,
+) -> Option<&'b str> {
+    use neon::sys::bindings;
+    use std::ptr;
+
+    let env = cx.to_raw();
+    let value = string.to_raw();
+    let mut len = 0;
+    buffer.clear();
+    // SAFETY: `value` is a string of `env`, the first call only writes its length and
+    // the second at most `capacity` bytes, nul terminator included, into `buffer`
+    unsafe {
+        bindings::get_value_string_utf8(env, value, ptr::null_mut(), 0, &mut len).ok()?;
+        buffer.reserve(len + 1);
+        let capacity = buffer.capacity();
+        let out = buffer.as_mut_ptr().cast();
+        bindings::get_value_string_utf8(env, value, out, capacity, &mut len).ok()?;
+        buffer.set_len(len);
+    }
+    std::str::from_utf8(buffer).ok()
+}
+
+/// The own string keys of `object`, for `require_all_fields`
+fn own_keys<'j, C: Context<'j>>(
+    cx: &mut C,
+    object: Handle<'j, JsObject>,
+) -> LibResult<BTreeSet<String>> {
+    let keys = object.get_own_property_names(cx)?.to_vec(cx)?;
+    let mut present = BTreeSet::new();
+    for key in keys {
+        if let Ok(key) = key.downcast::<JsString, C>(cx) {
+            present.insert(key.value(cx));
+        }
+    }
+    Ok(present)
+}
+
+/// Wraps the visitor of a struct for `require_all_fields`, so its map fails with a
+/// missing field error before its first key if a field has none of its names set
+struct RequireFields<V> {
+    inner: V,
+    fields: &'static [&'static str],
+    present: BTreeSet<String>,
+}
+
+impl<'x, V: Visitor<'x>> Visitor<'x> for RequireFields<V> {
+    type Value = V::Value;
+
+    fn expecting(&self, formatter: &mut std::fmt::Formatter) -> std::fmt::Result {
+        self.inner.expecting(formatter)
+    }
+
+    fn visit_map<A>(self, map: A) -> Result<Self::Value, A::Error>
+    where
+        A: MapAccess<'x>,
+    {
+        self.inner.visit_map(RequireFieldsAccess {
+            inner: map,
+            fields: self.fields,
+            present: Some(self.present),
+        })
+    }
+
+    fn visit_seq<A>(self, seq: A) -> Result<Self::Value, A::Error>
+    where
+        A: SeqAccess<'x>,
+    {
+        self.inner.visit_seq(seq)
+    }
+
+    fn visit_str<E: serde::de::Error>(self, v: &str) -> Result<Self::Value, E> {
+        self.inner.visit_str(v)
+    }
+
+    fn visit_string<E: serde::de::Error>(self, v: String) -> Result<Self::Value, E> {
+        self.inner.visit_string(v)
+    }
+
+    fn visit_f64<E: serde::de::Error>(self, v: f64) -> Result<Self::Value, E> {
+        self.inner.visit_f64(v)
+    }
+
+    fn visit_unit<E: serde::de::Error>(self) -> Result<Self::Value, E> {
+        self.inner.visit_unit()
+    }
+}
+
+/// The map of a [`RequireFields`] visitor
+struct RequireFieldsAccess<A> {
+    inner: A,
+    fields: &'static [&'static str],
+    /// The keys set, until the fields are checked
+    present: Option<BTreeSet<String>>,
+}
+
+impl<'x, A: MapAccess<'x>> RequireFieldsAccess<A> {
+    /// Fails with a missing field error for the first field that has none of its names set
+    ///
+    /// `fields` lists every name of a field, `#[serde(alias)]` ones included, without saying
+    /// which go together. Derived structs read their keys with `next_key`, so each name is
+    /// read as the key type and the names read as the same field make up its names
+    fn require<K: Deserialize<'x>>(&mut self) -> Result<(), A::Error> {
+        let Some(present) = self.present.take() else {
+            return Ok(());
+        };
+        let mut groups: Vec<(Option<std::mem::Discriminant<K>>, &'static str, bool)> = vec![];
+        for &name in self.fields {
+            let key = K::deserialize(IntoDeserializer::<LibError>::into_deserializer(name)).ok();
+            let group = key.as_ref().map(std::mem::discriminant);
+            let set = present.contains(name);
+            match groups
+                .iter_mut()
+                .find(|(g, ..)| group.is_some() && *g == group)
+            {
+                Some((_, _, any_set)) => *any_set |= set,
+                None => groups.push((group, name, set)),
+            }
+        }
+        match groups.into_iter().find(|(.., set)| !set) {
+            Some((_, name, _)) => Err(serde::de::Error::missing_field(name)),
+            None => Ok(()),
+        }
+    }
+}
+
+impl<'x, A: MapAccess<'x>> MapAccess<'x> for RequireFieldsAccess<A> {
+    type Error = A::Error;
+
+    fn next_key<K>(&mut self) -> Result<Option<K>, Self::Error>
     where
-        V: Visitor<'x>,
+        K: Deserialize<'x>,
     {
-        let buff = self
-            .input
-            .downcast::<JsBuffer, C>(self.cx)
-            .or_throw(self.cx)?;
-        let copy = Vec::from(buff.as_slice(self.cx));
-        visitor.visit_bytes(&copy)
+        self.require::<K>()?;
+        self.inner.next_key()
     }
 
-    fn deserialize_byte_buf<V>(self, visitor: V) -> Result<V::Value, Self::Error>
+    fn next_key_seed<K>(&mut self, seed: K) -> Result<Option<K::Value>, Self::Error>
     where
-        V: Visitor<'x>,
+        K: DeserializeSeed<'x>,
     {
-        let buff = self
-            .input
-            .downcast::<JsBuffer, C>(self.cx)
-            .or_throw(self.cx)?;
-        let copy = Vec::from(buff.as_slice(self.cx));
-        visitor.visit_byte_buf(copy)
+        // Without the key type the names can't be told apart, so each must be set
+        if let Some(present) = self.present.take() {
+            if let Some(name) = self.fields.iter().find(|name| !present.contains(**name)) {
+                return Err(serde::de::Error::missing_field(name));
+            }
+        }
+        self.inner.next_key_seed(seed)
     }
 
-    fn deserialize_ignored_any<V>(self, visitor: V) -> Result<V::Value, Self::Error>
+    fn next_value_seed<V>(&mut self, seed: V) -> Result<V::Value, Self::Error>
     where
-        V: Visitor<'x>,
+        V: DeserializeSeed<'x>,
     {
-        visitor.visit_unit()
+        self.inner.next_value_seed(seed)
     }
 
-    serde::forward_to_deserialize_any! {
-       <V: Visitor<'x>>
-        bool i8 i16 i32 i64 i128 u8 u16 u32 u64 u128 f32 f64 char str string
-        unit unit_struct seq tuple tuple_struct map struct identifier
-        newtype_struct
+    fn size_hint(&self) -> Option<usize> {
+        self.inner.size_hint()
+    }
+}
+
+/// A copy of `object` with its properties that aren't `fields` moved to an object
+/// set as the field `into`, for `capture_unknown_into`
+fn capture_unknown<'j, C: Context<'j>>(
+    cx: &mut C,
+    object: Handle<'j, JsObject>,
+    fields: &[&'static str],
+    into: &'static str,
+) -> LibResult<Handle<'j, JsObject>> {
+    ensure!(
+        fields.contains(&into),
+        errors::MissingCaptureFieldSnafu { field: into }
+    );
+    let known = cx.empty_object();
+    let unknown = cx.empty_object();
+    let keys = object.get_own_property_names(cx)?.to_vec(cx)?;
+    for key in keys {
+        let name = key.to_string(cx)?.value(cx);
+        let value: Handle<JsValue> = object.get(cx, key)?;
+        let target = if name != into && fields.contains(&name.as_str()) {
+            known
+        } else {
+            unknown
+        };
+        if name == "__proto__" {
+            // setting it would change the prototype
+            template::define_own(cx, target, &name, value)?;
+        } else {
+            target.set(cx, key, value)?;
+        }
+    }
+    known.set(cx, into, unknown)?;
+    Ok(known)
+}
+
+fn as_usize(n: u32) -> LibResult<usize> {
+    num::cast(n).context(errors::CastSnafu)
+}
+
+/// Whether `value` is a `Map`, going by `Object.prototype.toString` so it works across realms
+fn is_map<'j, C: Context<'j>>(cx: &mut C, value: Handle<'j, JsValue>) -> LibResult<bool> {
+    if !value.is_a::<JsObject, C>(cx) {
+        return Ok(false);
     }
+    let object: Handle<JsFunction> = cx.global("Object")?;
+    let prototype: Handle<JsObject> = object.get(cx, "prototype")?;
+    let to_string: Handle<JsFunction> = prototype.get(cx, "toString")?;
+    let tag: Handle<JsString> = to_string.call_with(cx).this(value).apply(cx)?;
+    Ok(tag.value(cx) == "[object Map]")
+}
+
+/// Calls `value[Symbol.iterator]()`, `None` if `value` isn't an iterable object
+fn iterator_of<'j, C: Context<'j>>(
+    cx: &mut C,
+    value: Handle<'j, JsValue>,
+) -> LibResult<Option<Handle<'j, JsObject>>> {
+    let Ok(object) = value.downcast::<JsObject, C>(cx) else {
+        return Ok(None);
+    };
+    let symbol: Handle<JsFunction> = cx.global("Symbol")?;
+    let key: Handle<JsValue> = symbol.get(cx, "iterator")?;
+    let Ok(method) = object.get_value(cx, key)?.downcast::<JsFunction, C>(cx) else {
+        return Ok(None);
+    };
+    let iterator = method.call_with(cx).this(object).apply(cx)?;
+    Ok(Some(iterator))
 }
 
 #[doc(hidden)]
-struct JsArrayAccess<'a, 'j, C: Context<'j> + 'a> {
+struct JsIteratorAccess<'a, 'j, C: Context<'j> + 'a> {
     cx: &'a mut C,
-    input: Handle<'j, JsArray>,
-    idx: u32,
-    len: u32,
+    shared: &'a Shared<'a>,
+    iterator: Handle<'j, JsObject>,
+    next: Handle<'j, JsFunction>,
 }
 
 #[doc(hidden)]
-impl<'a, 'j, C: Context<'j>> JsArrayAccess<'a, 'j, C> {
-    fn new(cx: &'a mut C, input: Handle<'j, JsArray>) -> Self {
-        let len = input.len(cx);
-        JsArrayAccess {
+impl<'a, 'j, C: Context<'j>> JsIteratorAccess<'a, 'j, C> {
+    fn new(
+        cx: &'a mut C,
+        shared: &'a Shared<'a>,
+        iterator: Handle<'j, JsObject>,
+    ) -> LibResult<Self> {
+        let next = iterator.get(cx, "next")?;
+        Ok(JsIteratorAccess {
             cx,
-            input,
-            idx: 0,
-            len,
-        }
+            shared,
+            iterator,
+            next,
+        })
     }
 }
 
 #[doc(hidden)]
-impl<'x, 'a, 'j, C: Context<'j>> SeqAccess<'x> for JsArrayAccess<'a, 'j, C> {
+impl<'x, 'j, C: Context<'j>> SeqAccess<'x> for JsIteratorAccess<'_, 'j, C> {
     type Error = LibError;
 
     fn next_element_seed<T>(&mut self, seed: T) -> LibResult<Option<T::Value>>
     where
         T: DeserializeSeed<'x>,
     {
-        if self.idx >= self.len {
-            return Ok(None);
+        let result: Handle<JsObject> = self
+            .next
+            .call_with(self.cx)
+            .this(self.iterator)
+            .apply(self.cx)?;
+        let done = result.get_value(self.cx, "done")?;
+        if let Ok(done) = done.downcast::<JsBoolean, C>(self.cx) {
+            if done.value(self.cx) {
+                return Ok(None);
+            }
         }
-        let v = self.input.get(self.cx, self.idx)?;
-        self.idx += 1;
+        let v = result.get(self.cx, "value")?;
 
-        let mut de = Deserializer::new(self.cx, v);
-        seed.deserialize(&mut de).map(Some)
+        let mut de = Deserializer::new(self.cx, self.shared, v);
+        let result = seed.deserialize(&mut de).map(Some);
+        self.shared.dump_on_error(self.cx, v, result)
     }
 }
 
+/// Iterates the own enumerable properties of an object, in the order of `Reflect.ownKeys`
+///
+/// Keys come from `getOwnPropertyNames` so an own `__proto__` or `constructor`
+/// key (e.g. from `JSON.parse`) is read back as plain data, the prototype is never walked
 #[doc(hidden)]
 struct JsObjectAccess<'a, 'j, C: Context<'j> + 'a> {
     cx: &'a mut C,
+    shared: &'a Shared<'a>,
     input: Handle<'j, JsObject>,
     prop_names: Handle<'j, JsArray>,
     idx: u32,
     len: u32,
+    /// Skip keys set to `null`, for structs with `null_as_default`
+    skip_nulls: bool,
+    /// Value already read by `next_key_seed` when skipping nulls
+    value: Option<Handle<'j, JsValue>>,
 }
 
 #[doc(hidden)]
 impl<'a, 'j, C: Context<'j>> JsObjectAccess<'a, 'j, C> {
-    fn new(cx: &'a mut C, input: Handle<'j, JsObject>) -> LibResult<Self> {
+    fn new(cx: &'a mut C, shared: &'a Shared<'a>, input: Handle<'j, JsObject>) -> LibResult<Self> {
         let prop_names = input.get_own_property_names(cx)?;
         let len = prop_names.len(cx);
 
         Ok(JsObjectAccess {
             cx,
+            shared,
             input,
             prop_names,
             idx: 0,
             len,
+            skip_nulls: false,
+            value: None,
         })
     }
+
+    /// Like `new` but honouring `null_as_default`
+    fn for_struct(
+        cx: &'a mut C,
+        shared: &'a Shared<'a>,
+        input: Handle<'j, JsObject>,
+    ) -> LibResult<Self> {
+        let mut access = JsObjectAccess::new(cx, shared, input)?;
+        access.skip_nulls = shared.opts.null_as_default;
+        Ok(access)
+    }
 }
 
 #[doc(hidden)]
-impl<'x, 'a, 'j, C: Context<'j>> MapAccess<'x> for JsObjectAccess<'a, 'j, C> {
+impl<'x, 'j, C: Context<'j>> MapAccess<'x> for JsObjectAccess<'_, 'j, C> {
     type Error = LibError;
 
     fn next_key_seed<K>(&mut self, seed: K) -> Result<Option<K::Value>, Self::Error>
     where
         K: DeserializeSeed<'x>,
     {
-        if self.idx >= self.len {
-            return Ok(None);
-        }
-
-        let prop_name = self.prop_names.get(self.cx, self.idx)?;
+        while self.idx < self.len {
+            let prop_name = self.prop_names.get(self.cx, self.idx)?;
+            if self.skip_nulls {
+                let value: Handle<JsValue> = self.input.get(self.cx, prop_name)?;
+                if value.is_a::<JsNull, C>(self.cx) {
+                    self.idx += 1;
+                    continue;
+                }
+                self.value = Some(value);
+            }
 
-        let mut de = Deserializer::new(self.cx, prop_name);
-        seed.deserialize(&mut de).map(Some)
+            let mut de = Deserializer::new(self.cx, self.shared, prop_name);
+            return seed.deserialize(&mut de).map(Some);
+        }
+        Ok(None)
     }
 
     fn next_value_seed<V>(&mut self, seed: V) -> Result<V::Value, Self::Error>
@@ -294,30 +1995,212 @@ impl<'x, 'a, 'j, C: Context<'j>> MapAccess<'x> for JsObjectAccess<'a, 'j, C> {
             }
         );
 
-        let prop_name: Handle<'j, neon::prelude::JsValue> =
-            self.prop_names.get(self.cx, self.idx)?;
+        let value = if let Some(value) = self.value.take() {
+            value
+        } else {
+            let prop_name: Handle<'j, neon::prelude::JsValue> =
+                self.prop_names.get(self.cx, self.idx)?;
+            self.input.get(self.cx, prop_name)?
+        };
+
+        self.idx += 1;
+        let mut de = Deserializer::new(self.cx, self.shared, value);
+        let result = seed.deserialize(&mut de);
+        self.shared.dump_on_error(self.cx, value, result)
+    }
+
+    fn size_hint(&self) -> Option<usize> {
+        num::cast(self.len - self.idx)
+    }
+}
+
+/// Reads the fields of a struct by name, skipping the object's other keys
+#[doc(hidden)]
+struct JsStructAccess<'a, 'j, C: Context<'j> + 'a> {
+    cx: &'a mut C,
+    shared: &'a Shared<'a>,
+    input: Handle<'j, JsObject>,
+    has_own: Handle<'j, JsFunction>,
+    fields: &'static [&'static str],
+    value: Option<Handle<'j, JsValue>>,
+    idx: u32,
+    len: u32,
+}
+
+#[doc(hidden)]
+impl<'a, 'j, C: Context<'j>> JsStructAccess<'a, 'j, C> {
+    fn new(
+        cx: &'a mut C,
+        shared: &'a Shared<'a>,
+        input: Handle<'j, JsObject>,
+        fields: &'static [&'static str],
+    ) -> LibResult<Self> {
+        // `Object.prototype.hasOwnProperty` so inherited properties like
+        // `constructor` aren't read as fields, even from null prototype objects
+        let object: Handle<JsFunction> = cx.global("Object")?;
+        let prototype: Handle<JsObject> = object.get(cx, "prototype")?;
+        let has_own = prototype.get(cx, "hasOwnProperty")?;
+        let len = num::cast(fields.len()).context(errors::CastSnafu)?;
+
+        Ok(JsStructAccess {
+            cx,
+            shared,
+            input,
+            has_own,
+            fields,
+            value: None,
+            idx: 0,
+            len,
+        })
+    }
+}
+
+#[doc(hidden)]
+impl<'x, 'j, C: Context<'j>> MapAccess<'x> for JsStructAccess<'_, 'j, C> {
+    type Error = LibError;
+
+    fn next_key_seed<K>(&mut self, seed: K) -> Result<Option<K::Value>, Self::Error>
+    where
+        K: DeserializeSeed<'x>,
+    {
+        use serde::de::IntoDeserializer;
+
+        while self.idx < self.len {
+            let field = self.fields[self.idx as usize];
+            self.idx += 1;
+
+            let value: Handle<JsValue> = self.input.get_value(self.cx, field)?;
+            if value.is_a::<JsUndefined, C>(self.cx)
+                || (self.shared.opts.null_as_default && value.is_a::<JsNull, C>(self.cx))
+            {
+                continue;
+            }
+            let own: Handle<JsBoolean> = self
+                .has_own
+                .call_with(self.cx)
+                .this(self.input)
+                .arg(self.cx.string(field))
+                .apply(self.cx)?;
+            if !own.value(self.cx) {
+                continue;
+            }
+
+            self.value = Some(value);
+            return seed.deserialize(field.into_deserializer()).map(Some);
+        }
+        Ok(None)
+    }
+
+    fn next_value_seed<V>(&mut self, seed: V) -> Result<V::Value, Self::Error>
+    where
+        V: DeserializeSeed<'x>,
+    {
+        let value = self
+            .value
+            .take()
+            .context(errors::ArrayIndexOutOfBoundsSnafu {
+                length: self.len,
+                index: self.idx,
+            })?;
+
+        let mut de = Deserializer::new(self.cx, self.shared, value);
+        let result = seed.deserialize(&mut de);
+        self.shared.dump_on_error(self.cx, value, result)
+    }
+}
+
+/// Reads an array of `[key, value]` arrays as a map
+#[doc(hidden)]
+struct JsPairArrayAccess<'a, 'j, C: Context<'j> + 'a> {
+    cx: &'a mut C,
+    shared: &'a Shared<'a>,
+    input: Handle<'j, JsArray>,
+    value: Option<Handle<'j, JsValue>>,
+    idx: u32,
+    len: u32,
+}
+
+#[doc(hidden)]
+impl<'a, 'j, C: Context<'j>> JsPairArrayAccess<'a, 'j, C> {
+    fn new(cx: &'a mut C, shared: &'a Shared<'a>, input: Handle<'j, JsArray>) -> Self {
+        let len = input.len(cx);
+        JsPairArrayAccess {
+            cx,
+            shared,
+            input,
+            value: None,
+            idx: 0,
+            len,
+        }
+    }
+}
+
+#[doc(hidden)]
+impl<'x, 'j, C: Context<'j>> MapAccess<'x> for JsPairArrayAccess<'_, 'j, C> {
+    type Error = LibError;
+
+    fn next_key_seed<K>(&mut self, seed: K) -> Result<Option<K::Value>, Self::Error>
+    where
+        K: DeserializeSeed<'x>,
+    {
+        if self.idx >= self.len {
+            return Ok(None);
+        }
+
+        let pair: Handle<JsValue> = self.input.get(self.cx, self.idx)?;
+        let pair = match pair.downcast::<JsArray, C>(self.cx) {
+            Ok(pair) if pair.len(self.cx) == 2 => pair,
+            _ => return errors::InvalidPairSnafu { index: self.idx }.fail(),
+        };
+        let key = pair.get(self.cx, 0)?;
+        self.value = Some(pair.get(self.cx, 1)?);
+
+        let mut de = Deserializer::new(self.cx, self.shared, key);
+        seed.deserialize(&mut de).map(Some)
+    }
 
-        let value = self.input.get(self.cx, prop_name)?;
+    fn next_value_seed<V>(&mut self, seed: V) -> Result<V::Value, Self::Error>
+    where
+        V: DeserializeSeed<'x>,
+    {
+        let value = self
+            .value
+            .take()
+            .context(errors::ArrayIndexOutOfBoundsSnafu {
+                length: self.len,
+                index: self.idx,
+            })?;
 
         self.idx += 1;
-        let mut de = Deserializer::new(self.cx, value);
-        let res = seed.deserialize(&mut de)?;
-        Ok(res)
+        let mut de = Deserializer::new(self.cx, self.shared, value);
+        let result = seed.deserialize(&mut de);
+        self.shared.dump_on_error(self.cx, value, result)
+    }
+
+    fn size_hint(&self) -> Option<usize> {
+        num::cast(self.len - self.idx)
     }
 }
 
 #[doc(hidden)]
 struct JsEnumAccess<'a, 'j, C: Context<'j> + 'a> {
     cx: &'a mut C,
+    shared: &'a Shared<'a>,
     variant: String,
     value: Option<Handle<'j, JsValue>>,
 }
 
 #[doc(hidden)]
 impl<'a, 'j, C: Context<'j>> JsEnumAccess<'a, 'j, C> {
-    fn new(cx: &'a mut C, key: String, value: Option<Handle<'j, JsValue>>) -> Self {
+    fn new(
+        cx: &'a mut C,
+        shared: &'a Shared<'a>,
+        key: String,
+        value: Option<Handle<'j, JsValue>>,
+    ) -> Self {
         JsEnumAccess {
             cx,
+            shared,
             variant: key,
             value,
         }
@@ -335,7 +2218,7 @@ impl<'x, 'a, 'j, C: Context<'j>> EnumAccess<'x> for JsEnumAccess<'a, 'j, C> {
     {
         use serde::de::IntoDeserializer;
         let variant = self.variant.into_deserializer();
-        let variant_access = JsVariantAccess::new(self.cx, self.value);
+        let variant_access = JsVariantAccess::new(self.cx, self.shared, self.value);
         seed.deserialize(variant).map(|v| (v, variant_access))
     }
 }
@@ -343,24 +2226,32 @@ impl<'x, 'a, 'j, C: Context<'j>> EnumAccess<'x> for JsEnumAccess<'a, 'j, C> {
 #[doc(hidden)]
 struct JsVariantAccess<'a, 'j, C: Context<'j> + 'a> {
     cx: &'a mut C,
+    shared: &'a Shared<'a>,
     value: Option<Handle<'j, JsValue>>,
 }
 
 #[doc(hidden)]
 impl<'a, 'j, C: Context<'j>> JsVariantAccess<'a, 'j, C> {
-    fn new(cx: &'a mut C, value: Option<Handle<'j, JsValue>>) -> Self {
-        JsVariantAccess { cx, value }
+    fn new(cx: &'a mut C, shared: &'a Shared<'a>, value: Option<Handle<'j, JsValue>>) -> Self {
+        JsVariantAccess { cx, shared, value }
     }
 }
 
 #[doc(hidden)]
-impl<'x, 'a, 'j, C: Context<'j>> VariantAccess<'x> for JsVariantAccess<'a, 'j, C> {
+impl<'x, 'j, C: Context<'j>> VariantAccess<'x> for JsVariantAccess<'_, 'j, C> {
     type Error = LibError;
 
     fn unit_variant(self) -> Result<(), Self::Error> {
+        if matches!(
+            self.shared.opts.enum_style,
+            EnumStyle::InternallyTagged { .. }
+        ) {
+            // the value is the object holding the tag
+            return Ok(());
+        }
         match self.value {
             Some(val) => {
-                let mut deserializer = Deserializer::new(self.cx, val);
+                let mut deserializer = Deserializer::new(self.cx, self.shared, val);
                 serde::de::Deserialize::deserialize(&mut deserializer)
             }
             None => Ok(()),
@@ -373,7 +2264,7 @@ impl<'x, 'a, 'j, C: Context<'j>> VariantAccess<'x> for JsVariantAccess<'a, 'j, C
     {
         match self.value {
             Some(val) => {
-                let mut deserializer = Deserializer::new(self.cx, val);
+                let mut deserializer = Deserializer::new(self.cx, self.shared, val);
                 seed.deserialize(&mut deserializer)
             }
             None => Err(serde::de::Error::invalid_type(
@@ -383,14 +2274,19 @@ impl<'x, 'a, 'j, C: Context<'j>> VariantAccess<'x> for JsVariantAccess<'a, 'j, C
         }
     }
 
-    fn tuple_variant<V>(self, _len: usize, visitor: V) -> Result<V::Value, Self::Error>
+    fn tuple_variant<V>(self, len: usize, visitor: V) -> Result<V::Value, Self::Error>
     where
         V: Visitor<'x>,
     {
         match self.value {
             Some(handle) => {
+                if let Some(mut deserializer) =
+                    tuple_access(self.cx, self.shared, handle, len, &visitor)?
+                {
+                    return visitor.visit_seq(&mut deserializer);
+                }
                 if let Ok(val) = handle.downcast::<JsArray, C>(self.cx) {
-                    let mut deserializer = JsArrayAccess::new(self.cx, val);
+                    let mut deserializer = JsArrayAccess::new(self.cx, self.shared, val);
                     visitor.visit_seq(&mut deserializer)
                 } else {
                     Err(serde::de::Error::invalid_type(
@@ -408,7 +2304,7 @@ impl<'x, 'a, 'j, C: Context<'j>> VariantAccess<'x> for JsVariantAccess<'a, 'j, C
 
     fn struct_variant<V>(
         self,
-        _fields: &'static [&'static str],
+        fields: &'static [&'static str],
         visitor: V,
     ) -> Result<V::Value, Self::Error>
     where
@@ -417,7 +2313,13 @@ impl<'x, 'a, 'j, C: Context<'j>> VariantAccess<'x> for JsVariantAccess<'a, 'j, C
         match self.value {
             Some(handle) => {
                 if let Ok(val) = handle.downcast::<JsObject, C>(self.cx) {
-                    let mut deserializer = JsObjectAccess::new(self.cx, val)?;
+                    if let EnumStyle::InternallyTagged { .. } = self.shared.opts.enum_style {
+                        // skips the tag, even with `#[serde(deny_unknown_fields)]`
+                        let mut deserializer =
+                            JsStructAccess::new(self.cx, self.shared, val, fields)?;
+                        return visitor.visit_map(&mut deserializer);
+                    }
+                    let mut deserializer = JsObjectAccess::for_struct(self.cx, self.shared, val)?;
                     visitor.visit_map(&mut deserializer)
                 } else {
                     Err(serde::de::Error::invalid_type(
@@ -433,3 +2335,170 @@ impl<'x, 'a, 'j, C: Context<'j>> VariantAccess<'x> for JsVariantAccess<'a, 'j, C
         }
     }
 }
+
+/// Reads the empty value of the type asked for, a missing tuple element with `tuple_fill_defaults`
+#[derive(Clone, Copy)]
+struct EmptyDeserializer;
+
+impl IntoDeserializer<'_, LibError> for EmptyDeserializer {
+    type Deserializer = Self;
+
+    fn into_deserializer(self) -> Self {
+        self
+    }
+}
+
+macro_rules! deserialize_empty {
+    ($($method:ident => $visit:ident($($value:expr)?)),* $(,)?) => {$(
+        fn $method<V>(self, visitor: V) -> Result<V::Value, Self::Error>
+        where
+            V: Visitor<'x>,
+        {
+            visitor.$visit($($value)?)
+        }
+    )*};
+}
+
+impl<'x> serde::de::Deserializer<'x> for EmptyDeserializer {
+    type Error = LibError;
+
+    deserialize_empty!(
+        deserialize_any => visit_unit(),
+        deserialize_bool => visit_bool(false),
+        deserialize_i8 => visit_i8(0),
+        deserialize_i16 => visit_i16(0),
+        deserialize_i32 => visit_i32(0),
+        deserialize_i64 => visit_i64(0),
+        deserialize_i128 => visit_i128(0),
+        deserialize_u8 => visit_u8(0),
+        deserialize_u16 => visit_u16(0),
+        deserialize_u32 => visit_u32(0),
+        deserialize_u64 => visit_u64(0),
+        deserialize_u128 => visit_u128(0),
+        deserialize_f32 => visit_f32(0.0),
+        deserialize_f64 => visit_f64(0.0),
+        deserialize_char => visit_char('\0'),
+        deserialize_str => visit_str(""),
+        deserialize_string => visit_str(""),
+        deserialize_bytes => visit_bytes(&[]),
+        deserialize_byte_buf => visit_bytes(&[]),
+        deserialize_option => visit_none(),
+        deserialize_unit => visit_unit(),
+        deserialize_ignored_any => visit_unit(),
+    );
+
+    fn deserialize_unit_struct<V>(self, _name: &'static str, visitor: V) -> LibResult<V::Value>
+    where
+        V: Visitor<'x>,
+    {
+        visitor.visit_unit()
+    }
+
+    fn deserialize_newtype_struct<V>(self, _name: &'static str, visitor: V) -> LibResult<V::Value>
+    where
+        V: Visitor<'x>,
+    {
+        visitor.visit_newtype_struct(self)
+    }
+
+    fn deserialize_seq<V>(self, visitor: V) -> LibResult<V::Value>
+    where
+        V: Visitor<'x>,
+    {
+        visitor.visit_seq(SeqDeserializer::new(std::iter::empty::<Self>()))
+    }
+
+    fn deserialize_tuple<V>(self, len: usize, visitor: V) -> LibResult<V::Value>
+    where
+        V: Visitor<'x>,
+    {
+        visitor.visit_seq(SeqDeserializer::new(std::iter::repeat_n(self, len)))
+    }
+
+    fn deserialize_tuple_struct<V>(
+        self,
+        _name: &'static str,
+        len: usize,
+        visitor: V,
+    ) -> LibResult<V::Value>
+    where
+        V: Visitor<'x>,
+    {
+        self.deserialize_tuple(len, visitor)
+    }
+
+    fn deserialize_map<V>(self, visitor: V) -> LibResult<V::Value>
+    where
+        V: Visitor<'x>,
+    {
+        visitor.visit_map(MapDeserializer::new(std::iter::empty::<(Self, Self)>()))
+    }
+
+    fn deserialize_struct<V>(
+        self,
+        _name: &'static str,
+        _fields: &'static [&'static str],
+        visitor: V,
+    ) -> LibResult<V::Value>
+    where
+        V: Visitor<'x>,
+    {
+        self.deserialize_map(visitor)
+    }
+
+    fn deserialize_enum<V>(
+        self,
+        _name: &'static str,
+        _variants: &'static [&'static str],
+        visitor: V,
+    ) -> LibResult<V::Value>
+    where
+        V: Visitor<'x>,
+    {
+        Err(serde::de::Error::invalid_type(
+            Unexpected::Other("missing tuple element"),
+            &visitor,
+        ))
+    }
+
+    fn deserialize_identifier<V>(self, visitor: V) -> LibResult<V::Value>
+    where
+        V: Visitor<'x>,
+    {
+        self.deserialize_enum("", &[], visitor)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::visit_key;
+    use serde::de::{Error, Visitor};
+    use std::fmt;
+
+    /// Whether the key was lent with `visit_str` or given with `visit_string`
+    struct KeyKind;
+
+    impl Visitor<'_> for KeyKind {
+        type Value = (&'static str, String);
+
+        fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+            formatter.write_str("a key")
+        }
+
+        fn visit_str<E: Error>(self, v: &str) -> Result<Self::Value, E> {
+            Ok(("borrowed", v.to_owned()))
+        }
+
+        fn visit_string<E: Error>(self, v: String) -> Result<Self::Value, E> {
+            Ok(("owned", v))
+        }
+    }
+
+    #[test]
+    fn test_visit_key() {
+        let borrowed = visit_key(KeyKind, Some("a"), || unreachable!()).unwrap();
+        assert_eq!(borrowed, ("borrowed", "a".to_owned()));
+        let owned = visit_key(KeyKind, None, || "b".to_owned()).unwrap();
+        assert_eq!(owned, ("owned", "b".to_owned()));
+    }
+}
@@ -1,20 +1,24 @@
 //! Defines error handling types used by the create
 //! uses the `snafu` crate for generation
 
-use neon::result::{JsResult, Throw};
+use neon::result::Throw;
 use serde::{de, ser};
 use snafu::{Backtrace, Snafu};
-use std::{convert::From, fmt::Display};
+use std::{borrow::Cow, convert::From, fmt::Display};
 
 #[derive(Debug, Snafu)]
 #[snafu(visibility(pub(crate)))]
 pub enum Error {
-    /// NodeJS has a hardcoded limit on string length
+    /// `NodeJS` has a hardcoded limit on string length
     ///
     /// Trying to serialize a string that is too long will result in an error
     #[snafu(display("String too long for NodeJS, len: {len}"))]
     StringTooLong { len: usize, backtrace: Backtrace },
 
+    /// A string read back with `validate_output_strings` differs from the one serialized
+    #[snafu(display("String of {len} bytes changed converting to a JS string"))]
+    StringMismatch { len: usize, backtrace: Backtrace },
+
     /// Unable to coerce type
     ///
     /// When deserializing to a boolean, valid inputs are:
@@ -59,6 +63,106 @@ pub enum Error {
         backtrace: Backtrace,
     },
 
+    /// An element of an array read as a map with `pair_arrays_as_maps`
+    /// isn't a `[key, value]` array
+    #[snafu(display("Expected a [key, value] pair at index {index}"))]
+    InvalidPair { index: u32, backtrace: Backtrace },
+
+    /// An enum tag read with `case_insensitive_variants` matches more than one variant
+    #[snafu(display("Variant '{variant}' matches more than one of {variants:?} ignoring case"))]
+    AmbiguousVariant {
+        variant: String,
+        variants: &'static [&'static str],
+        backtrace: Backtrace,
+    },
+
+    /// An enum tag isn't one of `DeserializeOptions::expected_variants`
+    #[snafu(display("Unexpected variant '{got}', expected one of {expected:?}"))]
+    UnexpectedVariant {
+        got: String,
+        expected: Vec<String>,
+        backtrace: Backtrace,
+    },
+
+    /// A number read as an `f32` with `strict_f32` isn't exactly representable
+    #[snafu(display("{value} can't be represented exactly as an f32"))]
+    PrecisionLoss { value: f64, backtrace: Backtrace },
+
+    /// A property name isn't ASCII, with `NonAsciiKeyPolicy::Reject`
+    #[snafu(display("Property name '{key}' isn't ASCII"))]
+    NonAsciiKey { key: String, backtrace: Backtrace },
+
+    /// A `Date` read as a string with `date_to_iso_string`, or as a number with
+    /// `dates_as_millis`, is an invalid date
+    #[snafu(display("Invalid Date can't be read as a string or a number"))]
+    InvalidDate { backtrace: Backtrace },
+
+    /// An element of an array read as bytes isn't an integer from 0 to 255
+    #[snafu(display("Invalid byte {value}, expected an integer from 0 to 255"))]
+    InvalidByte { value: String, backtrace: Backtrace },
+
+    /// A `BigInt` doesn't fit in any of the integer types, up to `i128` and `u128`
+    #[snafu(display("BigInt {value} doesn't fit in 128 bits"))]
+    BigIntTooWide { value: String, backtrace: Backtrace },
+
+    /// No function is registered for the `type` of an object read with `from_value_dynamic`
+    #[snafu(display("No deserializer is registered for type '{discriminant}'"))]
+    UnknownDiscriminant {
+        discriminant: String,
+        backtrace: Backtrace,
+    },
+
+    /// A field set by `to_instance` has the name of a method of the instance
+    #[snafu(display("Field '{key}' would shadow the method of the same name"))]
+    ShadowsMethod { key: String, backtrace: Backtrace },
+
+    /// A tuple variant, or a newtype variant not holding a struct or map,
+    /// with `EnumStyle::InternallyTagged`
+    #[snafu(display("Variant '{variant}' must hold a struct or map to be internally tagged"))]
+    InternallyTaggedContent {
+        variant: &'static str,
+        backtrace: Backtrace,
+    },
+
+    /// Arrays and objects are nested past `max_depth`, with `DepthPolicy::Error`
+    #[snafu(display("Serialized value is nested deeper than {max_depth} levels"))]
+    DepthExceeded {
+        max_depth: usize,
+        backtrace: Backtrace,
+    },
+
+    /// A struct read with `capture_unknown_into` has no field of that name
+    #[snafu(display("Struct has no field '{field}' to capture unknown properties into"))]
+    MissingCaptureField {
+        field: &'static str,
+        backtrace: Backtrace,
+    },
+
+    /// An `ArrayBuffer` read with `reinterpret_buffers` isn't a whole number of `f64`s
+    #[snafu(display("ArrayBuffer of {len} bytes isn't a multiple of 8 bytes long"))]
+    BufferLength { len: usize, backtrace: Backtrace },
+
+    /// A value of `to_query_params` is an object or array, with `NestedParamPolicy::Reject`
+    #[snafu(display("Query parameter '{key}' isn't a primitive value"))]
+    NestedQueryParam { key: String, backtrace: Backtrace },
+
+    /// Occurs when the number of names given to `to_value_named_tuple`
+    /// doesn't match the length of the tuple
+    #[snafu(display("Expected {names} tuple elements to match the names, got {arity}"))]
+    TupleArityMismatch {
+        names: usize,
+        arity: u32,
+        backtrace: Backtrace,
+    },
+
+    /// A value read with `DeserializeOptions::string_table` isn't a table written
+    /// with `SerializeOptions::string_table`
+    #[snafu(display("Invalid string table, {reason}"))]
+    InvalidStringTable {
+        reason: &'static str,
+        backtrace: Backtrace,
+    },
+
     /// A JS exception was throws
     #[snafu(display("JS exception: {throw}"))]
     Js { throw: Throw, backtrace: Backtrace },
@@ -72,6 +176,15 @@ pub enum Error {
     #[snafu(context(suffix(false)))]
     Serde { msg: String, backtrace: Backtrace },
 
+    /// Multiple errors, one per failing array element
+    ///
+    /// Only returned when `DeserializeOptions::fail_fast` is disabled
+    #[snafu(display("{} error(s) occurred while deserializing: {}", errors.len(), join(errors)))]
+    Multiple {
+        errors: Vec<Error>,
+        backtrace: Backtrace,
+    },
+
     /// This type of object is not supported
     #[doc(hidden)]
     #[snafu(display("Deserialization not implemented for {name}"))]
@@ -84,6 +197,97 @@ pub enum Error {
 
 pub type Result<T> = ::core::result::Result<T, Error>;
 
+fn join(errors: &[Error]) -> String {
+    errors
+        .iter()
+        .map(ToString::to_string)
+        .collect::<Vec<_>>()
+        .join("; ")
+}
+
+impl Error {
+    /// Rewrites serde's messages into JS-facing ones, see `DeserializeOptions::friendly_errors`
+    #[must_use]
+    pub(crate) fn into_friendly(self) -> Self {
+        match self {
+            Error::Serde { msg, backtrace } => Error::Serde {
+                msg: friendly_message(&msg).unwrap_or(msg),
+                backtrace,
+            },
+            Error::Multiple { errors, backtrace } => Error::Multiple {
+                errors: errors.into_iter().map(Error::into_friendly).collect(),
+                backtrace,
+            },
+            other => other,
+        }
+    }
+}
+
+/// Translates the common serde messages to use JS type names
+///
+/// `invalid type: integer `5`, expected a string` becomes
+/// `expected a string but got a number`, unknown messages return `None`
+fn friendly_message(msg: &str) -> Option<String> {
+    if let Some(rest) = msg.strip_prefix("invalid type: ") {
+        let (unexpected, expected) = rest.split_once(", expected ")?;
+        return Some(format!(
+            "expected {} but got {}",
+            js_expected(expected),
+            js_unexpected(unexpected)
+        ));
+    }
+    if let Some(rest) = msg.strip_prefix("invalid length ") {
+        let (len, expected) = rest.split_once(", expected ")?;
+        return Some(format!(
+            "expected {} but got an array of length {len}",
+            js_expected(expected)
+        ));
+    }
+    if let Some(field) = msg.strip_prefix("missing field ") {
+        return Some(format!("missing property {field}"));
+    }
+    if let Some(rest) = msg.strip_prefix("unknown field ") {
+        let (field, expected) = rest.split_once(", ")?;
+        return Some(format!("unknown property {field}, {expected}"));
+    }
+    None
+}
+
+/// Names the JS type behind serde's `Unexpected` descriptions
+fn js_unexpected(unexpected: &str) -> &str {
+    match unexpected
+        .split_once(' ')
+        .map_or(unexpected, |(kind, _)| kind)
+    {
+        "boolean" => "a boolean",
+        "integer" | "floating" => "a number",
+        "string" | "character" => "a string",
+        "sequence" => "an array",
+        "map" => "an object",
+        "byte" => "a Buffer",
+        _ if unexpected == "unit value" => "null",
+        _ => unexpected,
+    }
+}
+
+/// Names the JS type matching serde's `Expected` descriptions
+fn js_expected(expected: &str) -> Cow<'_, str> {
+    if let Some(size) = expected.strip_prefix("a tuple of size ") {
+        return Cow::Owned(format!("an array of length {size}"));
+    }
+    Cow::Borrowed(match expected {
+        "i8" | "i16" | "i32" | "i64" | "i128" | "u8" | "u16" | "u32" | "u64" | "u128" | "f32"
+        | "f64" => "a number",
+        "a sequence" => "an array",
+        "a map" => "an object",
+        "a character" | "a char" => "a single character string",
+        "unit" => "null",
+        _ if expected.starts_with("struct ") => "an object",
+        _ if expected.starts_with("tuple struct ") => "an array",
+        _ => expected,
+    })
+}
+
 impl ser::Error for Error {
     fn custom<T: Display>(msg: T) -> Self {
         Serde {
@@ -106,4 +310,50 @@ impl From<Throw> for Error {
     fn from(err: Throw) -> Self {
         JsSnafu { throw: err }.build()
     }
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod tests {
+    use super::friendly_message;
+
+    #[test]
+    fn test_friendly_invalid_type() {
+        assert_eq!(
+            friendly_message("invalid type: integer `5`, expected a string").as_deref(),
+            Some("expected a string but got a number")
+        );
+        assert_eq!(
+            friendly_message("invalid type: string \"x\", expected i32").as_deref(),
+            Some("expected a number but got a string")
+        );
+        assert_eq!(
+            friendly_message("invalid type: unit value, expected a sequence").as_deref(),
+            Some("expected an array but got null")
+        );
+        assert_eq!(
+            friendly_message("invalid type: sequence, expected struct Point").as_deref(),
+            Some("expected an object but got an array")
+        );
+    }
+
+    #[test]
+    fn test_friendly_fields_and_length() {
+        assert_eq!(
+            friendly_message("missing field `a`").as_deref(),
+            Some("missing property `a`")
+        );
+        assert_eq!(
+            friendly_message("unknown field `z`, expected `x` or `y`").as_deref(),
+            Some("unknown property `z`, expected `x` or `y`")
+        );
+        assert_eq!(
+            friendly_message("invalid length 1, expected a tuple of size 2").as_deref(),
+            Some("expected an array of length 2 but got an array of length 1")
+        );
+    }
+
+    #[test]
+    fn test_friendly_unknown_message() {
+        assert_eq!(friendly_message("something else"), None);
+    }
+}
@@ -21,10 +21,21 @@
 //! Convert a `Handle<js::JsValue>` to
 //! a type implementing `serde::Deserialize`
 //!
+//! #### `neon_serde::from_value_with`
+//! Same as `from_value` but takes a `DeserializeOptions`
+//! to customize how the `JsValue` is read
+//!
 //! #### `neon_serde::to_value`
 //! Convert a value implementing `serde::Serialize` to
 //! a `Handle<JsValue>`
 //!
+//! #### `neon_serde::to_value_with`
+//! Same as `to_value` but takes a `SerializeOptions`
+//! to customize the produced `JsValue`
+//!
+//! #### `neon_serde::convert`
+//! All of the conversion functions, for a single `use neon_serde::convert::*`
+//!
 //!
 //! ## Example
 //!
@@ -51,7 +62,7 @@
 //!     let arg0_value :AnObject = neon_serde::from_value(&mut cx, arg0)?;
 //!     println!("{:?}", arg0_value);
 //!
-//!     Ok(JsUndefined::new().upcast())
+//!     Ok(JsUndefined::new(&mut cx).upcast())
 //! }
 //!
 //! fn serialize_something<'j>(mut cx: FunctionContext<'j>) -> Result<'j, JsValue> {
@@ -71,20 +82,96 @@ pub mod de;
 pub mod errors;
 pub mod ser;
 
+mod coerce;
 mod macros;
+mod table;
+mod template;
+mod typed;
 
+pub use de::from_root;
 pub use de::from_value;
+pub use de::from_value_dynamic;
 pub use de::from_value_js;
 pub use de::from_value_opt;
+pub use de::from_value_patch;
+pub use de::from_value_scalar;
+pub use de::from_value_seq_until;
+pub use de::from_value_with;
+pub use de::DeserializeOptions;
+pub use de::DynamicDeserializer;
+pub use de::FromJsScalar;
+pub use de::ReusableDeserializer;
 
+pub use ser::serialize_append;
+pub use ser::to_generator;
+pub use ser::to_instance;
+pub use ser::to_query_params;
+pub use ser::to_record_batch;
+pub use ser::to_rooted;
 pub use ser::to_value;
 pub use ser::to_value_js;
+pub use ser::to_value_merged;
+pub use ser::to_value_named_tuple;
+pub use ser::to_value_or_throw;
+pub use ser::to_value_with;
+pub use ser::to_value_with_plan;
+pub use ser::AsNull;
+pub use ser::AsUndefined;
+pub use ser::DepthPolicy;
+pub use ser::EnumStyle;
+pub use ser::JsPath;
+pub use ser::MetaFn;
+pub use ser::NestedParamPolicy;
+pub use ser::NonAsciiKeyPolicy;
+pub use ser::NonePolicy;
+pub use ser::RootedValue;
+pub use ser::SerializeOptions;
+pub use ser::SerializePlan;
+pub use ser::SerializeTransform;
+pub use ser::ValueTransform;
+
+/// Every conversion function in one place, for `use neon_serde::convert::*`
+///
+/// They're the same functions as the ones at the crate root
+pub mod convert {
+    pub use self::deserialize::*;
+    pub use self::serialize::*;
+
+    /// From JS values to Rust values
+    pub mod deserialize {
+        pub use crate::de::{
+            from_root, from_value, from_value_dynamic, from_value_js, from_value_opt,
+            from_value_patch, from_value_scalar, from_value_seq_until, from_value_with,
+        };
+    }
+
+    /// From Rust values to JS values
+    pub mod serialize {
+        pub use crate::ser::{
+            serialize_append, to_generator, to_instance, to_query_params, to_record_batch,
+            to_rooted, to_value, to_value_js, to_value_merged, to_value_named_tuple,
+            to_value_or_throw, to_value_with, to_value_with_plan,
+        };
+    }
+}
 
 use neon::{context::Context, result::NeonResult};
 
 pub trait ResultExt<T>: Sized {
     #[doc(hidden)]
     fn throw<'cx, C: Context<'cx>>(self, cx: &mut C) -> NeonResult<T>;
+
+    /// Throws a JS error with the message returned by `f`, for custom or localized messages
+    ///
+    /// An exception already pending from JS is passed through untouched
+    ///
+    /// # Errors
+    ///
+    /// Fails with the thrown exception when `self` is an error
+    fn or_throw_with<'cx, C, F>(self, cx: &mut C, f: F) -> NeonResult<T>
+    where
+        C: Context<'cx>,
+        F: FnOnce(&errors::Error) -> String;
 }
 
 impl<T> ResultExt<T> for errors::Result<T> {
@@ -92,12 +179,26 @@ impl<T> ResultExt<T> for errors::Result<T> {
         use snafu::ErrorCompat;
         match self {
             Ok(ok) => Ok(ok),
+            // the exception is already pending, throwing again would fail
+            Err(errors::Error::Js { throw, .. }) => Err(throw),
             Err(e) => match e.backtrace() {
                 Some(backtrace) => cx.throw_error(format!("{e}! Backtrace:\n{backtrace}")),
                 None => cx.throw_error(e.to_string()),
             },
         }
     }
+
+    fn or_throw_with<'cx, C, F>(self, cx: &mut C, f: F) -> NeonResult<T>
+    where
+        C: Context<'cx>,
+        F: FnOnce(&errors::Error) -> String,
+    {
+        match self {
+            Ok(ok) => Ok(ok),
+            Err(errors::Error::Js { throw, .. }) => Err(throw),
+            Err(e) => cx.throw_error(f(&e)),
+        }
+    }
 }
 
 #[cfg(test)]
@@ -135,4 +236,22 @@ mod tests {
 
         let _ = check;
     }
+
+    #[test]
+    fn test_convert_compiles() {
+        fn check<'j>(mut cx: FunctionContext<'j>) -> Result<'j, JsValue> {
+            use crate::convert::*;
+
+            let arg: Handle<'j, JsValue> = cx.argument::<JsValue>(0)?;
+            let value: Vec<u32> = from_value_with(&mut cx, arg, &DeserializeOptions::default())?;
+            let rooted = to_rooted(&mut cx, &value)?;
+            let value: Vec<u32> = from_root(&mut cx, &rooted)?;
+            rooted.drop(&mut cx);
+            let result = serialize::to_value(&mut cx, &value)?;
+            let _: () = deserialize::from_value_opt(&mut cx, None)?;
+            Ok(result)
+        }
+
+        let _ = check;
+    }
 }
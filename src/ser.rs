@@ -2,17 +2,605 @@
 //! Serialize a Rust data structure into a `JsValue`
 //!
 
-use crate::errors::{self, Error, Result as LibResult};
-use neon::{prelude::*, types::buffer::TypedArray};
+use crate::{
+    errors::{self, Error, Result as LibResult},
+    table, template,
+    typed::{self, Scalar, TypedElements},
+    ResultExt,
+};
+use neon::{
+    prelude::*,
+    types::{bigint::Sign, buffer::TypedArray, JsBigInt},
+};
 use num;
-use serde::ser::{self, Serialize};
-use snafu::OptionExt;
-use std::marker::PhantomData;
+use serde::{
+    de::{self, Deserialize},
+    ser::{self, Serialize},
+};
+use snafu::{ensure, OptionExt};
+use std::{
+    borrow::Cow,
+    cell::RefCell,
+    fmt::{self, Write},
+    marker::PhantomData,
+    path::{Path, MAIN_SEPARATOR},
+    sync::Arc,
+};
 
 fn as_num<T: num::cast::NumCast, OutT: num::cast::NumCast>(n: T) -> LibResult<OutT> {
     num::cast::<T, OutT>(n).context(errors::CastSnafu)
 }
 
+/// Largest integer a JS number can hold exactly (`Number.MAX_SAFE_INTEGER`)
+const MAX_SAFE_INTEGER: i128 = (1 << 53) - 1;
+
+/// Serializes a 128-bit integer that doesn't fit in a JS number
+///
+/// With the `bigint` feature this is a `BigInt`, otherwise the decimal string
+macro_rules! serialize_wide_int {
+    ($ser:expr, $v:expr, $ctor:ident) => {
+        if cfg!(feature = "bigint") {
+            Ok(JsBigInt::$ctor($ser.cx, $v).upcast())
+        } else {
+            $ser.serialize_str(&$v.to_string())
+        }
+    };
+}
+
+/// Serializes an integer that fits in a JS number,
+/// as a `BigInt` with `SerializeOptions::all_ints_as_bigint`
+macro_rules! serialize_int {
+    ($ser:expr, $v:expr, $ctor:ident) => {
+        if $ser.opts.all_ints_as_bigint {
+            Ok(JsBigInt::$ctor($ser.cx, $v.into()).upcast())
+        } else {
+            new_number($ser.cx, $ser.opts, as_num::<_, f64>($v)?)
+        }
+    };
+}
+
+/// Creates an empty object, honouring `SerializeOptions::null_prototype`
+pub(crate) fn new_object<'j, C>(
+    cx: &mut C,
+    opts: &SerializeOptions,
+) -> LibResult<Handle<'j, JsObject>>
+where
+    C: Context<'j>,
+{
+    if !opts.null_prototype {
+        return Ok(JsObject::new(cx));
+    }
+    let object_ctor: Handle<JsFunction> = cx.global("Object")?;
+    let create: Handle<JsFunction> = object_ctor.get(cx, "create")?;
+    let null = cx.null();
+    let object = create.call_with(cx).arg(null).apply(cx)?;
+    Ok(object)
+}
+
+/// Options to customize how values are serialized
+///
+/// Use with [`to_value_with`], the [`Default`] matches the behaviour of [`to_value`]
+#[derive(Debug, Clone)]
+#[allow(clippy::struct_excessive_bools)]
+pub struct SerializeOptions {
+    /// Create objects with `Object.create(null)` instead of `{}`
+    ///
+    /// Applies to structs, maps and the wrapper objects of enum variants
+    pub null_prototype: bool,
+    /// Value of `Serializer::is_human_readable`, defaults to `true`
+    ///
+    /// Set to `false` for the compact form of types like `uuid::Uuid` (a `Buffer` of 16 bytes)
+    pub human_readable: bool,
+    /// Serialize maps to an array of `[key, value]` pairs when a key is an object or array
+    ///
+    /// Such keys would all become `"[object Object]"` as property names,
+    /// maps with only string or number keys are still serialized to an object
+    pub complex_keys_as_pairs: bool,
+    /// Set the keys of structs and maps in sorted order, for deterministic output
+    ///
+    /// Struct fields no longer follow declaration order. JS always lists
+    /// integer-like keys first in numeric order, whatever order they're set in
+    pub sort_keys: bool,
+    /// Set the keys of maps in sorted order, like `sort_keys` but keeping the order of struct fields
+    ///
+    /// Makes `HashMap`s deterministic, ordered maps like `BTreeMap` with string keys
+    /// are already sorted
+    pub sort_map_keys: bool,
+    /// Use `/` as the separator of paths wrapped in a [`JsPath`], even on Windows
+    pub paths_forward_slash: bool,
+    /// Serialize empty sequences, maps and structs (e.g. with every field skipped) as `null`
+    ///
+    /// Without it a struct with no fields like `struct Empty {}` is `{}`. A unit struct
+    /// like `struct Empty;` is `null` either way, like `()`
+    pub empty_as_null: bool,
+    /// Serialize sequences of a single number type as the matching typed array,
+    /// e.g. a `Vec<f64>` becomes a `Float64Array`
+    ///
+    /// Covers `i8` to `u32`, `f32` and `f64`, 64-bit integers don't fit the JS number
+    /// typed arrays. Sequences with other or mixed elements are still a plain `Array`.
+    /// Applies at any depth, so each `Vec<f64>` field of a struct of arrays is a `Float64Array`
+    pub typed_arrays: bool,
+    /// Serialize bytes as `{ len, data }` with `data` the `Buffer`, instead of a bare `Buffer`
+    pub bytes_with_length: bool,
+    /// What to do with map entries whose value is `None`
+    pub map_none_policy: NonePolicy,
+    /// Serialize numbers as `Number` objects (`new Number(x)`) instead of primitives
+    ///
+    /// Map keys stay primitive, so they are still set as property names.
+    /// Numbers too wide for a JS number are unaffected
+    pub boxed_numbers: bool,
+    /// Shape of enum variants, see [`EnumStyle`]
+    pub enum_style: EnumStyle,
+    /// Serialize newtype variants as just their data, `E::V(5)` becomes `5` instead of `{ V: 5 }`
+    ///
+    /// The variant name is lost, so it can't be deserialized back unless the enum has a single
+    /// variant or is `#[serde(untagged)]`, and variants with the same data type can't be told apart
+    pub flatten_newtype_variants: bool,
+    /// What to do with property names that aren't ASCII
+    ///
+    /// Applies to struct fields, map keys and the keys of externally tagged variants
+    pub on_non_ascii_key: NonAsciiKeyPolicy,
+    /// Round `f32` and `f64` values to this many decimal places, `3.14159` becomes `3.14` with `2`
+    ///
+    /// Rounds the exact value of the float through its decimal form and emits the float
+    /// closest to the result, so no digits past `n` show up when JS prints it
+    pub float_decimals: Option<u8>,
+    /// Only emit numbers that are valid JSON, `NaN` and infinities become `null` and `-0` becomes `0`
+    ///
+    /// A sequence with a non-finite float is a plain `Array` even with `typed_arrays`,
+    /// since a typed array can't hold `null`
+    pub json_number_grammar: bool,
+    /// Serialize every integer as a `BigInt`, even the ones that fit in a JS number
+    ///
+    /// Works without the `bigint` feature. Floats stay numbers, and sequences of
+    /// integers are arrays of `BigInt`s rather than typed arrays
+    pub all_ints_as_bigint: bool,
+    /// Read back every string value after creating it, failing with `StringMismatch` if it changed
+    ///
+    /// Rust strings are always valid UTF-8 so this only catches conversion bugs, at the cost
+    /// of copying each string back out of JS
+    pub validate_output_strings: bool,
+    /// Rename the properties of structs and maps and replace their values, see [`SerializeTransform`]
+    pub transform: Option<Arc<dyn SerializeTransform>>,
+    /// Most arrays and objects nested in each other, structures past it are handled
+    /// per `on_depth_exceeded`
+    ///
+    /// `[1, [2, [3]]]` with `2` is `[1, [2, "[Truncated]"]]`, with `0` it's just
+    /// the placeholder. Values past the limit aren't serialized at all
+    pub max_depth: Option<usize>,
+    /// What to do with structures nested past `max_depth`
+    pub on_depth_exceeded: DepthPolicy,
+    /// Set a metadata object on each struct under the symbol `Symbol.for(key)`, with the
+    /// properties returned by the [`MetaFn`] for the name of the struct, or of the enum for
+    /// struct variants
+    ///
+    /// Symbol keys don't show up in `Object.keys`, `for..in` or `JSON.stringify`,
+    /// so the metadata can't collide with the fields
+    pub metadata_symbol: Option<(&'static str, MetaFn)>,
+    /// Serialize sequences of `char`s, like a `Vec<char>`, as a single string of the chars
+    ///
+    /// Sequences with other elements are still a plain `Array`, and so are empty
+    /// sequences since they could be of anything
+    pub char_vec_as_string: bool,
+    /// Serialize floats that are integers and larger than this in absolute value as `BigInt`s,
+    /// e.g. `Some(9007199254740991.0)` for the ones past `Number.MAX_SAFE_INTEGER`
+    ///
+    /// The `BigInt` is exactly the integer the float holds, which past 2^53 is already
+    /// rounded to what an `f64` can represent, so `1e20 + 1.0` is `100000000000000000000n`.
+    /// Works without the `bigint` feature. Floats in typed arrays are left as they are
+    pub integral_floats_as_bigint_above: Option<f64>,
+    /// Serialize maps as a JS `Map` rather than an object, so keys keep their type
+    ///
+    /// Number keys stay numbers, and `i64` or `u64` keys past `Number.MAX_SAFE_INTEGER`
+    /// are `BigInt`s so they're exact. Object keys are kept as they are, so
+    /// `complex_keys_as_pairs` and `empty_as_null` don't apply to maps. Structs are still objects
+    pub maps_as_js_map: bool,
+    /// Serialize the value as `{ strings, data }`, with each distinct string and property
+    /// name stored once in the `strings` array and replaced in `data` by its index
+    ///
+    /// `[{ name: "a" }, { name: "a" }]` is `{ strings: ["name", "a"], data: [{ "#0": "1" }, { "#0": "1" }] }`.
+    /// Indices are strings so they can't be mistaken for numbers, which are kept as they are,
+    /// like `Map`s, `Date`s and buffers. Property names are prefixed with `#` so they keep
+    /// their order. Read it back with `DeserializeOptions::string_table`
+    pub string_table: bool,
+}
+
+/// The metadata properties of a struct given its name, for [`SerializeOptions::metadata_symbol`],
+/// `None` to leave it without metadata
+pub type MetaFn = fn(&'static str) -> Option<Vec<(&'static str, String)>>;
+
+/// Handling of `None` map values, see [`SerializeOptions::map_none_policy`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum NonePolicy {
+    /// Set the key to `null`
+    #[default]
+    Null,
+    /// Leave the key out, other values that serialize to `null` such as `()` keep theirs
+    Omit,
+}
+
+/// Handling of structures nested past [`SerializeOptions::max_depth`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum DepthPolicy {
+    /// Replace them with the string [`TRUNCATED`], e.g. to log large values
+    #[default]
+    Truncate,
+    /// Fail with `DepthExceeded`
+    Error,
+}
+
+/// The placeholder for structures nested past `max_depth`, with [`DepthPolicy::Truncate`]
+pub const TRUNCATED: &str = "[Truncated]";
+
+/// Handling of non-ASCII property names, see [`SerializeOptions::on_non_ascii_key`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum NonAsciiKeyPolicy {
+    /// Set the name as is
+    #[default]
+    Allow,
+    /// Fail with `NonAsciiKey`
+    Reject,
+    /// Replace each non-ASCII character with `\uXXXX`, `"café"` becomes `"caf\u00e9"`
+    EscapeUnicode,
+}
+
+/// Rewrites property names and values as structs and maps are serialized,
+/// for [`SerializeOptions::transform`]
+///
+/// ```
+/// # use std::borrow::Cow;
+/// # use neon_serde::{SerializeTransform, ValueTransform};
+/// #[derive(Debug)]
+/// struct Redact;
+///
+/// impl SerializeTransform for Redact {
+///     fn transform_key<'k>(&self, key: &'k str) -> Cow<'k, str> {
+///         Cow::Owned(key.to_uppercase())
+///     }
+///
+///     fn transform_value(&self, key: &str) -> ValueTransform {
+///         if key == "password" {
+///             ValueTransform::Replace("***".into())
+///         } else {
+///             ValueTransform::Keep
+///         }
+///     }
+/// }
+/// ```
+pub trait SerializeTransform: fmt::Debug + Send + Sync {
+    /// The property name to use for a struct field or a string map key
+    ///
+    /// Applies before `on_non_ascii_key` and `sort_keys`
+    fn transform_key<'k>(&self, key: &'k str) -> Cow<'k, str> {
+        Cow::Borrowed(key)
+    }
+
+    /// What to set for the property `key`, named as before `transform_key`
+    ///
+    /// The value is only serialized for `ValueTransform::Keep`
+    fn transform_value(&self, _key: &str) -> ValueTransform {
+        ValueTransform::Keep
+    }
+}
+
+/// The value to set for a property, see [`SerializeTransform::transform_value`]
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub enum ValueTransform {
+    /// Set the serialized value
+    #[default]
+    Keep,
+    /// Leave the property out
+    Omit,
+    /// Set `null`
+    Null,
+    /// Set this string instead, e.g. to redact the value
+    Replace(String),
+}
+
+/// Serializes the value of the property `key`, applying `SerializeOptions::transform`,
+/// `None` if it is left out
+fn property_value<'j, C, T>(
+    cx: &mut C,
+    opts: &SerializeOptions,
+    depth: usize,
+    key: &str,
+    value: &T,
+) -> LibResult<Option<Handle<'j, JsValue>>>
+where
+    C: Context<'j>,
+    T: ?Sized + Serialize,
+{
+    let Some(transform) = &opts.transform else {
+        return to_value_at(cx, value, opts, depth).map(Some);
+    };
+    match transform.transform_value(key) {
+        ValueTransform::Keep => to_value_at(cx, value, opts, depth).map(Some),
+        ValueTransform::Omit => Ok(None),
+        ValueTransform::Null => Ok(Some(cx.null().upcast())),
+        ValueTransform::Replace(replacement) => Ok(Some(cx.string(replacement).upcast())),
+    }
+}
+
+/// How enum variants are represented, for [`SerializeOptions::enum_style`]
+/// and [`DeserializeOptions::enum_style`](crate::DeserializeOptions::enum_style)
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum EnumStyle {
+    /// Unit variants are the variant name, other variants `{ Variant: data }`
+    #[default]
+    External,
+    /// Every variant is `{ type: "Variant", value: data }`, like serde's
+    /// `#[serde(tag = "type", content = "value")]`
+    ///
+    /// Unit variants have no `value`, a `null` one is accepted when deserializing.
+    /// Objects with other keys are rejected
+    TypeValue,
+    /// The variant name is set as `tag` next to the fields, `{ type: "Variant", field: 1 }`,
+    /// like serde's `#[serde(tag = "type")]`
+    ///
+    /// Unit variants are just `{ type: "Variant" }` and newtype variants must hold a struct
+    /// or map, tuple variants fail with `InternallyTaggedContent`. When deserializing, struct
+    /// variants read their fields by name so other keys are ignored, newtype variants get
+    /// the whole object including the tag
+    InternallyTagged { tag: &'static str },
+}
+
+impl Default for SerializeOptions {
+    fn default() -> Self {
+        SerializeOptions {
+            null_prototype: false,
+            human_readable: true,
+            complex_keys_as_pairs: false,
+            sort_keys: false,
+            sort_map_keys: false,
+            paths_forward_slash: false,
+            empty_as_null: false,
+            typed_arrays: false,
+            bytes_with_length: false,
+            map_none_policy: NonePolicy::Null,
+            boxed_numbers: false,
+            enum_style: EnumStyle::External,
+            flatten_newtype_variants: false,
+            on_non_ascii_key: NonAsciiKeyPolicy::Allow,
+            float_decimals: None,
+            json_number_grammar: false,
+            all_ints_as_bigint: false,
+            validate_output_strings: false,
+            transform: None,
+            max_depth: None,
+            on_depth_exceeded: DepthPolicy::Truncate,
+            metadata_symbol: None,
+            char_vec_as_string: false,
+            integral_floats_as_bigint_above: None,
+            maps_as_js_map: false,
+            string_table: false,
+        }
+    }
+}
+
+/// Creates a number, boxed in a `Number` object with `SerializeOptions::boxed_numbers`
+pub(crate) fn new_number<'j, C>(
+    cx: &mut C,
+    opts: &SerializeOptions,
+    v: impl Into<f64>,
+) -> LibResult<Handle<'j, JsValue>>
+where
+    C: Context<'j>,
+{
+    let number = cx.number(v);
+    if !opts.boxed_numbers {
+        return Ok(number.upcast());
+    }
+    let number_ctor: Handle<JsFunction> = cx.global("Number")?;
+    let boxed: Handle<JsObject> = number_ctor.construct_with(cx).arg(number).apply(cx)?;
+    Ok(boxed.upcast())
+}
+
+/// Applies `SerializeOptions::float_decimals` and `json_number_grammar` to a float,
+/// `None` if it has to be emitted as `null`
+pub(crate) fn float_value(opts: &SerializeOptions, v: f64) -> Option<f64> {
+    if !v.is_finite() {
+        return (!opts.json_number_grammar).then_some(v);
+    }
+    let v = match opts.float_decimals {
+        Some(decimals) => format!("{v:.*}", usize::from(decimals))
+            .parse()
+            .unwrap_or(v),
+        None => v,
+    };
+    // `-0.0 == 0.0`, so this only drops the sign, also of values rounded to `-0`
+    if opts.json_number_grammar && v == 0.0 {
+        return Some(0.0);
+    }
+    Some(v)
+}
+
+/// Creates the `BigInt` of the integer a float holds, exactly
+fn integral_bigint<'j, C>(cx: &mut C, v: f64) -> Handle<'j, JsBigInt>
+where
+    C: Context<'j>,
+{
+    // 2^64, the remainders and quotients by it are exact for floats
+    const DIGIT: f64 = 18_446_744_073_709_551_616.0;
+    let sign = if v < 0.0 {
+        Sign::Negative
+    } else {
+        Sign::Positive
+    };
+    let mut rest = v.abs();
+    let mut digits = Vec::new();
+    while rest > 0.0 {
+        let digit = rest % DIGIT;
+        digits.push(num::cast::<f64, u64>(digit).unwrap_or_default());
+        rest = (rest - digit) / DIGIT;
+    }
+    JsBigInt::from_digits_le(cx, sign, &digits)
+}
+
+/// Applies `SerializeOptions::on_non_ascii_key` to a property name,
+/// `None` if it can be set as is
+fn ascii_key(opts: &SerializeOptions, key: &str) -> LibResult<Option<String>> {
+    if key.is_ascii() {
+        return Ok(None);
+    }
+    match opts.on_non_ascii_key {
+        NonAsciiKeyPolicy::Allow => Ok(None),
+        NonAsciiKeyPolicy::Reject => errors::NonAsciiKeySnafu { key }.fail(),
+        NonAsciiKeyPolicy::EscapeUnicode => {
+            let mut escaped = String::with_capacity(key.len() + 8);
+            for c in key.chars() {
+                if c.is_ascii() {
+                    escaped.push(c);
+                } else {
+                    for unit in c.encode_utf16(&mut [0; 2]) {
+                        let _ = write!(escaped, "\\u{unit:04x}");
+                    }
+                }
+            }
+            Ok(Some(escaped))
+        }
+    }
+}
+
+/// Wraps the data of an enum variant, honouring `SerializeOptions::enum_style`
+///
+/// `value` is `None` for unit variants
+fn variant_object<'j, C>(
+    cx: &mut C,
+    opts: &SerializeOptions,
+    variant: &'static str,
+    value: Option<Handle<'j, JsValue>>,
+) -> LibResult<Handle<'j, JsObject>>
+where
+    C: Context<'j>,
+{
+    let object = new_object(cx, opts)?;
+    match opts.enum_style {
+        EnumStyle::External => {
+            let value = match value {
+                Some(value) => value,
+                None => cx.null().upcast(),
+            };
+            let key = ascii_key(opts, variant)?;
+            object.set(cx, key.as_deref().unwrap_or(variant), value)?;
+        }
+        EnumStyle::TypeValue => {
+            let name = cx.string(variant);
+            object.set(cx, "type", name)?;
+            if let Some(value) = value {
+                object.set(cx, "value", value)?;
+            }
+        }
+        EnumStyle::InternallyTagged { tag } => {
+            let name = cx.string(variant);
+            object.set(cx, tag, name)?;
+            if let Some(value) = value {
+                let fields = value
+                    .downcast::<JsObject, C>(cx)
+                    .ok()
+                    .filter(|_| !value.is_a::<JsArray, C>(cx))
+                    .context(errors::InternallyTaggedContentSnafu { variant })?;
+                for key in fields.get_own_property_names(cx)?.to_vec(cx)? {
+                    let field = fields.get_value(cx, key)?;
+                    object.set(cx, key, field)?;
+                }
+            }
+        }
+    }
+    Ok(object)
+}
+
+/// Returns `null` in place of an object without keys, with `empty_as_null`
+fn object_or_null<'j, C>(
+    cx: &mut C,
+    opts: &SerializeOptions,
+    object: Handle<'j, JsObject>,
+) -> LibResult<Handle<'j, JsValue>>
+where
+    C: Context<'j>,
+{
+    if opts.empty_as_null && object.get_own_property_names(cx)?.len(cx) == 0 {
+        return Ok(cx.null().upcast());
+    }
+    Ok(object.upcast())
+}
+
+/// Newtype name `JsPath` serializes with, so the serializer can tell it apart
+const PATH_NAME: &str = "$neon_serde::private::JsPath";
+
+/// Serializes a path as a string, honouring [`SerializeOptions::paths_forward_slash`]
+///
+/// Serde serializes a `Path` like any other string, wrap it in a `JsPath`
+/// to opt in to the option. Other serializers see a plain string
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct JsPath<P>(pub P);
+
+impl<P: AsRef<Path>> Serialize for JsPath<P> {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: ser::Serializer,
+    {
+        serializer.serialize_newtype_struct(PATH_NAME, self.0.as_ref())
+    }
+}
+
+/// Newtype name `AsUndefined` serializes `None` with, so the serializer can tell it apart
+const UNDEFINED_NAME: &str = "$neon_serde::private::AsUndefined";
+
+/// An `Option` serializing `None` as `undefined` rather than `null`, for the fields that
+/// need it
+///
+/// The property is still set, to `undefined`, which `JSON.stringify` leaves out. Other
+/// serializers see a plain `Option`, and it deserializes like one
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub struct AsUndefined<T>(pub Option<T>);
+
+impl<T: Serialize> Serialize for AsUndefined<T> {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: ser::Serializer,
+    {
+        match &self.0 {
+            Some(value) => serializer.serialize_some(value),
+            None => serializer.serialize_newtype_struct(UNDEFINED_NAME, &None::<()>),
+        }
+    }
+}
+
+impl<'de, T: Deserialize<'de>> Deserialize<'de> for AsUndefined<T> {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: de::Deserializer<'de>,
+    {
+        Option::deserialize(deserializer).map(AsUndefined)
+    }
+}
+
+/// An `Option` serializing `None` as `null`, like a plain `Option` does, to spell out
+/// the representation next to [`AsUndefined`] fields
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub struct AsNull<T>(pub Option<T>);
+
+impl<T: Serialize> Serialize for AsNull<T> {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: ser::Serializer,
+    {
+        match &self.0 {
+            Some(value) => serializer.serialize_some(value),
+            None => serializer.serialize_none(),
+        }
+    }
+}
+
+impl<'de, T: Deserialize<'de>> Deserialize<'de> for AsNull<T> {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: de::Deserializer<'de>,
+    {
+        Option::deserialize(deserializer).map(AsNull)
+    }
+}
+
 /// Converts a value of type `V` to a `JsValue`
 ///
 /// # Errors
@@ -22,16 +610,107 @@ fn as_num<T: num::cast::NumCast, OutT: num::cast::NumCast>(n: T) -> LibResult<Ou
 ///
 #[inline]
 pub fn to_value<'j, C, V>(cx: &mut C, value: &V) -> LibResult<Handle<'j, JsValue>>
+where
+    C: Context<'j>,
+    V: Serialize + ?Sized,
+{
+    to_value_with(cx, value, &SerializeOptions::default())
+}
+
+/// Converts a value of type `V` to a `JsValue` using the given `SerializeOptions`
+///
+/// # Errors
+///
+/// See [`to_value`] errors
+///
+#[inline]
+pub fn to_value_with<'j, C, V>(
+    cx: &mut C,
+    value: &V,
+    options: &SerializeOptions,
+) -> LibResult<Handle<'j, JsValue>>
+where
+    C: Context<'j>,
+    V: Serialize + ?Sized,
+{
+    let value = to_value_at(cx, value, options, 0)?;
+    if options.string_table {
+        return Ok(table::encode(cx, value)?.upcast());
+    }
+    Ok(value)
+}
+
+/// Converts a struct like [`to_value_with`], creating the object with a [`SerializePlan`]
+/// made for its fields
+///
+/// The plan only applies to the value itself, not to the structs nested in it. Without
+/// `null_prototype`, `transform`, `sort_keys` or non-ASCII field names with
+/// `on_non_ascii_key`, this skips looking up the object template by field names.
+/// Otherwise, or if the fields serialized aren't those of the plan (e.g. because of
+/// `#[serde(skip_serializing_if)]`), the struct is converted like [`to_value_with`] does
+///
+/// # Errors
+///
+/// See [`to_value`] errors
+///
+pub fn to_value_with_plan<'j, C, V>(
+    cx: &mut C,
+    plan: &SerializePlan,
+    value: &V,
+    options: &SerializeOptions,
+) -> LibResult<Handle<'j, JsValue>>
+where
+    C: Context<'j>,
+    V: Serialize + ?Sized,
+{
+    let value = to_value_planned(cx, value, options, 0, Some(plan))?;
+    if options.string_table {
+        return Ok(table::encode(cx, value)?.upcast());
+    }
+    Ok(value)
+}
+
+/// Converts a value nested `depth` structures deep, which is the placeholder
+/// if it's a structure past `max_depth` with `DepthPolicy::Truncate`
+fn to_value_at<'j, C, V>(
+    cx: &mut C,
+    value: &V,
+    options: &SerializeOptions,
+    depth: usize,
+) -> LibResult<Handle<'j, JsValue>>
+where
+    C: Context<'j>,
+    V: Serialize + ?Sized,
+{
+    to_value_planned(cx, value, options, depth, None)
+}
+
+/// Converts a value like [`to_value_at`], creating it with `plan` if it's a struct
+fn to_value_planned<'j, C, V>(
+    cx: &mut C,
+    value: &V,
+    options: &SerializeOptions,
+    depth: usize,
+    plan: Option<&SerializePlan>,
+) -> LibResult<Handle<'j, JsValue>>
 where
     C: Context<'j>,
     V: Serialize + ?Sized,
 {
     let serializer = Serializer {
-        cx,
+        cx: &mut *cx,
+        opts: options,
+        depth,
+        plan,
         ph: PhantomData,
     };
-    let serialized_value = value.serialize(serializer)?;
-    Ok(serialized_value)
+    match value.serialize(serializer) {
+        // raised by this value, the structures nested in it are replaced at their own depth
+        Err(Error::DepthExceeded { .. }) if options.on_depth_exceeded == DepthPolicy::Truncate => {
+            Ok(cx.string(TRUNCATED).upcast())
+        }
+        result => result,
+    }
 }
 
 /// Converts a value of type `V` to a `JsValue` and wraps with `JsResult`
@@ -50,12 +729,435 @@ where
     to_value(cx, value).or_else(|err| cx.throw_error(err.to_string()))
 }
 
+/// Converts a slice of records, typically flat structs, to an array of objects
+///
+/// The array is created at its full length up front. Objects of the same shape are all
+/// created by one cached template function, so the property names are only created once
+///
+/// # Errors
+///
+/// See [`to_value`] errors
+///
+pub fn to_record_batch<'j, C, V>(cx: &mut C, records: &[V]) -> LibResult<Handle<'j, JsArray>>
+where
+    C: Context<'j>,
+    V: Serialize,
+{
+    let opts = SerializeOptions::default();
+    let array = JsArray::new(cx, records.len());
+    for (index, record) in (0u32..).zip(records) {
+        let value = to_value_with(cx, record, &opts)?;
+        array.set(cx, index, value)?;
+    }
+    Ok(array)
+}
+
+/// Converts the `Ok` value of `result` to a `JsValue`, or throws an `Error` with the message of the `Err`
+///
+/// # Errors
+///
+/// * The thrown `Error` for an `Err`
+/// * See [`to_value_js`] errors
+///
+pub fn to_value_or_throw<'j, C, V, E>(cx: &mut C, result: &Result<V, E>) -> JsResult<'j, JsValue>
+where
+    C: Context<'j>,
+    V: Serialize,
+    E: fmt::Display,
+{
+    match result {
+        Ok(value) => to_value_js(cx, value),
+        Err(err) => cx.throw_error(err.to_string()),
+    }
+}
+
+/// Wraps an iterator in a JS iterator object, items are serialized as `next()` is called
+///
+/// The returned object is also iterable so works with `for...of` and spread.
+/// The iterator is dropped once exhausted or when the object is garbage collected
+///
+/// # Errors
+///
+/// * Fails if the JS functions can't be created, errors serializing items are thrown from `next()`
+///
+pub fn to_generator<'j, C, I>(cx: &mut C, iter: I) -> LibResult<Handle<'j, JsObject>>
+where
+    C: Context<'j>,
+    I: IntoIterator,
+    I::IntoIter: 'static,
+    I::Item: Serialize,
+{
+    let iter = RefCell::new(Some(iter.into_iter()));
+    let next = JsFunction::new(cx, move |mut cx| {
+        let Ok(mut iter) = iter.try_borrow_mut() else {
+            return cx.throw_error("Generator is already running");
+        };
+        let item = iter.as_mut().and_then(Iterator::next);
+        if item.is_none() {
+            *iter = None;
+        }
+        drop(iter);
+
+        let result = cx.empty_object();
+        let done = cx.boolean(item.is_none());
+        result.set(&mut cx, "done", done)?;
+        if let Some(item) = item {
+            let value = to_value(&mut cx, &item).throw(&mut cx)?;
+            result.set(&mut cx, "value", value)?;
+        }
+        Ok(result)
+    })?;
+    let iterator = JsFunction::new(cx, |mut cx| cx.this::<JsObject>())?;
+
+    let generator = JsObject::new(cx);
+    generator.set(cx, "next", next)?;
+    let symbol: Handle<JsFunction> = cx.global("Symbol")?;
+    let key: Handle<JsValue> = symbol.get(cx, "iterator")?;
+    generator.set(cx, key, iterator)?;
+    Ok(generator)
+}
+
+/// Converts a tuple (or tuple struct) to a `JsObject` using `names` as the keys
+///
+/// `(1, "a")` with the names `["x", "y"]` becomes `{ x: 1, y: "a" }`
+///
+/// # Errors
+///
+/// * `UnableToCoerce` if the value doesn't serialize to an array
+/// * `TupleArityMismatch` if the number of names doesn't match the tuple length
+/// * See [`to_value`] errors
+///
+pub fn to_value_named_tuple<'j, C, V>(
+    cx: &mut C,
+    value: &V,
+    names: &[&str],
+) -> LibResult<Handle<'j, JsValue>>
+where
+    C: Context<'j>,
+    V: Serialize + ?Sized,
+{
+    let array = to_value(cx, value)?
+        .downcast::<JsArray, C>(cx)
+        .ok()
+        .context(errors::UnableToCoerceSnafu { to_type: "tuple" })?;
+    let arity = array.len(cx);
+    ensure!(
+        as_num::<_, u32>(names.len())? == arity,
+        errors::TupleArityMismatchSnafu {
+            names: names.len(),
+            arity,
+        }
+    );
+
+    let object = JsObject::new(cx);
+    for (index, name) in (0..arity).zip(names) {
+        let element: Handle<JsValue> = array.get(cx, index)?;
+        object.set(cx, *name, element)?;
+    }
+    Ok(object.upcast())
+}
+
+/// Sets the fields of a struct (or entries of a map) on an existing object, like a class instance
+///
+/// Only the serialized fields are set, as own properties, so methods inherited from the
+/// prototype stay callable. Setters on the prototype are called like with any assignment
+///
+/// # Errors
+///
+/// * `UnableToCoerce` if the value doesn't serialize to a plain object
+/// * `ShadowsMethod` if a field has the name of a method of `instance`, no field is set then
+/// * See [`to_value`] errors
+///
+pub fn to_instance<'j, C, V>(cx: &mut C, value: &V, instance: Handle<'j, JsObject>) -> LibResult<()>
+where
+    C: Context<'j>,
+    V: Serialize + ?Sized,
+{
+    let fields = plain_object(cx, value)?;
+
+    let object_ctor: Handle<JsFunction> = cx.global("Object")?;
+    let prototype: Handle<JsObject> = object_ctor.get(cx, "prototype")?;
+    let has_own: Handle<JsFunction> = prototype.get(cx, "hasOwnProperty")?;
+
+    let keys = fields.get_own_property_names(cx)?.to_vec(cx)?;
+    for key in &keys {
+        let own: Handle<JsBoolean> = has_own.call_with(cx).this(instance).arg(*key).apply(cx)?;
+        if !own.value(cx) && instance.get_value(cx, *key)?.is_a::<JsFunction, C>(cx) {
+            let key = key.to_string(cx)?.value(cx);
+            return errors::ShadowsMethodSnafu { key }.fail();
+        }
+    }
+    for key in keys {
+        let field = fields.get_value(cx, key)?;
+        instance.set(cx, key, field)?;
+    }
+    Ok(())
+}
+
+/// A serialized value that outlives the context it was created in, see [`to_rooted`]
+///
+/// neon only roots objects, so the value is kept in a one element array. Like a `Root`,
+/// it can be sent across threads but only read on the JS thread that created it, and should
+/// be released with [`RootedValue::into_inner`] or [`RootedValue::drop`]. Otherwise it's
+/// released from a global queue when dropped, which has a runtime cost
+#[derive(Debug)]
+pub struct RootedValue(Root<JsArray>);
+
+impl RootedValue {
+    /// Reads the value in the current context, keeping it rooted
+    ///
+    /// # Errors
+    ///
+    /// * `Js` if reading the value throws
+    ///
+    pub fn to_inner<'j, C>(&self, cx: &mut C) -> LibResult<Handle<'j, JsValue>>
+    where
+        C: Context<'j>,
+    {
+        let holder = self.0.to_inner(cx);
+        Ok(holder.get_value(cx, 0)?)
+    }
+
+    /// Reads the value in the current context and releases the root
+    ///
+    /// # Errors
+    ///
+    /// * `Js` if reading the value throws
+    ///
+    pub fn into_inner<'j, C>(self, cx: &mut C) -> LibResult<Handle<'j, JsValue>>
+    where
+        C: Context<'j>,
+    {
+        let holder = self.0.into_inner(cx);
+        Ok(holder.get_value(cx, 0)?)
+    }
+
+    /// Releases the root without reading the value
+    pub fn drop<'j, C>(self, cx: &mut C)
+    where
+        C: Context<'j>,
+    {
+        self.0.drop(cx);
+    }
+}
+
+impl Finalize for RootedValue {
+    fn finalize<'j, C: Context<'j>>(self, cx: &mut C) {
+        self.drop(cx);
+    }
+}
+
+/// Converts a value of type `V` to a `JsValue` kept alive past the current context,
+/// e.g. to store it or to use it after an async task
+///
+/// # Errors
+///
+/// See [`to_value`] errors
+///
+pub fn to_rooted<'j, C, V>(cx: &mut C, value: &V) -> LibResult<RootedValue>
+where
+    C: Context<'j>,
+    V: Serialize + ?Sized,
+{
+    let value = to_value(cx, value)?;
+    let holder = JsArray::new(cx, 1);
+    holder.set(cx, 0, value)?;
+    Ok(RootedValue(holder.root(cx)))
+}
+
+/// The fields of a struct, prepared once to convert many values of it with
+/// [`to_value_with_plan`]
+///
+/// JS strings only live as long as the context they were created in, so the property names
+/// can't be kept as `JsString`s between calls. Instead the plan roots the function creating
+/// objects with these fields, which has the names in its source. Like a `Root`, it can be
+/// sent across threads but only used on the JS thread that created it, and should be released
+/// with [`SerializePlan::drop`]. Otherwise it's released from a global queue when dropped
+#[derive(Debug)]
+pub struct SerializePlan {
+    fields: Vec<&'static str>,
+    /// `None` if the function can't be compiled, objects are then created property by property
+    template: Option<Root<JsFunction>>,
+}
+
+impl SerializePlan {
+    /// Prepares a plan for structs serializing `fields` in this order,
+    /// as seen after `#[serde(rename)]`
+    pub fn for_struct<'j, C>(cx: &mut C, fields: &[&'static str]) -> Self
+    where
+        C: Context<'j>,
+    {
+        let template = template::compile_template(cx, false, fields);
+        SerializePlan {
+            fields: fields.to_vec(),
+            template: template.map(|template| template.root(cx)),
+        }
+    }
+
+    /// The fields the plan was prepared for
+    #[must_use]
+    pub fn fields(&self) -> &[&'static str] {
+        &self.fields
+    }
+
+    /// Releases the plan
+    pub fn drop<'j, C>(self, cx: &mut C)
+    where
+        C: Context<'j>,
+    {
+        if let Some(template) = self.template {
+            template.drop(cx);
+        }
+    }
+}
+
+impl Finalize for SerializePlan {
+    fn finalize<'j, C: Context<'j>>(self, cx: &mut C) {
+        self.drop(cx);
+    }
+}
+
+/// Serializes a sequence and appends its elements to `target`, so an array can be built
+/// up across calls
+///
+/// # Errors
+///
+/// * `UnableToCoerce` if the value doesn't serialize to an `Array`, e.g. it isn't a sequence
+/// * See [`to_value`] errors
+///
+pub fn serialize_append<'j, C, V>(
+    cx: &mut C,
+    target: Handle<'j, JsArray>,
+    value: &V,
+) -> LibResult<()>
+where
+    C: Context<'j>,
+    V: Serialize + ?Sized,
+{
+    let elements = to_value(cx, value)?
+        .downcast::<JsArray, C>(cx)
+        .ok()
+        .context(errors::UnableToCoerceSnafu { to_type: "array" })?;
+    let start = target.len(cx);
+    for i in 0..elements.len(cx) {
+        let element = elements.get_value(cx, i)?;
+        target.set(cx, start + i, element)?;
+    }
+    Ok(())
+}
+
+/// Converts two values to one object with the properties of both, like a
+/// `#[serde(flatten)]` of each decided at runtime
+///
+/// The properties of `extra` are set after those of `base`, so they win when both have a key
+///
+/// # Errors
+///
+/// * `UnableToCoerce` if either value doesn't serialize to a plain object
+/// * See [`to_value`] errors
+///
+pub fn to_value_merged<'j, C, B, E>(
+    cx: &mut C,
+    base: &B,
+    extra: &E,
+) -> LibResult<Handle<'j, JsObject>>
+where
+    C: Context<'j>,
+    B: Serialize + ?Sized,
+    E: Serialize + ?Sized,
+{
+    let merged = plain_object(cx, base)?;
+    let extra = plain_object(cx, extra)?;
+    for key in extra.get_own_property_names(cx)?.to_vec(cx)? {
+        let value = extra.get_value(cx, key)?;
+        merged.set(cx, key, value)?;
+    }
+    Ok(merged)
+}
+
+/// Converts a value that must serialize to an object other than an array
+fn plain_object<'j, C, V>(cx: &mut C, value: &V) -> LibResult<Handle<'j, JsObject>>
+where
+    C: Context<'j>,
+    V: Serialize + ?Sized,
+{
+    let value = to_value(cx, value)?;
+    ensure!(
+        !value.is_a::<JsArray, C>(cx),
+        errors::UnableToCoerceSnafu { to_type: "object" }
+    );
+    value
+        .downcast::<JsObject, C>(cx)
+        .ok()
+        .context(errors::UnableToCoerceSnafu { to_type: "object" })
+}
+
+/// Handling of object and array values in [`to_query_params`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum NestedParamPolicy {
+    /// Fail with `NestedQueryParam`
+    #[default]
+    Reject,
+    /// Set the parameter to the JSON of the value
+    Json,
+}
+
+/// Converts a flat struct or map to an object of string values, which can be passed
+/// to `new URLSearchParams()`
+///
+/// Numbers, booleans and `BigInt`s are converted with `String()`, and keys set to `null`
+/// (e.g. `None`) are left out
+///
+/// # Errors
+///
+/// * `UnableToCoerce` if the value doesn't serialize to a plain object
+/// * `NestedQueryParam` if a value is an object or array, with `NestedParamPolicy::Reject`
+/// * See [`to_value`] errors
+///
+pub fn to_query_params<'j, C, V>(
+    cx: &mut C,
+    value: &V,
+    nested: NestedParamPolicy,
+) -> LibResult<Handle<'j, JsObject>>
+where
+    C: Context<'j>,
+    V: Serialize + ?Sized,
+{
+    let fields = plain_object(cx, value)?;
+
+    let params = JsObject::new(cx);
+    for key in fields.get_own_property_names(cx)?.to_vec(cx)? {
+        let field = fields.get_value(cx, key)?;
+        if field.is_a::<JsNull, C>(cx) || field.is_a::<JsUndefined, C>(cx) {
+            continue;
+        }
+        let param = if field.is_a::<JsObject, C>(cx) {
+            if nested == NestedParamPolicy::Reject {
+                let key = key.to_string(cx)?.value(cx);
+                return errors::NestedQueryParamSnafu { key }.fail();
+            }
+            let json: Handle<JsObject> = cx.global("JSON")?;
+            let stringify: Handle<JsFunction> = json.get(cx, "stringify")?;
+            stringify.call_with(cx).this(json).arg(field).apply(cx)?
+        } else {
+            field.to_string(cx)?
+        };
+        params.set(cx, key, param)?;
+    }
+    Ok(params)
+}
+
 #[doc(hidden)]
 pub struct Serializer<'a, 'j, C: 'a>
 where
     C: Context<'j>,
 {
     cx: &'a mut C,
+    opts: &'a SerializeOptions,
+    /// Structures the value is nested in, for `max_depth`
+    depth: usize,
+    /// Plan for the value, only set for the value passed to `to_value_with_plan`
+    plan: Option<&'a SerializePlan>,
     ph: PhantomData<&'j ()>,
 }
 
@@ -65,7 +1167,17 @@ where
     C: Context<'j>,
 {
     cx: &'a mut C,
+    opts: &'a SerializeOptions,
+    /// Depth of the elements
+    depth: usize,
     array: Handle<'j, JsArray>,
+    /// Elements held back while they can still become a typed array, with `typed_arrays`
+    typed: Option<TypedElements>,
+    /// Chars held back while the sequence can still become a string, with `char_vec_as_string`
+    chars: Option<String>,
+    /// Emit elements that are byte sequences (e.g. `&[u8]`) as a `Buffer`,
+    /// for the elements of tuples with `typed_arrays`
+    byte_elements: bool,
 }
 
 #[doc(hidden)]
@@ -83,8 +1195,20 @@ where
     C: Context<'j>,
 {
     cx: &'a mut C,
+    opts: &'a SerializeOptions,
+    /// Depth of the keys and values
+    depth: usize,
     object: Handle<'j, JsObject>,
+    /// `Map.prototype.set`, when `object` is a `Map` with `maps_as_js_map`
+    map_set: Option<Handle<'j, JsFunction>>,
     key_holder: Handle<'j, JsObject>,
+    /// Entries held back until `end`, with `sort_keys`, `sort_map_keys` or `complex_keys_as_pairs`
+    entries: Option<Vec<(Handle<'j, JsValue>, Handle<'j, JsValue>)>>,
+    complex_keys: bool,
+    /// Options for the keys without `boxed_numbers`, when it's set
+    key_opts: Option<SerializeOptions>,
+    /// The current key before `transform_key`, with `transform`
+    key_name: Option<String>,
 }
 
 #[doc(hidden)]
@@ -93,7 +1217,15 @@ where
     C: Context<'j>,
 {
     cx: &'a mut C,
-    object: Handle<'j, JsObject>,
+    opts: &'a SerializeOptions,
+    /// Depth of the field values
+    depth: usize,
+    /// Name of the struct, or of the enum for variants
+    name: &'static str,
+    plan: Option<&'a SerializePlan>,
+    /// Fields held back until `end`, which creates the object with all of them at once
+    keys: Vec<&'static str>,
+    values: Vec<Handle<'j, JsValue>>,
 }
 
 #[doc(hidden)]
@@ -101,10 +1233,26 @@ pub struct StructVariantSerializer<'a, 'j, C: 'a>
 where
     C: Context<'j>,
 {
-    outer_object: Handle<'j, JsObject>,
+    variant: &'static str,
     inner: StructSerializer<'a, 'j, C>,
 }
 
+impl<'j, C> Serializer<'_, 'j, C>
+where
+    C: Context<'j>,
+{
+    /// The depth of the values in the structure being serialized, checking `max_depth`
+    fn nested(&self) -> LibResult<usize> {
+        if let Some(max_depth) = self.opts.max_depth {
+            ensure!(
+                self.depth < max_depth,
+                errors::DepthExceededSnafu { max_depth }
+            );
+        }
+        Ok(self.depth + 1)
+    }
+}
+
 #[doc(hidden)]
 impl<'a, 'j, C> ser::Serializer for Serializer<'a, 'j, C>
 where
@@ -121,6 +1269,10 @@ where
     type SerializeStruct = StructSerializer<'a, 'j, C>;
     type SerializeStructVariant = StructVariantSerializer<'a, 'j, C>;
 
+    fn is_human_readable(&self) -> bool {
+        self.opts.human_readable
+    }
+
     #[inline]
     fn serialize_bool(self, v: bool) -> Result<Self::Ok, Self::Error> {
         Ok(JsBoolean::new(self.cx, v).upcast())
@@ -128,70 +1280,91 @@ where
 
     #[inline]
     fn serialize_i8(self, v: i8) -> Result<Self::Ok, Self::Error> {
-        Ok(JsNumber::new(self.cx, as_num::<_, f64>(v)?).upcast())
+        serialize_int!(self, v, from_i64)
     }
 
     #[inline]
     fn serialize_i16(self, v: i16) -> Result<Self::Ok, Self::Error> {
-        Ok(JsNumber::new(self.cx, as_num::<_, f64>(v)?).upcast())
+        serialize_int!(self, v, from_i64)
     }
 
     #[inline]
     fn serialize_i32(self, v: i32) -> Result<Self::Ok, Self::Error> {
-        Ok(JsNumber::new(self.cx, as_num::<_, f64>(v)?).upcast())
+        serialize_int!(self, v, from_i64)
     }
 
     #[inline]
     fn serialize_i64(self, v: i64) -> Result<Self::Ok, Self::Error> {
-        Ok(JsNumber::new(self.cx, as_num::<_, f64>(v)?).upcast())
+        serialize_int!(self, v, from_i64)
     }
 
     #[inline]
     fn serialize_i128(self, v: i128) -> Result<Self::Ok, Self::Error> {
-        Ok(JsNumber::new(self.cx, as_num::<_, f64>(v)?).upcast())
+        if self.opts.all_ints_as_bigint {
+            Ok(JsBigInt::from_i128(self.cx, v).upcast())
+        } else if (-MAX_SAFE_INTEGER..=MAX_SAFE_INTEGER).contains(&v) {
+            new_number(self.cx, self.opts, as_num::<_, f64>(v)?)
+        } else {
+            serialize_wide_int!(self, v, from_i128)
+        }
     }
 
     #[inline]
     fn serialize_u8(self, v: u8) -> Result<Self::Ok, Self::Error> {
-        Ok(JsNumber::new(self.cx, as_num::<_, f64>(v)?).upcast())
+        serialize_int!(self, v, from_u64)
     }
 
     #[inline]
     fn serialize_u16(self, v: u16) -> Result<Self::Ok, Self::Error> {
-        Ok(JsNumber::new(self.cx, as_num::<_, f64>(v)?).upcast())
+        serialize_int!(self, v, from_u64)
     }
 
     #[inline]
     fn serialize_u32(self, v: u32) -> Result<Self::Ok, Self::Error> {
-        Ok(JsNumber::new(self.cx, as_num::<_, f64>(v)?).upcast())
+        serialize_int!(self, v, from_u64)
     }
 
     #[inline]
     fn serialize_u64(self, v: u64) -> Result<Self::Ok, Self::Error> {
-        Ok(JsNumber::new(self.cx, as_num::<_, f64>(v)?).upcast())
+        serialize_int!(self, v, from_u64)
     }
 
     #[inline]
     fn serialize_u128(self, v: u128) -> Result<Self::Ok, Self::Error> {
-        Ok(JsNumber::new(self.cx, as_num::<_, f64>(v)?).upcast())
+        if self.opts.all_ints_as_bigint {
+            return Ok(JsBigInt::from_u128(self.cx, v).upcast());
+        }
+        match i128::try_from(v) {
+            Ok(v) if v <= MAX_SAFE_INTEGER => new_number(self.cx, self.opts, as_num::<_, f64>(v)?),
+            _ => serialize_wide_int!(self, v, from_u128),
+        }
     }
 
     #[inline]
     fn serialize_f32(self, v: f32) -> Result<Self::Ok, Self::Error> {
-        Ok(JsNumber::new(self.cx, as_num::<_, f64>(v)?).upcast())
+        self.serialize_f64(as_num::<_, f64>(v)?)
     }
 
     #[inline]
     fn serialize_f64(self, v: f64) -> Result<Self::Ok, Self::Error> {
-        Ok(JsNumber::new(self.cx, v).upcast())
+        match float_value(self.opts, v) {
+            Some(v)
+                if self
+                    .opts
+                    .integral_floats_as_bigint_above
+                    .is_some_and(|above| v.fract() == 0.0 && v.abs() > above) =>
+            {
+                Ok(integral_bigint(self.cx, v).upcast())
+            }
+            Some(v) => new_number(self.cx, self.opts, v),
+            None => Ok(self.cx.null().upcast()),
+        }
     }
 
     fn serialize_char(self, v: char) -> Result<Self::Ok, Self::Error> {
         let mut b = [0; 4];
         let result = v.encode_utf8(&mut b);
-        let js_str = JsString::try_new(self.cx, result)
-            .map_err(|_| errors::StringTooLongSnafu { len: 4usize }.build())?;
-        Ok(js_str.upcast())
+        self.serialize_str(result)
     }
 
     #[inline]
@@ -199,6 +1372,12 @@ where
         let len = v.len();
         let js_str = JsString::try_new(self.cx, v)
             .map_err(|_| errors::StringTooLongSnafu { len }.build())?;
+        if self.opts.validate_output_strings {
+            ensure!(
+                js_str.value(self.cx) == v,
+                errors::StringMismatchSnafu { len }
+            );
+        }
         Ok(js_str.upcast())
     }
 
@@ -208,6 +1387,13 @@ where
         buff.as_mut_slice(self.cx).clone_from_slice(v);
         // self.cx
         //     .borrow_mut(&mut buff, |buff| buff.as_mut_slice().clone_from_slice(v));
+        if self.opts.bytes_with_length {
+            let object = new_object(self.cx, self.opts)?;
+            let len = JsNumber::new(self.cx, as_num::<_, f64>(v.len())?);
+            object.set(self.cx, "len", len)?;
+            object.set(self.cx, "data", buff)?;
+            return Ok(object.upcast());
+        }
         Ok(buff.upcast())
     }
 
@@ -217,9 +1403,9 @@ where
     }
 
     #[inline]
-    fn serialize_some<T: ?Sized>(self, value: &T) -> Result<Self::Ok, Self::Error>
+    fn serialize_some<T>(self, value: &T) -> Result<Self::Ok, Self::Error>
     where
-        T: Serialize,
+        T: ?Sized + Serialize,
     {
         value.serialize(self)
     }
@@ -241,23 +1427,39 @@ where
         _variant_index: u32,
         variant: &'static str,
     ) -> Result<Self::Ok, Self::Error> {
-        self.serialize_str(variant)
+        match self.opts.enum_style {
+            EnumStyle::External => self.serialize_str(variant),
+            EnumStyle::TypeValue | EnumStyle::InternallyTagged { .. } => {
+                Ok(variant_object(self.cx, self.opts, variant, None)?.upcast())
+            }
+        }
     }
 
     #[inline]
-    fn serialize_newtype_struct<T: ?Sized>(
+    fn serialize_newtype_struct<T>(
         self,
-        _name: &'static str,
+        name: &'static str,
         value: &T,
     ) -> Result<Self::Ok, Self::Error>
     where
-        T: Serialize,
+        T: ?Sized + Serialize,
     {
+        if name == PATH_NAME && self.opts.paths_forward_slash && MAIN_SEPARATOR != '/' {
+            let path = to_value_at(self.cx, value, self.opts, self.depth)?;
+            if let Ok(path) = path.downcast::<JsString, _>(self.cx) {
+                let path = path.value(self.cx).replace(MAIN_SEPARATOR, "/");
+                return self.serialize_str(&path);
+            }
+            return Ok(path);
+        }
+        if name == UNDEFINED_NAME {
+            return Ok(self.cx.undefined().upcast());
+        }
         value.serialize(self)
     }
 
     #[inline]
-    fn serialize_newtype_variant<T: ?Sized>(
+    fn serialize_newtype_variant<T>(
         self,
         _name: &'static str,
         _variant_index: u32,
@@ -265,23 +1467,33 @@ where
         value: &T,
     ) -> Result<Self::Ok, Self::Error>
     where
-        T: Serialize,
+        T: ?Sized + Serialize,
     {
-        let obj = JsObject::new(&mut *self.cx);
-        let value_js = to_value(self.cx, value)?;
-        obj.set(self.cx, variant, value_js)?;
+        let depth = if self.opts.flatten_newtype_variants {
+            self.depth
+        } else {
+            self.nested()?
+        };
+        let value_js = to_value_at(self.cx, value, self.opts, depth)?;
+        if self.opts.flatten_newtype_variants {
+            return Ok(value_js);
+        }
+        let obj = variant_object(self.cx, self.opts, variant, Some(value_js))?;
 
         Ok(obj.upcast())
     }
 
     #[inline]
     fn serialize_seq(self, _len: Option<usize>) -> Result<Self::SerializeSeq, Self::Error> {
-        Ok(ArraySerializer::new(self.cx))
+        // the array grows as elements are set, so `collect_seq` works without a length
+        let depth = self.nested()?;
+        Ok(ArraySerializer::new_seq(self.cx, self.opts, depth))
     }
 
     #[inline]
     fn serialize_tuple(self, _len: usize) -> Result<Self::SerializeTuple, Self::Error> {
-        Ok(ArraySerializer::new(self.cx))
+        let depth = self.nested()?;
+        Ok(ArraySerializer::new_tuple(self.cx, self.opts, depth))
     }
 
     #[inline]
@@ -290,7 +1502,8 @@ where
         _name: &'static str,
         _len: usize,
     ) -> Result<Self::SerializeTupleStruct, Self::Error> {
-        Ok(ArraySerializer::new(self.cx))
+        let depth = self.nested()?;
+        Ok(ArraySerializer::new_tuple(self.cx, self.opts, depth))
     }
 
     #[inline]
@@ -301,32 +1514,40 @@ where
         variant: &'static str,
         _len: usize,
     ) -> Result<Self::SerializeTupleVariant, Self::Error> {
-        TupleVariantSerializer::new(self.cx, variant)
+        let depth = self.nested()?;
+        TupleVariantSerializer::new(self.cx, self.opts, depth, variant)
     }
 
     #[inline]
     fn serialize_map(self, _len: Option<usize>) -> Result<Self::SerializeMap, Self::Error> {
-        Ok(MapSerializer::new(self.cx))
+        let depth = self.nested()?;
+        MapSerializer::new(self.cx, self.opts, depth)
     }
 
     #[inline]
     fn serialize_struct(
         self,
-        _name: &'static str,
-        _len: usize,
+        name: &'static str,
+        len: usize,
     ) -> Result<Self::SerializeStruct, Self::Error> {
-        Ok(StructSerializer::new(self.cx))
+        let depth = self.nested()?;
+        Ok(StructSerializer::new(
+            self.cx, self.opts, depth, name, len, self.plan,
+        ))
     }
 
     #[inline]
     fn serialize_struct_variant(
         self,
-        _name: &'static str,
+        name: &'static str,
         _variant_index: u32,
         variant: &'static str,
-        _len: usize,
+        len: usize,
     ) -> Result<Self::SerializeStructVariant, Self::Error> {
-        StructVariantSerializer::new(self.cx, variant)
+        let depth = self.nested()?;
+        Ok(StructVariantSerializer::new(
+            self.cx, self.opts, depth, name, variant, len,
+        ))
     }
 }
 
@@ -336,25 +1557,95 @@ where
     C: Context<'j>,
 {
     #[inline]
-    fn new(cx: &'a mut C) -> Self {
+    fn new(cx: &'a mut C, opts: &'a SerializeOptions, depth: usize) -> Self {
         let array = JsArray::new(cx, 0);
-        ArraySerializer { cx, array }
+        ArraySerializer {
+            cx,
+            opts,
+            depth,
+            array,
+            typed: None,
+            chars: None,
+            byte_elements: false,
+        }
+    }
+
+    /// Like `new` but for tuples, whose byte sequences become buffers with `typed_arrays`
+    #[inline]
+    fn new_tuple(cx: &'a mut C, opts: &'a SerializeOptions, depth: usize) -> Self {
+        let mut serializer = ArraySerializer::new(cx, opts, depth);
+        serializer.byte_elements = opts.typed_arrays;
+        serializer
+    }
+
+    /// Like `new` but for sequences, which may become typed arrays
+    #[inline]
+    fn new_seq(cx: &'a mut C, opts: &'a SerializeOptions, depth: usize) -> Self {
+        let mut serializer = ArraySerializer::new(cx, opts, depth);
+        if opts.typed_arrays {
+            serializer.typed = Some(TypedElements::default());
+        }
+        if opts.char_vec_as_string {
+            serializer.chars = Some(String::new());
+        }
+        serializer
     }
 }
 
 #[doc(hidden)]
-impl<'a, 'j, C> ser::SerializeSeq for ArraySerializer<'a, 'j, C>
+impl<'j, C> ser::SerializeSeq for ArraySerializer<'_, 'j, C>
 where
     C: Context<'j>,
 {
     type Ok = Handle<'j, JsValue>;
     type Error = Error;
 
-    fn serialize_element<T: ?Sized>(&mut self, value: &T) -> Result<(), Self::Error>
+    fn serialize_element<T>(&mut self, value: &T) -> Result<(), Self::Error>
     where
-        T: Serialize,
+        T: ?Sized + Serialize,
     {
-        let value = to_value(self.cx, value)?;
+        if let Some(chars) = &mut self.chars {
+            if let Some(Scalar::Char(c)) = Scalar::of(value, self.opts) {
+                chars.push(c);
+                return Ok(());
+            }
+            // not all chars after all, keep the chars seen so far as strings
+            for (i, c) in (0u32..).zip(chars.chars()) {
+                let c = to_value_at(self.cx, &c, self.opts, self.depth)?;
+                self.array.set(self.cx, i, c)?;
+            }
+            if !chars.is_empty() {
+                // the strings are in the array, which can't be a typed array anymore
+                self.typed = None;
+            }
+            self.chars = None;
+        }
+        if let Some(typed) = &mut self.typed {
+            if Scalar::of(value, self.opts).is_some_and(|scalar| typed.push(scalar)) {
+                return Ok(());
+            }
+            // not a single number type after all, keep the elements seen so far
+            typed.write_numbers(self.cx, self.opts, self.array)?;
+            self.typed = None;
+        }
+
+        let bytes = if self.byte_elements {
+            typed::bytes_of(value)
+        } else {
+            None
+        };
+        let value = if let Some(bytes) = bytes {
+            let serializer = Serializer {
+                cx: &mut *self.cx,
+                opts: self.opts,
+                depth: self.depth,
+                plan: None,
+                ph: PhantomData,
+            };
+            ser::Serializer::serialize_bytes(serializer, &bytes)?
+        } else {
+            to_value_at(self.cx, value, self.opts, self.depth)?
+        };
 
         let arr: Handle<'j, JsArray> = self.array;
         let len = arr.len(self.cx);
@@ -364,11 +1655,22 @@ where
 
     #[inline]
     fn end(self) -> Result<Self::Ok, Self::Error> {
+        if let Some(chars) = self.chars.as_deref().filter(|chars| !chars.is_empty()) {
+            return to_value_at(self.cx, chars, self.opts, self.depth);
+        }
+        if let Some(typed) = &self.typed {
+            if let Some(array) = typed.to_js(self.cx)? {
+                return Ok(array);
+            }
+        }
+        if self.opts.empty_as_null && self.array.len(self.cx) == 0 {
+            return Ok(self.cx.null().upcast());
+        }
         Ok(self.array.upcast())
     }
 }
 
-impl<'a, 'j, C> ser::SerializeTuple for ArraySerializer<'a, 'j, C>
+impl<'j, C> ser::SerializeTuple for ArraySerializer<'_, 'j, C>
 where
     C: Context<'j>,
 {
@@ -376,9 +1678,9 @@ where
     type Error = Error;
 
     #[inline]
-    fn serialize_element<T: ?Sized>(&mut self, value: &T) -> Result<(), Self::Error>
+    fn serialize_element<T>(&mut self, value: &T) -> Result<(), Self::Error>
     where
-        T: Serialize,
+        T: ?Sized + Serialize,
     {
         ser::SerializeSeq::serialize_element(self, value)
     }
@@ -390,7 +1692,7 @@ where
 }
 
 #[doc(hidden)]
-impl<'a, 'j, C> ser::SerializeTupleStruct for ArraySerializer<'a, 'j, C>
+impl<'j, C> ser::SerializeTupleStruct for ArraySerializer<'_, 'j, C>
 where
     C: Context<'j>,
 {
@@ -398,9 +1700,9 @@ where
     type Error = Error;
 
     #[inline]
-    fn serialize_field<T: ?Sized>(&mut self, value: &T) -> Result<(), Self::Error>
+    fn serialize_field<T>(&mut self, value: &T) -> Result<(), Self::Error>
     where
-        T: Serialize,
+        T: ?Sized + Serialize,
     {
         ser::SerializeSeq::serialize_element(self, value)
     }
@@ -416,22 +1718,31 @@ impl<'a, 'j, C> TupleVariantSerializer<'a, 'j, C>
 where
     C: Context<'j>,
 {
-    fn new(cx: &'a mut C, key: &'static str) -> LibResult<Self> {
+    fn new(
+        cx: &'a mut C,
+        opts: &'a SerializeOptions,
+        depth: usize,
+        key: &'static str,
+    ) -> LibResult<Self> {
         let inner_array = JsArray::new(cx, 0);
-        let outter_object = JsObject::new(cx);
-        outter_object.set(cx, key, inner_array)?;
+        let outter_object = variant_object(cx, opts, key, Some(inner_array.upcast()))?;
         Ok(TupleVariantSerializer {
             outter_object,
             inner: ArraySerializer {
                 cx,
+                opts,
+                depth,
                 array: inner_array,
+                typed: None,
+                chars: None,
+                byte_elements: opts.typed_arrays,
             },
         })
     }
 }
 
 #[doc(hidden)]
-impl<'a, 'j, C> ser::SerializeTupleVariant for TupleVariantSerializer<'a, 'j, C>
+impl<'j, C> ser::SerializeTupleVariant for TupleVariantSerializer<'_, 'j, C>
 where
     C: Context<'j>,
 {
@@ -439,9 +1750,9 @@ where
     type Error = Error;
 
     #[inline]
-    fn serialize_field<T: ?Sized>(&mut self, value: &T) -> Result<(), Self::Error>
+    fn serialize_field<T>(&mut self, value: &T) -> Result<(), Self::Error>
     where
-        T: Serialize,
+        T: ?Sized + Serialize,
     {
         use serde::ser::SerializeSeq;
         self.inner.serialize_element(value)
@@ -458,47 +1769,176 @@ impl<'a, 'j, C> MapSerializer<'a, 'j, C>
 where
     C: Context<'j>,
 {
-    fn new(cx: &'a mut C) -> Self {
-        let object = JsObject::new(cx);
+    fn new(cx: &'a mut C, opts: &'a SerializeOptions, depth: usize) -> LibResult<Self> {
+        let (object, map_set) = if opts.maps_as_js_map {
+            let map_ctor: Handle<JsFunction> = cx.global("Map")?;
+            let map: Handle<JsObject> = map_ctor.construct_with(cx).apply(cx)?;
+            let set: Handle<JsFunction> = map.get(cx, "set")?;
+            (map, Some(set))
+        } else {
+            (new_object(cx, opts)?, None)
+        };
         let key_holder = JsObject::new(cx);
-        MapSerializer {
+        let complex_pairs = opts.complex_keys_as_pairs && !opts.maps_as_js_map;
+        let entries = (opts.sort_keys || opts.sort_map_keys || complex_pairs).then(Vec::new);
+        let key_opts = opts.boxed_numbers.then(|| SerializeOptions {
+            boxed_numbers: false,
+            ..opts.clone()
+        });
+        Ok(MapSerializer {
             cx,
+            opts,
+            depth,
             object,
+            map_set,
             key_holder,
+            entries,
+            complex_keys: false,
+            key_opts,
+            key_name: None,
+        })
+    }
+
+    /// Sets an entry of the object, or of the `Map` with `maps_as_js_map`
+    fn set(&mut self, key: Handle<'j, JsValue>, value: Handle<'j, JsValue>) -> LibResult<()> {
+        match self.map_set {
+            Some(set) => set
+                .call_with(self.cx)
+                .this(self.object)
+                .arg(key)
+                .arg(value)
+                .exec(self.cx)?,
+            None => {
+                self.object.set(self.cx, key, value)?;
+            }
         }
+        Ok(())
+    }
+
+    /// The finished object, `null` if it's empty with `empty_as_null`
+    fn finish(self) -> LibResult<Handle<'j, JsValue>> {
+        if self.map_set.is_some() {
+            return Ok(self.object.upcast());
+        }
+        object_or_null(self.cx, self.opts, self.object)
     }
 }
 
 #[doc(hidden)]
-impl<'a, 'j, C> ser::SerializeMap for MapSerializer<'a, 'j, C>
+impl<'j, C> ser::SerializeMap for MapSerializer<'_, 'j, C>
 where
     C: Context<'j>,
 {
     type Ok = Handle<'j, JsValue>;
     type Error = Error;
 
-    fn serialize_key<T: ?Sized>(&mut self, key: &T) -> Result<(), Self::Error>
+    fn serialize_key<T>(&mut self, key: &T) -> Result<(), Self::Error>
     where
-        T: Serialize,
+        T: ?Sized + Serialize,
     {
-        let key = to_value(self.cx, key)?;
+        let key_opts = self.key_opts.as_ref().unwrap_or(self.opts);
+        let wide_key = if self.map_set.is_some() {
+            match Scalar::of(key, key_opts) {
+                Some(Scalar::I64(v))
+                    if !(-MAX_SAFE_INTEGER..=MAX_SAFE_INTEGER).contains(&v.into()) =>
+                {
+                    Some(JsBigInt::from_i64(self.cx, v))
+                }
+                Some(Scalar::U64(v)) if i128::from(v) > MAX_SAFE_INTEGER => {
+                    Some(JsBigInt::from_u64(self.cx, v))
+                }
+                _ => None,
+            }
+        } else {
+            None
+        };
+        let mut key = match wide_key {
+            Some(key) => key.upcast(),
+            None => to_value_at(self.cx, key, key_opts, self.depth)?,
+        };
+        if let Some(transform) = &self.opts.transform {
+            let name = key.to_string(self.cx)?.value(self.cx);
+            if key.is_a::<JsString, _>(self.cx) {
+                if let Cow::Owned(renamed) = transform.transform_key(&name) {
+                    key = self.cx.string(renamed).upcast();
+                }
+            }
+            self.key_name = Some(name);
+        }
+        if self.opts.on_non_ascii_key != NonAsciiKeyPolicy::Allow {
+            if let Ok(name) = key.downcast::<JsString, _>(self.cx) {
+                if let Some(escaped) = ascii_key(self.opts, &name.value(self.cx))? {
+                    key = self.cx.string(escaped).upcast();
+                }
+            }
+        }
+        if self.opts.complex_keys_as_pairs
+            && self.map_set.is_none()
+            && key.is_a::<JsObject, _>(self.cx)
+        {
+            self.complex_keys = true;
+        }
         self.key_holder.set(self.cx, "key", key)?;
         Ok(())
     }
 
-    fn serialize_value<T: ?Sized>(&mut self, value: &T) -> Result<(), Self::Error>
+    fn serialize_value<T>(&mut self, value: &T) -> Result<(), Self::Error>
     where
-        T: Serialize,
+        T: ?Sized + Serialize,
     {
         let key: Handle<'j, JsValue> = self.key_holder.get(&mut *self.cx, "key")?;
-        let value_obj = to_value(self.cx, value)?;
-        self.object.set(self.cx, key, value_obj)?;
+        let name = self.key_name.take().unwrap_or_default();
+        if self.opts.map_none_policy == NonePolicy::Omit && typed::is_none(value) {
+            return Ok(());
+        }
+        let Some(value_obj) = property_value(self.cx, self.opts, self.depth, &name, value)? else {
+            return Ok(());
+        };
+        if let Some(entries) = &mut self.entries {
+            entries.push((key, value_obj));
+        } else {
+            self.set(key, value_obj)?;
+        }
         Ok(())
     }
 
-    #[inline]
-    fn end(self) -> Result<Self::Ok, Self::Error> {
-        Ok(self.object.upcast())
+    fn end(mut self) -> Result<Self::Ok, Self::Error> {
+        let Some(entries) = self.entries.take() else {
+            return self.finish();
+        };
+
+        if self.complex_keys {
+            let pairs = JsArray::new(self.cx, entries.len());
+            for (i, (key, value)) in entries.into_iter().enumerate() {
+                let pair = JsArray::new(self.cx, 2);
+                pair.set(self.cx, 0, key)?;
+                pair.set(self.cx, 1, value)?;
+                pairs.set(self.cx, as_num::<_, u32>(i)?, pair)?;
+            }
+            return Ok(pairs.upcast());
+        }
+
+        if self.opts.sort_keys || self.opts.sort_map_keys {
+            let mut keyed = Vec::with_capacity(entries.len());
+            for (key, value) in entries {
+                keyed.push((key.to_string(self.cx)?.value(self.cx), (key, value)));
+            }
+            keyed.sort_by(|a, b| a.0.cmp(&b.0));
+            for (name, (key, value)) in keyed {
+                // object keys are set by name as before, a `Map` keeps the typed key
+                let key = if self.map_set.is_some() {
+                    key
+                } else {
+                    self.cx.string(name).upcast()
+                };
+                self.set(key, value)?;
+            }
+        } else {
+            for (key, value) in entries {
+                self.set(key, value)?;
+            }
+        }
+        self.finish()
     }
 }
 
@@ -508,14 +1948,95 @@ where
     C: Context<'j>,
 {
     #[inline]
-    fn new(cx: &'a mut C) -> Self {
-        let object = JsObject::new(cx);
-        StructSerializer { cx, object }
+    fn new(
+        cx: &'a mut C,
+        opts: &'a SerializeOptions,
+        depth: usize,
+        name: &'static str,
+        len: usize,
+        plan: Option<&'a SerializePlan>,
+    ) -> Self {
+        StructSerializer {
+            cx,
+            opts,
+            depth,
+            name,
+            plan,
+            keys: Vec::with_capacity(len),
+            values: Vec::with_capacity(len),
+        }
+    }
+
+    /// Creates the object from the fields so far, even without fields,
+    /// with its metadata for `metadata_symbol`
+    fn build(&mut self) -> LibResult<Handle<'j, JsObject>> {
+        let object = self.create()?;
+        let Some((key, meta)) = self.opts.metadata_symbol else {
+            return Ok(object);
+        };
+        let Some(properties) = meta(self.name) else {
+            return Ok(object);
+        };
+        let symbol_ctor: Handle<JsFunction> = self.cx.global("Symbol")?;
+        let symbol_for: Handle<JsFunction> = symbol_ctor.get(self.cx, "for")?;
+        let key = self.cx.string(key);
+        let symbol: Handle<JsValue> = symbol_for.call_with(self.cx).arg(key).apply(self.cx)?;
+        let metadata = self.cx.empty_object();
+        for (name, value) in properties {
+            let value = self.cx.string(value);
+            metadata.set(self.cx, name, value)?;
+        }
+        object.set(self.cx, symbol, metadata)?;
+        Ok(object)
+    }
+
+    fn create(&mut self) -> LibResult<Handle<'j, JsObject>> {
+        if let Some(transform) = &self.opts.transform {
+            // renamed fields aren't `'static` like the template keys
+            let mut fields = Vec::with_capacity(self.keys.len());
+            for (key, value) in self.keys.iter().zip(&self.values) {
+                let key = transform.transform_key(key);
+                let key = ascii_key(self.opts, &key)?.unwrap_or_else(|| key.into_owned());
+                fields.push((key, *value));
+            }
+            if self.opts.sort_keys {
+                fields.sort_by(|a, b| a.0.cmp(&b.0));
+            }
+            let (keys, values): (Vec<_>, Vec<_>) = fields.into_iter().unzip();
+            return template::set_each(self.cx, self.opts, &keys, &values);
+        }
+        if self.opts.sort_keys {
+            let keys = std::mem::take(&mut self.keys);
+            let values = std::mem::take(&mut self.values);
+            let mut fields: Vec<_> = keys.into_iter().zip(values).collect();
+            fields.sort_by_key(|(key, _)| *key);
+            (self.keys, self.values) = fields.into_iter().unzip();
+        }
+        if self.opts.on_non_ascii_key != NonAsciiKeyPolicy::Allow
+            && !self.keys.iter().all(|key| key.is_ascii())
+        {
+            // escaped names aren't `'static` like the template keys
+            let mut keys = Vec::with_capacity(self.keys.len());
+            for key in &self.keys {
+                keys.push(ascii_key(self.opts, key)?.unwrap_or_else(|| (*key).to_owned()));
+            }
+            return template::set_each(self.cx, self.opts, &keys, &self.values);
+        }
+        match self.plan {
+            Some(plan) if !self.opts.null_prototype && plan.fields == self.keys => {
+                let Some(template) = &plan.template else {
+                    return template::set_each(self.cx, self.opts, &self.keys, &self.values);
+                };
+                let template = template.to_inner(self.cx);
+                template::call_template(self.cx, template, &self.values)
+            }
+            _ => template::new_struct(self.cx, self.opts, &self.keys, &self.values),
+        }
     }
 }
 
 #[doc(hidden)]
-impl<'a, 'j, C> ser::SerializeStruct for StructSerializer<'a, 'j, C>
+impl<'j, C> ser::SerializeStruct for StructSerializer<'_, 'j, C>
 where
     C: Context<'j>,
 {
@@ -523,22 +2044,24 @@ where
     type Error = Error;
 
     #[inline]
-    fn serialize_field<T: ?Sized>(
-        &mut self,
-        key: &'static str,
-        value: &T,
-    ) -> Result<(), Self::Error>
+    fn serialize_field<T>(&mut self, key: &'static str, value: &T) -> Result<(), Self::Error>
     where
-        T: Serialize,
+        T: ?Sized + Serialize,
     {
-        let value = to_value(self.cx, value)?;
-        self.object.set(self.cx, key, value)?;
+        let Some(value) = property_value(self.cx, self.opts, self.depth, key, value)? else {
+            return Ok(());
+        };
+        self.keys.push(key);
+        self.values.push(value);
         Ok(())
     }
 
     #[inline]
-    fn end(self) -> Result<Self::Ok, Self::Error> {
-        Ok(self.object.upcast())
+    fn end(mut self) -> Result<Self::Ok, Self::Error> {
+        if self.opts.empty_as_null && self.keys.is_empty() {
+            return Ok(self.cx.null().upcast());
+        }
+        Ok(self.build()?.upcast())
     }
 }
 
@@ -547,22 +2070,23 @@ impl<'a, 'j, C> StructVariantSerializer<'a, 'j, C>
 where
     C: Context<'j>,
 {
-    fn new(cx: &'a mut C, key: &'static str) -> LibResult<Self> {
-        let inner_object = JsObject::new(cx);
-        let outter_object = JsObject::new(cx);
-        outter_object.set(cx, key, inner_object)?;
-        Ok(StructVariantSerializer {
-            outer_object: outter_object,
-            inner: StructSerializer {
-                cx,
-                object: inner_object,
-            },
-        })
+    fn new(
+        cx: &'a mut C,
+        opts: &'a SerializeOptions,
+        depth: usize,
+        name: &'static str,
+        variant: &'static str,
+        len: usize,
+    ) -> Self {
+        StructVariantSerializer {
+            variant,
+            inner: StructSerializer::new(cx, opts, depth, name, len, None),
+        }
     }
 }
 
 #[doc(hidden)]
-impl<'a, 'j, C> ser::SerializeStructVariant for StructVariantSerializer<'a, 'j, C>
+impl<'j, C> ser::SerializeStructVariant for StructVariantSerializer<'_, 'j, C>
 where
     C: Context<'j>,
 {
@@ -570,20 +2094,30 @@ where
     type Error = Error;
 
     #[inline]
-    fn serialize_field<T: ?Sized>(
-        &mut self,
-        key: &'static str,
-        value: &T,
-    ) -> Result<(), Self::Error>
+    fn serialize_field<T>(&mut self, key: &'static str, value: &T) -> Result<(), Self::Error>
     where
-        T: Serialize,
+        T: ?Sized + Serialize,
     {
         use serde::ser::SerializeStruct;
         self.inner.serialize_field(key, value)
     }
 
     #[inline]
-    fn end(self) -> Result<Self::Ok, Self::Error> {
-        Ok(self.outer_object.upcast())
+    fn end(mut self) -> Result<Self::Ok, Self::Error> {
+        if let EnumStyle::InternallyTagged { tag } = self.inner.opts.enum_style {
+            // the tag goes first, with the fields, so the template is still used
+            let name = self.inner.cx.string(self.variant);
+            self.inner.keys.insert(0, tag);
+            self.inner.values.insert(0, name.upcast());
+            return Ok(self.inner.build()?.upcast());
+        }
+        let object = self.inner.build()?;
+        let outer = variant_object(
+            self.inner.cx,
+            self.inner.opts,
+            self.variant,
+            Some(object.upcast()),
+        )?;
+        Ok(outer.upcast())
     }
 }
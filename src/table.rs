@@ -0,0 +1,214 @@
+//!
+//! Deduplicates the strings of a serialized value into a table, for
+//! `SerializeOptions::string_table` and `DeserializeOptions::string_table`
+//!
+//! `{ a: "x", b: ["x"] }` is `{ strings: ["a", "x", "b"], data: { "#0": "1", "#2": ["1"] } }`.
+//! Every string in `data` is the index of a string of the table, so numbers and other
+//! values are kept as they are. Property names are the index after a `#`, as integer-like
+//! names would be enumerated in ascending order rather than in the order they were set
+//!
+
+use crate::{
+    errors::{self, Result as LibResult},
+    template,
+};
+use neon::prelude::*;
+use snafu::OptionExt;
+use std::collections::HashMap;
+
+/// Before the index of a property name, so it isn't an integer-like name
+const KEY_PREFIX: char = '#';
+
+/// Replaces the strings of `value` with their index in a table of unique strings
+pub(crate) fn encode<'j, C>(
+    cx: &mut C,
+    value: Handle<'j, JsValue>,
+) -> LibResult<Handle<'j, JsObject>>
+where
+    C: Context<'j>,
+{
+    let mut encoder = Encoder::default();
+    let data = encoder.encode(cx, value)?;
+    let strings = JsArray::new(cx, encoder.strings.len());
+    for (i, string) in (0u32..).zip(encoder.strings) {
+        let string = cx.string(string);
+        strings.set(cx, i, string)?;
+    }
+    let table = cx.empty_object();
+    table.set(cx, "strings", strings)?;
+    table.set(cx, "data", data)?;
+    Ok(table)
+}
+
+/// Restores the value of a table created by [`encode`]
+///
+/// # Errors
+///
+/// * `InvalidStringTable` if `table` isn't an object with a `strings` array, or a string
+///   or property name of its `data` isn't an index in it
+pub(crate) fn decode<'j, C>(
+    cx: &mut C,
+    table: Handle<'j, JsValue>,
+) -> LibResult<Handle<'j, JsValue>>
+where
+    C: Context<'j>,
+{
+    let table =
+        table
+            .downcast::<JsObject, C>(cx)
+            .ok()
+            .context(errors::InvalidStringTableSnafu {
+                reason: "expected an object",
+            })?;
+    let strings = table
+        .get_value(cx, "strings")?
+        .downcast::<JsArray, C>(cx)
+        .ok()
+        .context(errors::InvalidStringTableSnafu {
+            reason: "expected a `strings` array",
+        })?;
+    let mut decoder = Decoder {
+        strings: Vec::with_capacity(strings.len(cx) as usize),
+    };
+    for string in strings.to_vec(cx)? {
+        let string =
+            string
+                .downcast::<JsString, C>(cx)
+                .ok()
+                .context(errors::InvalidStringTableSnafu {
+                    reason: "expected only strings in `strings`",
+                })?;
+        decoder.strings.push(string);
+    }
+    let data = table.get_value(cx, "data")?;
+    decoder.decode(cx, data)
+}
+
+/// Whether `value` is an object created from a struct or map, rather than e.g. a `Map`,
+/// a `Buffer` or a boxed number, which are kept as they are
+fn is_plain_object<'j, C>(
+    cx: &mut C,
+    value: Handle<'j, JsValue>,
+) -> LibResult<Option<Handle<'j, JsObject>>>
+where
+    C: Context<'j>,
+{
+    let Ok(object) = value.downcast::<JsObject, C>(cx) else {
+        return Ok(None);
+    };
+    if value.is_a::<JsArray, C>(cx) || value.is_a::<JsFunction, C>(cx) {
+        return Ok(None);
+    }
+    let object_ctor: Handle<JsFunction> = cx.global("Object")?;
+    let get_prototype: Handle<JsFunction> = object_ctor.get(cx, "getPrototypeOf")?;
+    let prototype: Handle<JsValue> = get_prototype.call_with(cx).arg(object).apply(cx)?;
+    let object_prototype: Handle<JsValue> = object_ctor.get(cx, "prototype")?;
+    let plain = prototype.is_a::<JsNull, C>(cx) || prototype.strict_equals(cx, object_prototype);
+    Ok(plain.then_some(object))
+}
+
+#[derive(Default)]
+struct Encoder {
+    indices: HashMap<String, usize>,
+    strings: Vec<String>,
+}
+
+impl Encoder {
+    /// The index of `string` in the table, adding it the first time it's seen
+    fn index(&mut self, string: String) -> String {
+        let next = self.strings.len();
+        let index = *self.indices.entry(string.clone()).or_insert(next);
+        if index == next {
+            self.strings.push(string);
+        }
+        index.to_string()
+    }
+
+    fn encode<'j, C>(
+        &mut self,
+        cx: &mut C,
+        value: Handle<'j, JsValue>,
+    ) -> LibResult<Handle<'j, JsValue>>
+    where
+        C: Context<'j>,
+    {
+        if let Ok(string) = value.downcast::<JsString, C>(cx) {
+            let index = self.index(string.value(cx));
+            return Ok(cx.string(index).upcast());
+        }
+        if let Ok(array) = value.downcast::<JsArray, C>(cx) {
+            let len = array.len(cx);
+            let encoded = JsArray::new(cx, len as usize);
+            for (i, element) in (0u32..).zip(array.to_vec(cx)?) {
+                let element = self.encode(cx, element)?;
+                encoded.set(cx, i, element)?;
+            }
+            return Ok(encoded.upcast());
+        }
+        let Some(object) = is_plain_object(cx, value)? else {
+            return Ok(value);
+        };
+        let encoded = cx.empty_object();
+        for key in object.get_own_property_names(cx)?.to_vec(cx)? {
+            let field = object.get_value(cx, key)?;
+            let name = key.to_string(cx)?.value(cx);
+            let key = format!("{KEY_PREFIX}{}", self.index(name));
+            let field = self.encode(cx, field)?;
+            encoded.set(cx, key.as_str(), field)?;
+        }
+        Ok(encoded.upcast())
+    }
+}
+
+struct Decoder<'j> {
+    strings: Vec<Handle<'j, JsString>>,
+}
+
+impl<'j> Decoder<'j> {
+    /// The string of the table at `index`
+    fn string(&self, index: &str) -> LibResult<Handle<'j, JsString>> {
+        let string = index
+            .parse::<usize>()
+            .ok()
+            .and_then(|i| self.strings.get(i));
+        string.copied().context(errors::InvalidStringTableSnafu {
+            reason: "expected the strings and property names in `data` to be indices in `strings`",
+        })
+    }
+
+    fn decode<C>(&self, cx: &mut C, value: Handle<'j, JsValue>) -> LibResult<Handle<'j, JsValue>>
+    where
+        C: Context<'j>,
+    {
+        if let Ok(index) = value.downcast::<JsString, C>(cx) {
+            let index = index.value(cx);
+            return Ok(self.string(&index)?.upcast());
+        }
+        if let Ok(array) = value.downcast::<JsArray, C>(cx) {
+            let len = array.len(cx);
+            let decoded = JsArray::new(cx, len as usize);
+            for (i, element) in (0u32..).zip(array.to_vec(cx)?) {
+                let element = self.decode(cx, element)?;
+                decoded.set(cx, i, element)?;
+            }
+            return Ok(decoded.upcast());
+        }
+        let Some(object) = is_plain_object(cx, value)? else {
+            return Ok(value);
+        };
+        let decoded = cx.empty_object();
+        for key in object.get_own_property_names(cx)?.to_vec(cx)? {
+            let field = object.get_value(cx, key)?;
+            let field = self.decode(cx, field)?;
+            let name = key.to_string(cx)?.value(cx);
+            let index = name.strip_prefix(KEY_PREFIX).unwrap_or_default();
+            let key = self.string(index)?.value(cx);
+            if key == "__proto__" {
+                template::define_own(cx, decoded, &key, field)?;
+            } else {
+                decoded.set(cx, key.as_str(), field)?;
+            }
+        }
+        Ok(decoded.upcast())
+    }
+}
@@ -0,0 +1,194 @@
+//!
+//! Creates struct objects through a JS function per set of field names,
+//! so an object is created with all of its fields in a single call
+//!
+
+use crate::{
+    errors::Result as LibResult,
+    ser::{new_object, SerializeOptions},
+};
+use neon::{prelude::*, thread::LocalKey};
+use std::{
+    collections::HashMap,
+    fmt::Write,
+    sync::{Mutex, PoisonError},
+};
+
+/// Compiled functions by field names, `None` if the function can't be compiled
+/// (e.g. with `--disallow-code-generation-from-strings`)
+///
+/// Field names are `'static` so there is one entry per shape of struct,
+/// including the shapes left by `#[serde(skip_serializing_if)]`
+type Templates = Mutex<HashMap<Vec<&'static str>, Option<Root<JsFunction>>>>;
+
+static TEMPLATES: LocalKey<Templates> = LocalKey::new();
+static NULL_PROTOTYPE_TEMPLATES: LocalKey<Templates> = LocalKey::new();
+
+/// Creates an object with `keys` set to `values` in order
+pub(crate) fn new_struct<'j, C>(
+    cx: &mut C,
+    opts: &SerializeOptions,
+    keys: &[&'static str],
+    values: &[Handle<'j, JsValue>],
+) -> LibResult<Handle<'j, JsObject>>
+where
+    C: Context<'j>,
+{
+    let null_prototype = opts.null_prototype;
+    let templates = if null_prototype {
+        NULL_PROTOTYPE_TEMPLATES.get_or_init_default(cx)
+    } else {
+        TEMPLATES.get_or_init_default(cx)
+    };
+
+    let cached = templates
+        .lock()
+        .unwrap_or_else(PoisonError::into_inner)
+        .get(keys)
+        .map(|template| template.as_ref().map(|template| template.to_inner(cx)));
+    let template = if let Some(template) = cached {
+        template
+    } else {
+        let template = compile_template(cx, null_prototype, keys);
+        let root = template.map(|template| template.root(cx));
+        templates
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .insert(keys.to_vec(), root);
+        template
+    };
+
+    let Some(template) = template else {
+        return set_each(cx, opts, keys, values);
+    };
+    call_template(cx, template, values)
+}
+
+/// Compiles the function creating objects with `keys`, `None` if it can't be compiled
+pub(crate) fn compile_template<'j, C>(
+    cx: &mut C,
+    null_prototype: bool,
+    keys: &[&'static str],
+) -> Option<Handle<'j, JsFunction>>
+where
+    C: Context<'j>,
+{
+    cx.try_catch(|cx| compile(cx, null_prototype, keys)).ok()
+}
+
+/// Creates an object by calling a compiled template with the values of its keys
+pub(crate) fn call_template<'j, C>(
+    cx: &mut C,
+    template: Handle<'j, JsFunction>,
+    values: &[Handle<'j, JsValue>],
+) -> LibResult<Handle<'j, JsObject>>
+where
+    C: Context<'j>,
+{
+    let mut call = template.call_with(cx);
+    for value in values {
+        call.arg(*value);
+    }
+    Ok(call.apply(cx)?)
+}
+
+/// Compiles `function (v0, v1, ..) { return { "key0": v0, "key1": v1, .. } }`
+fn compile<'j, C>(
+    cx: &mut C,
+    null_prototype: bool,
+    keys: &[&'static str],
+) -> NeonResult<Handle<'j, JsFunction>>
+where
+    C: Context<'j>,
+{
+    let mut params = String::new();
+    let mut body = String::from("return {");
+    if null_prototype {
+        body.push_str("__proto__: null,");
+    }
+    for (i, key) in keys.iter().enumerate() {
+        if i > 0 {
+            params.push(',');
+        }
+        let _ = write!(params, "v{i}");
+        // a `__proto__` key sets the prototype, a computed one is a plain property
+        if *key == "__proto__" {
+            body.push_str("[\"__proto__\"]");
+        } else {
+            push_quoted(&mut body, key);
+        }
+        let _ = write!(body, ": v{i},");
+    }
+    body.push('}');
+
+    let function: Handle<JsFunction> = cx.global("Function")?;
+    let params = cx.string(params);
+    let body = cx.string(body);
+    function.construct_with(cx).arg(params).arg(body).apply(cx)
+}
+
+/// Appends `key` as a JS string literal, escaping anything but ASCII letters and digits
+fn push_quoted(source: &mut String, key: &str) {
+    source.push('"');
+    for c in key.chars() {
+        if c.is_ascii_alphanumeric() || c == '_' {
+            source.push(c);
+        } else {
+            for unit in c.encode_utf16(&mut [0; 2]) {
+                let _ = write!(source, "\\u{unit:04x}");
+            }
+        }
+    }
+    source.push('"');
+}
+
+/// Sets the properties one at a time, when the template can't be compiled
+pub(crate) fn set_each<'j, C, K>(
+    cx: &mut C,
+    opts: &SerializeOptions,
+    keys: &[K],
+    values: &[Handle<'j, JsValue>],
+) -> LibResult<Handle<'j, JsObject>>
+where
+    C: Context<'j>,
+    K: AsRef<str>,
+{
+    let object = new_object(cx, opts)?;
+    for (key, value) in keys.iter().zip(values) {
+        let key = key.as_ref();
+        if key == "__proto__" {
+            define_own(cx, object, key, *value)?;
+        } else {
+            object.set(cx, key, *value)?;
+        }
+    }
+    Ok(object)
+}
+
+/// Defines an own enumerable property like an object literal does, even for `__proto__`
+pub(crate) fn define_own<'j, C>(
+    cx: &mut C,
+    object: Handle<'j, JsObject>,
+    key: &str,
+    value: Handle<'j, JsValue>,
+) -> LibResult<()>
+where
+    C: Context<'j>,
+{
+    let object_ctor: Handle<JsFunction> = cx.global("Object")?;
+    let define: Handle<JsFunction> = object_ctor.get(cx, "defineProperty")?;
+    let descriptor = cx.empty_object();
+    descriptor.set(cx, "value", value)?;
+    for attribute in ["writable", "enumerable", "configurable"] {
+        let yes = cx.boolean(true);
+        descriptor.set(cx, attribute, yes)?;
+    }
+    let key = cx.string(key);
+    define
+        .call_with(cx)
+        .arg(object)
+        .arg(key)
+        .arg(descriptor)
+        .exec(cx)?;
+    Ok(())
+}
@@ -0,0 +1,620 @@
+//!
+//! Buffers the elements of a sequence to emit them as a typed array
+//!
+
+use crate::{
+    errors::Result as LibResult,
+    ser::{float_value, new_number, SerializeOptions},
+};
+use neon::{prelude::*, types::JsTypedArray};
+use serde::ser::{self, Impossible, Serialize};
+use std::fmt;
+
+/// A number captured from a single sequence element, a `char` for `char_vec_as_string`,
+/// or a 64-bit integer for the map keys of `maps_as_js_map`
+#[derive(Debug, Clone, Copy)]
+pub(crate) enum Scalar {
+    Char(char),
+    I64(i64),
+    U64(u64),
+    I8(i8),
+    U8(u8),
+    I16(i16),
+    U16(u16),
+    I32(i32),
+    U32(u32),
+    F32(f32),
+    F64(f64),
+}
+
+/// Captures `value` if it serializes to bytes or a sequence of `u8`, like `&[u8]` does
+///
+/// An empty sequence isn't captured, it could be of anything
+pub(crate) fn bytes_of<T>(value: &T) -> Option<Vec<u8>>
+where
+    T: ?Sized + Serialize,
+{
+    value.serialize(BytesSerializer).ok()
+}
+
+/// Whether `value` is a `None`, rather than any other value that serializes to `null`
+pub(crate) fn is_none<T>(value: &T) -> bool
+where
+    T: ?Sized + Serialize,
+{
+    value.serialize(NoneSerializer).is_ok()
+}
+
+impl Scalar {
+    /// Captures `value` if it serializes to one of the typed array element types,
+    /// a `char` or a 64-bit integer
+    pub(crate) fn of<T>(value: &T, opts: &SerializeOptions) -> Option<Self>
+    where
+        T: ?Sized + Serialize,
+    {
+        value.serialize(ScalarSerializer { opts }).ok()
+    }
+}
+
+/// The elements of a sequence so far, as long as they all have the same type
+#[derive(Debug, Default)]
+pub(crate) enum TypedElements {
+    #[default]
+    Empty,
+    I8(Vec<i8>),
+    U8(Vec<u8>),
+    I16(Vec<i16>),
+    U16(Vec<u16>),
+    I32(Vec<i32>),
+    U32(Vec<u32>),
+    F32(Vec<f32>),
+    F64(Vec<f64>),
+}
+
+macro_rules! each_typed {
+    ($elements:expr, $values:ident => $body:expr, $empty:expr) => {
+        match $elements {
+            TypedElements::Empty => $empty,
+            TypedElements::I8($values) => $body,
+            TypedElements::U8($values) => $body,
+            TypedElements::I16($values) => $body,
+            TypedElements::U16($values) => $body,
+            TypedElements::I32($values) => $body,
+            TypedElements::U32($values) => $body,
+            TypedElements::F32($values) => $body,
+            TypedElements::F64($values) => $body,
+        }
+    };
+}
+
+impl TypedElements {
+    /// Adds an element, `false` if its type differs from the previous elements
+    pub(crate) fn push(&mut self, scalar: Scalar) -> bool {
+        match (self, scalar) {
+            (this @ TypedElements::Empty, scalar) => {
+                *this = match scalar {
+                    // not typed array elements
+                    Scalar::Char(_) | Scalar::I64(_) | Scalar::U64(_) => return false,
+                    Scalar::I8(v) => TypedElements::I8(vec![v]),
+                    Scalar::U8(v) => TypedElements::U8(vec![v]),
+                    Scalar::I16(v) => TypedElements::I16(vec![v]),
+                    Scalar::U16(v) => TypedElements::U16(vec![v]),
+                    Scalar::I32(v) => TypedElements::I32(vec![v]),
+                    Scalar::U32(v) => TypedElements::U32(vec![v]),
+                    Scalar::F32(v) => TypedElements::F32(vec![v]),
+                    Scalar::F64(v) => TypedElements::F64(vec![v]),
+                };
+            }
+            (TypedElements::I8(values), Scalar::I8(v)) => values.push(v),
+            (TypedElements::U8(values), Scalar::U8(v)) => values.push(v),
+            (TypedElements::I16(values), Scalar::I16(v)) => values.push(v),
+            (TypedElements::U16(values), Scalar::U16(v)) => values.push(v),
+            (TypedElements::I32(values), Scalar::I32(v)) => values.push(v),
+            (TypedElements::U32(values), Scalar::U32(v)) => values.push(v),
+            (TypedElements::F32(values), Scalar::F32(v)) => values.push(v),
+            (TypedElements::F64(values), Scalar::F64(v)) => values.push(v),
+            _ => return false,
+        }
+        true
+    }
+
+    /// Writes the buffered elements as numbers at the start of `array`,
+    /// when the sequence turns out not to fit a typed array
+    pub(crate) fn write_numbers<'j, C>(
+        &self,
+        cx: &mut C,
+        opts: &SerializeOptions,
+        array: Handle<'j, JsArray>,
+    ) -> LibResult<()>
+    where
+        C: Context<'j>,
+    {
+        each_typed!(
+            self,
+            values => {
+                for (i, &v) in (0u32..).zip(values) {
+                    let n = new_number(cx, opts, v)?;
+                    array.set(cx, i, n)?;
+                }
+                Ok(())
+            },
+            Ok(())
+        )
+    }
+
+    /// Creates the typed array, `None` for an empty sequence which has no element type
+    pub(crate) fn to_js<'j, C>(&self, cx: &mut C) -> LibResult<Option<Handle<'j, JsValue>>>
+    where
+        C: Context<'j>,
+    {
+        each_typed!(
+            self,
+            values => Ok(Some(JsTypedArray::from_slice(cx, values)?.upcast())),
+            Ok(None)
+        )
+    }
+}
+
+/// Returned by `ScalarSerializer` for values that aren't typed array elements
+#[derive(Debug)]
+struct NotScalar;
+
+impl fmt::Display for NotScalar {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str("not a typed array element")
+    }
+}
+
+impl std::error::Error for NotScalar {}
+
+impl ser::Error for NotScalar {
+    fn custom<T: fmt::Display>(_msg: T) -> Self {
+        NotScalar
+    }
+}
+
+/// Captures numbers that fit a typed array without touching JS
+struct ScalarSerializer<'a> {
+    /// For `float_decimals` and `json_number_grammar`
+    opts: &'a SerializeOptions,
+}
+
+macro_rules! not_scalar {
+    ($($method:ident($($arg:ty),*)),* $(,)?) => {$(
+        fn $method(self, $(_: $arg),*) -> Result<Self::Ok, Self::Error> {
+            Err(NotScalar)
+        }
+    )*};
+}
+
+impl ScalarSerializer<'_> {
+    /// Integers aren't captured with `all_ints_as_bigint`, they become `BigInt`s
+    fn int(self, scalar: Scalar) -> Result<Scalar, NotScalar> {
+        if self.opts.all_ints_as_bigint {
+            Err(NotScalar)
+        } else {
+            Ok(scalar)
+        }
+    }
+}
+
+impl ser::Serializer for ScalarSerializer<'_> {
+    type Ok = Scalar;
+    type Error = NotScalar;
+
+    type SerializeSeq = Impossible<Scalar, NotScalar>;
+    type SerializeTuple = Impossible<Scalar, NotScalar>;
+    type SerializeTupleStruct = Impossible<Scalar, NotScalar>;
+    type SerializeTupleVariant = Impossible<Scalar, NotScalar>;
+    type SerializeMap = Impossible<Scalar, NotScalar>;
+    type SerializeStruct = Impossible<Scalar, NotScalar>;
+    type SerializeStructVariant = Impossible<Scalar, NotScalar>;
+
+    fn serialize_i8(self, v: i8) -> Result<Self::Ok, Self::Error> {
+        self.int(Scalar::I8(v))
+    }
+
+    fn serialize_u8(self, v: u8) -> Result<Self::Ok, Self::Error> {
+        self.int(Scalar::U8(v))
+    }
+
+    fn serialize_i16(self, v: i16) -> Result<Self::Ok, Self::Error> {
+        self.int(Scalar::I16(v))
+    }
+
+    fn serialize_u16(self, v: u16) -> Result<Self::Ok, Self::Error> {
+        self.int(Scalar::U16(v))
+    }
+
+    fn serialize_i32(self, v: i32) -> Result<Self::Ok, Self::Error> {
+        self.int(Scalar::I32(v))
+    }
+
+    fn serialize_u32(self, v: u32) -> Result<Self::Ok, Self::Error> {
+        self.int(Scalar::U32(v))
+    }
+
+    fn serialize_f32(self, v: f32) -> Result<Self::Ok, Self::Error> {
+        #[allow(clippy::cast_possible_truncation)]
+        float_value(self.opts, f64::from(v))
+            .map(|v| Scalar::F32(v as f32))
+            .ok_or(NotScalar)
+    }
+
+    fn serialize_f64(self, v: f64) -> Result<Self::Ok, Self::Error> {
+        float_value(self.opts, v).map(Scalar::F64).ok_or(NotScalar)
+    }
+
+    fn serialize_char(self, v: char) -> Result<Self::Ok, Self::Error> {
+        Ok(Scalar::Char(v))
+    }
+
+    fn serialize_i64(self, v: i64) -> Result<Self::Ok, Self::Error> {
+        Ok(Scalar::I64(v))
+    }
+
+    fn serialize_u64(self, v: u64) -> Result<Self::Ok, Self::Error> {
+        Ok(Scalar::U64(v))
+    }
+
+    not_scalar!(
+        serialize_bool(bool),
+        serialize_i128(i128),
+        serialize_u128(u128),
+        serialize_str(&str),
+        serialize_bytes(&[u8]),
+        serialize_none(),
+        serialize_unit(),
+        serialize_unit_struct(&'static str),
+        serialize_unit_variant(&'static str, u32, &'static str),
+    );
+
+    fn serialize_some<T>(self, _value: &T) -> Result<Self::Ok, Self::Error>
+    where
+        T: ?Sized + Serialize,
+    {
+        Err(NotScalar)
+    }
+
+    fn serialize_newtype_struct<T>(
+        self,
+        _name: &'static str,
+        value: &T,
+    ) -> Result<Self::Ok, Self::Error>
+    where
+        T: ?Sized + Serialize,
+    {
+        value.serialize(self)
+    }
+
+    fn serialize_newtype_variant<T>(
+        self,
+        _name: &'static str,
+        _variant_index: u32,
+        _variant: &'static str,
+        _value: &T,
+    ) -> Result<Self::Ok, Self::Error>
+    where
+        T: ?Sized + Serialize,
+    {
+        Err(NotScalar)
+    }
+
+    fn serialize_seq(self, _len: Option<usize>) -> Result<Self::SerializeSeq, Self::Error> {
+        Err(NotScalar)
+    }
+
+    fn serialize_tuple(self, _len: usize) -> Result<Self::SerializeTuple, Self::Error> {
+        Err(NotScalar)
+    }
+
+    fn serialize_tuple_struct(
+        self,
+        _name: &'static str,
+        _len: usize,
+    ) -> Result<Self::SerializeTupleStruct, Self::Error> {
+        Err(NotScalar)
+    }
+
+    fn serialize_tuple_variant(
+        self,
+        _name: &'static str,
+        _variant_index: u32,
+        _variant: &'static str,
+        _len: usize,
+    ) -> Result<Self::SerializeTupleVariant, Self::Error> {
+        Err(NotScalar)
+    }
+
+    fn serialize_map(self, _len: Option<usize>) -> Result<Self::SerializeMap, Self::Error> {
+        Err(NotScalar)
+    }
+
+    fn serialize_struct(
+        self,
+        _name: &'static str,
+        _len: usize,
+    ) -> Result<Self::SerializeStruct, Self::Error> {
+        Err(NotScalar)
+    }
+
+    fn serialize_struct_variant(
+        self,
+        _name: &'static str,
+        _variant_index: u32,
+        _variant: &'static str,
+        _len: usize,
+    ) -> Result<Self::SerializeStructVariant, Self::Error> {
+        Err(NotScalar)
+    }
+}
+
+/// Captures byte sequences without touching JS
+struct BytesSerializer;
+
+/// The bytes of a sequence so far, see `BytesSerializer`
+struct ByteSeq(Vec<u8>);
+
+impl ser::SerializeSeq for ByteSeq {
+    type Ok = Vec<u8>;
+    type Error = NotScalar;
+
+    fn serialize_element<T>(&mut self, value: &T) -> Result<(), Self::Error>
+    where
+        T: ?Sized + Serialize,
+    {
+        match value.serialize(ScalarSerializer {
+            opts: &SerializeOptions::default(),
+        })? {
+            Scalar::U8(v) => self.0.push(v),
+            _ => return Err(NotScalar),
+        }
+        Ok(())
+    }
+
+    fn end(self) -> Result<Self::Ok, Self::Error> {
+        if self.0.is_empty() {
+            return Err(NotScalar);
+        }
+        Ok(self.0)
+    }
+}
+
+impl ser::Serializer for BytesSerializer {
+    type Ok = Vec<u8>;
+    type Error = NotScalar;
+
+    type SerializeSeq = ByteSeq;
+    type SerializeTuple = Impossible<Vec<u8>, NotScalar>;
+    type SerializeTupleStruct = Impossible<Vec<u8>, NotScalar>;
+    type SerializeTupleVariant = Impossible<Vec<u8>, NotScalar>;
+    type SerializeMap = Impossible<Vec<u8>, NotScalar>;
+    type SerializeStruct = Impossible<Vec<u8>, NotScalar>;
+    type SerializeStructVariant = Impossible<Vec<u8>, NotScalar>;
+
+    fn serialize_bytes(self, v: &[u8]) -> Result<Self::Ok, Self::Error> {
+        Ok(v.to_vec())
+    }
+
+    not_scalar!(
+        serialize_bool(bool),
+        serialize_i8(i8),
+        serialize_u8(u8),
+        serialize_i16(i16),
+        serialize_u16(u16),
+        serialize_i32(i32),
+        serialize_u32(u32),
+        serialize_i64(i64),
+        serialize_u64(u64),
+        serialize_i128(i128),
+        serialize_u128(u128),
+        serialize_f32(f32),
+        serialize_f64(f64),
+        serialize_char(char),
+        serialize_str(&str),
+        serialize_none(),
+        serialize_unit(),
+        serialize_unit_struct(&'static str),
+        serialize_unit_variant(&'static str, u32, &'static str),
+    );
+
+    fn serialize_some<T>(self, _value: &T) -> Result<Self::Ok, Self::Error>
+    where
+        T: ?Sized + Serialize,
+    {
+        Err(NotScalar)
+    }
+
+    fn serialize_newtype_struct<T>(
+        self,
+        _name: &'static str,
+        value: &T,
+    ) -> Result<Self::Ok, Self::Error>
+    where
+        T: ?Sized + Serialize,
+    {
+        value.serialize(self)
+    }
+
+    fn serialize_newtype_variant<T>(
+        self,
+        _name: &'static str,
+        _variant_index: u32,
+        _variant: &'static str,
+        _value: &T,
+    ) -> Result<Self::Ok, Self::Error>
+    where
+        T: ?Sized + Serialize,
+    {
+        Err(NotScalar)
+    }
+
+    fn serialize_seq(self, len: Option<usize>) -> Result<Self::SerializeSeq, Self::Error> {
+        Ok(ByteSeq(Vec::with_capacity(len.unwrap_or(0))))
+    }
+
+    fn serialize_tuple(self, _len: usize) -> Result<Self::SerializeTuple, Self::Error> {
+        Err(NotScalar)
+    }
+
+    fn serialize_tuple_struct(
+        self,
+        _name: &'static str,
+        _len: usize,
+    ) -> Result<Self::SerializeTupleStruct, Self::Error> {
+        Err(NotScalar)
+    }
+
+    fn serialize_tuple_variant(
+        self,
+        _name: &'static str,
+        _variant_index: u32,
+        _variant: &'static str,
+        _len: usize,
+    ) -> Result<Self::SerializeTupleVariant, Self::Error> {
+        Err(NotScalar)
+    }
+
+    fn serialize_map(self, _len: Option<usize>) -> Result<Self::SerializeMap, Self::Error> {
+        Err(NotScalar)
+    }
+
+    fn serialize_struct(
+        self,
+        _name: &'static str,
+        _len: usize,
+    ) -> Result<Self::SerializeStruct, Self::Error> {
+        Err(NotScalar)
+    }
+
+    fn serialize_struct_variant(
+        self,
+        _name: &'static str,
+        _variant_index: u32,
+        _variant: &'static str,
+        _len: usize,
+    ) -> Result<Self::SerializeStructVariant, Self::Error> {
+        Err(NotScalar)
+    }
+}
+
+/// Succeeds only for `serialize_none`, see [`is_none`]
+struct NoneSerializer;
+
+impl ser::Serializer for NoneSerializer {
+    type Ok = ();
+    type Error = NotScalar;
+
+    type SerializeSeq = Impossible<(), NotScalar>;
+    type SerializeTuple = Impossible<(), NotScalar>;
+    type SerializeTupleStruct = Impossible<(), NotScalar>;
+    type SerializeTupleVariant = Impossible<(), NotScalar>;
+    type SerializeMap = Impossible<(), NotScalar>;
+    type SerializeStruct = Impossible<(), NotScalar>;
+    type SerializeStructVariant = Impossible<(), NotScalar>;
+
+    fn serialize_none(self) -> Result<Self::Ok, Self::Error> {
+        Ok(())
+    }
+
+    not_scalar!(
+        serialize_bool(bool),
+        serialize_i8(i8),
+        serialize_u8(u8),
+        serialize_i16(i16),
+        serialize_u16(u16),
+        serialize_i32(i32),
+        serialize_u32(u32),
+        serialize_i64(i64),
+        serialize_u64(u64),
+        serialize_i128(i128),
+        serialize_u128(u128),
+        serialize_f32(f32),
+        serialize_f64(f64),
+        serialize_char(char),
+        serialize_str(&str),
+        serialize_bytes(&[u8]),
+        serialize_unit(),
+        serialize_unit_struct(&'static str),
+        serialize_unit_variant(&'static str, u32, &'static str),
+    );
+
+    fn serialize_some<T>(self, _value: &T) -> Result<Self::Ok, Self::Error>
+    where
+        T: ?Sized + Serialize,
+    {
+        Err(NotScalar)
+    }
+
+    fn serialize_newtype_struct<T>(
+        self,
+        _name: &'static str,
+        _value: &T,
+    ) -> Result<Self::Ok, Self::Error>
+    where
+        T: ?Sized + Serialize,
+    {
+        Err(NotScalar)
+    }
+
+    fn serialize_newtype_variant<T>(
+        self,
+        _name: &'static str,
+        _variant_index: u32,
+        _variant: &'static str,
+        _value: &T,
+    ) -> Result<Self::Ok, Self::Error>
+    where
+        T: ?Sized + Serialize,
+    {
+        Err(NotScalar)
+    }
+
+    fn serialize_seq(self, _len: Option<usize>) -> Result<Self::SerializeSeq, Self::Error> {
+        Err(NotScalar)
+    }
+
+    fn serialize_tuple(self, _len: usize) -> Result<Self::SerializeTuple, Self::Error> {
+        Err(NotScalar)
+    }
+
+    fn serialize_tuple_struct(
+        self,
+        _name: &'static str,
+        _len: usize,
+    ) -> Result<Self::SerializeTupleStruct, Self::Error> {
+        Err(NotScalar)
+    }
+
+    fn serialize_tuple_variant(
+        self,
+        _name: &'static str,
+        _variant_index: u32,
+        _variant: &'static str,
+        _len: usize,
+    ) -> Result<Self::SerializeTupleVariant, Self::Error> {
+        Err(NotScalar)
+    }
+
+    fn serialize_map(self, _len: Option<usize>) -> Result<Self::SerializeMap, Self::Error> {
+        Err(NotScalar)
+    }
+
+    fn serialize_struct(
+        self,
+        _name: &'static str,
+        _len: usize,
+    ) -> Result<Self::SerializeStruct, Self::Error> {
+        Err(NotScalar)
+    }
+
+    fn serialize_struct_variant(
+        self,
+        _name: &'static str,
+        _variant_index: u32,
+        _variant: &'static str,
+        _len: usize,
+    ) -> Result<Self::SerializeStructVariant, Self::Error> {
+        Err(NotScalar)
+    }
+}
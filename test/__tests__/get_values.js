@@ -1,5 +1,6 @@
 const native = require('../native');
 const expect = require('expect');
+const path = require('path');
 
 describe('all values ok', () => {
     it('value 32', () => {
@@ -34,6 +35,472 @@ describe('all values ok', () => {
         });
     });
 
+    it('null prototype object', () => {
+        const obj = native.make_obj_null_proto();
+        expect(Object.getPrototypeOf(obj)).toBe(null);
+        expect(Object.keys(obj)).toEqual(['a', 'b', 'c']);
+        expect(obj.c).toBe('Hi');
+    });
+
+    it('128-bit integers outside the safe range', () => {
+        const u128Max = '340282366920938463463374607431768211455';
+        const i128Min = '-170141183460469231731687303715884105728';
+        if (native.make_bigint_enabled()) {
+            expect(native.make_u128_max()).toBe(BigInt(u128Max));
+            expect(native.make_i128_min()).toBe(BigInt(i128Min));
+        } else {
+            expect(native.make_u128_max()).toBe(u128Max);
+            expect(native.make_i128_min()).toBe(i128Min);
+        }
+    });
+
+    it('make_nonzero', () => {
+        const u128Max = '340282366920938463463374607431768211455';
+        const values = native.make_nonzero();
+        expect(values.slice(0, 3)).toEqual([7, Number.MAX_SAFE_INTEGER, Number.MIN_SAFE_INTEGER]);
+        expect(values[3]).toBe(native.make_bigint_enabled() ? BigInt(u128Max) : u128Max);
+        native.expect_nonzero(values.slice(0, 3));
+    });
+
+    it('named tuple', () => {
+        expect(native.make_named_tuple()).toEqual({ x: 7, y: 'seven' });
+    });
+
+    it('fill_instance', () => {
+        class Person {
+            constructor() {
+                this.name = '';
+            }
+
+            greet() {
+                return `hi ${this.name}`;
+            }
+        }
+        const person = new Person();
+        expect(native.fill_instance(person)).toBe(person);
+        expect(person.greet()).toBe('hi bob');
+        expect(person.age).toBe(3);
+        expect(Object.keys(person)).toEqual(['name', 'age']);
+    });
+
+    it('make_rooted', async () => {
+        const rooted = native.make_rooted();
+        await new Promise((resolve) => setTimeout(resolve, 0));
+        expect(native.read_rooted(rooted)).toEqual({ name: 'bob', age: 3 });
+        expect(native.read_rooted(rooted)).toEqual({ name: 'bob', age: 3 });
+    });
+
+    it('expect_rooted', async () => {
+        const rooted = native.make_rooted();
+        await new Promise((resolve) => setTimeout(resolve, 0));
+        native.expect_rooted(rooted);
+        native.expect_rooted(rooted);
+    });
+
+    it('make_merged', () => {
+        const merged = native.make_merged();
+        expect(merged).toEqual({ name: 'bob', age: 4, checked: true });
+        expect(Object.keys(merged)).toEqual(['name', 'age', 'checked']);
+    });
+
+    it('append_numbers', () => {
+        const target = ['first'];
+        expect(native.append_numbers(target, 1)).toBe(target);
+        native.append_numbers(target, 10);
+        expect(target).toEqual(['first', 1, 2, 10, 11]);
+    });
+
+    it('make_query_params', () => {
+        const params = native.make_query_params();
+        expect(params).toEqual({ q: 'a b&c', page: '2', exact: 'true', ratio: '0.5' });
+        expect(new URLSearchParams(params).toString()).toBe('q=a+b%26c&page=2&exact=true&ratio=0.5');
+        expect(native.make_query_params_nested(true)).toEqual({ q: 'x', tags: '["a","b"]' });
+    });
+
+    it('make_sorted_keys', () => {
+        const [value, map] = native.make_sorted_keys();
+        expect(Object.keys(value)).toEqual(['alpha', 'mid', 'zeta']);
+        expect(Object.keys(value.mid.Struct)).toEqual(['a', 'b']);
+        expect(Object.keys(map)).toEqual(['a', 'b', 'c']);
+    });
+
+    it('make_sorted_map_keys', () => {
+        const sorted = Array.from({ length: 20 }, (_, i) => `key${String(i).padStart(2, '0')}`);
+        for (let i = 0; i < 5; i++) {
+            const [value, map] = native.make_sorted_map_keys();
+            expect(Object.keys(value)).toEqual(['zeta', 'alpha', 'mid']);
+            expect(Object.keys(map)).toEqual(sorted);
+        }
+    });
+
+    it('make_json_map', () => {
+        const map = native.make_json_map();
+        expect(typeof map.number).toBe('number');
+        expect(typeof map.string).toBe('string');
+        expect(typeof map.bool).toBe('boolean');
+        expect(map.null).toBeNull();
+        expect(Array.isArray(map.array)).toBe(true);
+        expect(typeof map.object.nested).toBe('object');
+        expect(map).toEqual({
+            number: 1.5,
+            string: 'two',
+            bool: true,
+            null: null,
+            array: [1, 'a'],
+            object: { nested: { deep: 3 } },
+        });
+    });
+
+    it('make_validated_strings', () => {
+        const [text, chars, empty] = native.make_validated_strings();
+        expect(text).toBe('a\u0000é\u0301ñ 日本語 مرحبا \u200d👩\u200d👩\u200d👧 🇨🇦 \uffff\u{10000}\u{10ffff}');
+        expect([...text].length).toBe(29);
+        expect(chars).toEqual(['\u0000', 'é', '\u0301', '日', '🤷', '\u{10ffff}']);
+        expect(empty).toBe('');
+    });
+
+    it('make_record_batch', () => {
+        const batch = native.make_record_batch(1000);
+        expect(batch).toHaveLength(1000);
+        expect(batch).toEqual(native.make_wide_structs(1000));
+        for (const i of [0, 1, 999]) {
+            expect(batch[i].f0).toBe(String(i));
+            expect(batch[i].f23).toBe(i);
+            expect(Object.keys(batch[i])).toHaveLength(24);
+        }
+        expect(native.make_record_batch(0)).toEqual([]);
+    });
+
+    it('make_wide_structs_planned', () => {
+        const planned = native.make_wide_structs_planned(100);
+        expect(planned).toEqual(native.make_wide_structs(100));
+        expect(native.make_wide_structs_each(100)).toEqual(planned);
+        expect(Object.keys(planned[99])).toEqual(Object.keys(native.make_wide_structs(100)[99]));
+    });
+
+    it('make_particles', () => {
+        const [particles, frames] = native.make_particles();
+        expect(Object.keys(particles)).toEqual(['x', 'y', 'vx']);
+        for (const field of [particles.x, particles.y, particles.vx, frames.next[0].x, frames.next[0].vx]) {
+            expect(field).toBeInstanceOf(Float64Array);
+        }
+        expect(Array.from(particles.x)).toEqual([0, 1]);
+        expect(Array.from(particles.y)).toEqual([2, 3]);
+        expect(Array.from(particles.vx)).toEqual([-0.5, 0.5]);
+        expect(Array.from(frames.next[0].vx)).toEqual([0.25]);
+        expect(frames.next[0].y).toEqual([]);
+    });
+
+    it('make_empty_as_null', () => {
+        expect(native.make_empty_as_null()).toEqual([null, null, null, [1]]);
+    });
+
+    it('make_no_fields', () => {
+        const [empty, marker] = native.make_no_fields();
+        expect(empty).toEqual({});
+        expect(Object.keys(empty)).toHaveLength(0);
+        expect(marker).toBeNull();
+        expect(native.make_no_fields_null()).toEqual([null, null]);
+    });
+
+    it('make_string_table', () => {
+        const table = native.make_string_table();
+        expect(table).toEqual({
+            strings: ['name', 'tag1', 'color', 'red', 'uses', 'tag0', 'blue'],
+            data: [
+                { '#0': '1', '#2': '3', '#4': 1 },
+                { '#0': '5', '#2': '3', '#4': 2 },
+                { '#0': '1', '#2': '6', '#4': 3 },
+            ],
+        });
+        native.expect_string_table(table);
+
+        const ordered = native.make_string_table_order();
+        expect(ordered.strings).toEqual(['b', 'a']);
+        expect(Object.keys(ordered.data[1])).toEqual(['#1', '#0']);
+        expect(native.read_string_table_order(ordered)).toEqual([['b'], ['a', 'b']]);
+    });
+
+    it('make_typed_arrays', () => {
+        const [f64, u8, i32, empty, u64, mixed, mixedFirst] = native.make_typed_arrays();
+        expect(f64).toBeInstanceOf(Float64Array);
+        expect(Array.from(f64)).toEqual([1.5, 2.5]);
+        expect(u8).toBeInstanceOf(Uint8Array);
+        expect(Array.from(u8)).toEqual([1, 2, 3]);
+        expect(i32).toBeInstanceOf(Int32Array);
+        expect(Array.from(i32)).toEqual([-1, 2]);
+        expect(empty).toEqual([]);
+        expect(u64).toEqual([1, 2]);
+        expect(Array.isArray(mixed)).toBe(true);
+        expect(mixed).toEqual([1, 'two', 3]);
+        expect(mixedFirst).toEqual(['one', 2]);
+    });
+
+    it('make_bytes_with_length', () => {
+        const { len, data } = native.make_bytes_with_length();
+        expect(len).toBe(3);
+        expect(data).toBeInstanceOf(Buffer);
+        expect(data).toEqual(Buffer.from([1, 2, 3]));
+        expect(native.make_buff()).toBeInstanceOf(Buffer);
+    });
+
+    it('make_map_none', () => {
+        expect(native.make_map_none_null()).toStrictEqual({ a: 1, b: null });
+        expect(native.make_map_none_omit()).toStrictEqual({ a: 1 });
+        expect(native.make_map_null_values_omit()).toStrictEqual({ unit: null });
+        expect(native.make_json_null_omit()).toStrictEqual({ a: null, b: 1 });
+    });
+
+    it('make_truncated', () => {
+        const [link, array] = native.make_truncated();
+        expect(link).toEqual({ id: 1, tags: ['x'], next: { id: 2, tags: '[Truncated]', next: '[Truncated]' } });
+        expect(array).toEqual([1, [2, '[Truncated]']]);
+    });
+
+    it('make_with_metadata', () => {
+        const meta = Symbol.for('neon-serde.meta');
+        const link = native.make_with_metadata();
+        expect(link[meta]).toEqual({ type: 'Link', version: '1' });
+        expect(link.next[meta]).toEqual({ type: 'Link', version: '1' });
+        expect(Object.keys(link)).toEqual(['id', 'tags', 'next']);
+        expect(JSON.parse(JSON.stringify(link))).toEqual({ id: 1, tags: ['x'], next: { id: 2, tags: ['x'], next: null } });
+    });
+
+    it('make_char_vec_string', () => {
+        expect(native.make_char_vec_string()).toEqual(['abc😀', [], ['a', 'b', 3], [1.5], 'zy']);
+        expect(native.make_char_vec()).toEqual(['z', 'y']);
+        const [letters, bytes, chars] = native.make_char_vec_string_typed();
+        expect(letters).toEqual(['a', 3]);
+        expect(bytes).toBeInstanceOf(Uint8Array);
+        expect(Array.from(bytes)).toEqual([1, 2]);
+        expect(chars).toBe('zy');
+    });
+
+    it('make_reply', () => {
+        const reply = native.make_reply();
+        expect(Object.keys(reply)).toEqual(['cursor', 'error', 'count']);
+        expect(reply.cursor).toBeUndefined();
+        expect(reply.error).toBeNull();
+        expect(reply.count).toBe(2);
+        expect(JSON.stringify(reply)).toBe('{"error":null,"count":2}');
+        native.expect_reply(reply);
+        native.expect_reply({ error: null, count: 2 });
+    });
+
+    it('expect_extra_tuple_elements', () => {
+        native.expect_extra_tuple_elements([1, 2, 3, 4]);
+        native.expect_extra_tuple_elements({ 0: 1, 1: 2, 2: 3, 3: 4 });
+        native.expect_extra_tuple_elements({ 0: 1, 1: 2 });
+        native.expect_extra_variant_elements({ Tuple: { 0: 1, 1: 'a', 2: 'extra' } });
+        native.expect_extra_variant_elements({ Tuple: [1, 'a', 'extra'] });
+        native.expect_variant_fill_defaults([{ Tuple: { 0: 1 } }, { Tuple: [] }]);
+        native.expect_variant_fill_defaults([{ Tuple: [1] }, { Tuple: {} }]);
+    });
+
+    it('make_odd_keys', () => {
+        const keys = ['0', '__proto__', 'quote" back\\slash\u00001 é😀'];
+        const obj = native.make_odd_keys();
+        expect(Object.getPrototypeOf(obj)).toBe(Object.prototype);
+        expect(Object.keys(obj)).toEqual(keys);
+        expect(Object.values(obj)).toEqual([3, 1, 2]);
+
+        const nullProto = native.make_odd_keys_null_proto();
+        expect(Object.getPrototypeOf(nullProto)).toBe(null);
+        expect(Object.keys(nullProto)).toEqual(keys);
+    });
+
+    it('make_non_ascii_keys', () => {
+        expect(native.make_non_ascii_keys_allow()).toEqual([{ 'café': 1, plain: 0 }, { 'naïve': 2 }]);
+        expect(native.make_non_ascii_keys_escape())
+            .toEqual([{ 'caf\\u00e9': 1, plain: 0 }, { 'na\\u00efve': 2 }]);
+    });
+
+    it('make_float_decimals', () => {
+        const [pi, e, big, int, typed] = native.make_float_decimals();
+        expect(pi).toBe(3.14);
+        expect(e).toBe(2.72);
+        expect(big).toBe(1e300);
+        expect(int).toBe(7);
+        expect(typed).toEqual(new Float64Array([1.23, -0.5]));
+    });
+
+    it('make_tuple_bytes', () => {
+        const [int, bytes, wide, empty] = native.make_tuple_bytes();
+        expect(int).toBe(7);
+        expect(Buffer.isBuffer(bytes)).toBe(true);
+        expect([...bytes]).toEqual([1, 2, 3]);
+        expect(wide).toBeInstanceOf(Uint16Array);
+        expect(empty).toEqual([]);
+    });
+
+    it('make_transformed', () => {
+        const account = native.make_transformed();
+        expect(account).toEqual({ NAME: 'bob', PASSWORD: '[redacted]', SETTINGS: { THEME: 1 } });
+        expect(Object.keys(account)).toEqual(['NAME', 'PASSWORD', 'SETTINGS']);
+    });
+
+    it('make_all_ints_as_bigint', () => {
+        const [small, negative, u64Max, i128Min, float, array] = native.make_all_ints_as_bigint();
+        expect(typeof small).toBe('bigint');
+        expect(small).toBe(1n);
+        expect(negative).toBe(-2n);
+        expect(u64Max).toBe(18446744073709551615n);
+        expect(i128Min).toBe(-(2n ** 127n));
+        expect(float).toBe(1.5);
+        expect(Array.isArray(array)).toBe(true);
+        expect(array[0]).toBe(1n);
+        expect(array[1]).toBe(2n);
+    });
+
+    it('make_js_maps', () => {
+        const [unsigned, signed, profile] = native.make_js_maps();
+        expect(unsigned).toBeInstanceOf(Map);
+        expect(unsigned.size).toBe(3);
+        expect(unsigned.get(1)).toBe('one');
+        expect(unsigned.get(1152921504606846977n)).toBe('big');
+        expect(unsigned.get(18446744073709551615n)).toBe('max');
+        expect([...signed]).toEqual([[-9223372036854775808n, 1], [-1, 2]]);
+        expect(profile).toEqual({ name: 'bob', age: 3 });
+    });
+
+    it('make_large_floats_bigint', () => {
+        const [large, negative, huge, first, safe, rounded, small] = native.make_large_floats_bigint();
+        expect(large).toBe(100000000000000000000n);
+        expect(negative).toBe(-100000000000000000000n);
+        expect(huge).toBe(BigInt(1e300));
+        expect(first).toBe(2n ** 53n);
+        expect(safe).toBe(Number.MAX_SAFE_INTEGER);
+        expect(rounded).toBe(100000000000000000000n);
+        expect(small).toBe(2.5);
+    });
+
+    it('make_infinities', () => {
+        expect(native.make_infinities()).toEqual([Infinity, -Infinity, -Infinity]);
+        const [f64s, f32s] = native.make_infinities_typed();
+        expect(f64s).toEqual(new Float64Array([-Infinity, 1]));
+        expect(f32s).toEqual(new Float32Array([-Infinity]));
+    });
+
+    it('make_json_number_grammar', () => {
+        const [nan, inf, negInf, negZero, negZero32, float, typed, mixed] =
+            native.make_json_number_grammar();
+        expect(nan).toBe(null);
+        expect(inf).toBe(null);
+        expect(negInf).toBe(null);
+        expect(JSON.stringify([nan, inf, negInf])).toBe('[null,null,null]');
+        expect(Object.is(negZero, 0)).toBe(true);
+        expect(Object.is(negZero32, 0)).toBe(true);
+        expect(float).toBe(-1.5);
+        expect(typed).toBeInstanceOf(Float64Array);
+        expect(Object.is(typed[1], 0)).toBe(true);
+        expect(mixed).toEqual([1, null]);
+    });
+
+    it('make_boxed_numbers', () => {
+        const [int, float, map] = native.make_boxed_numbers();
+        expect(typeof int).toBe('object');
+        expect(int.valueOf()).toBe(42);
+        expect(typeof float).toBe('object');
+        expect(float.valueOf()).toBe(-1.5);
+        expect(Object.keys(map)).toEqual(['1']);
+        expect(map[1].valueOf()).toBe(2.5);
+    });
+
+    it('make_empty_tuple_variant', () => {
+        const variants = native.make_empty_tuple_variant();
+        expect(variants).toEqual([{ Empty: [] }, 'Unit', { Pair: [1, 2] }]);
+        native.expect_empty_tuple_variant(variants);
+    });
+
+    it('make_flat_newtype_variants', () => {
+        expect(native.make_flat_newtype_variants()).toEqual([['a', 'b'], 'Empty', { Tuple: [1, 'x'] }]);
+    });
+
+    it('make_type_value_enums', () => {
+        const enums = native.make_type_value_enums();
+        expect(enums).toEqual([
+            { type: 'Empty' },
+            { type: 'Tuple', value: [27, 'hij'] },
+            { type: 'Struct', value: { a: 128, b: [9, 8, 7] } },
+            { type: 'Value', value: ['z', 'y'] },
+        ]);
+        expect('value' in enums[0]).toBe(false);
+        native.expect_type_value_enums(enums);
+        native.expect_type_value_enums(['Empty', ...enums.slice(1)]);
+        native.expect_type_value_enums([{ type: 'Empty', value: null }, ...enums.slice(1)]);
+    });
+
+    it('make_path', () => {
+        expect(native.make_path()).toBe(path.join('dir', 'file.txt'));
+        expect(native.make_path_forward_slash()).toBe('dir/file.txt');
+    });
+
+    it('make_char_key_map', () => {
+        expect(native.make_char_key_map()).toEqual({ a: 1, '🤷': 2 });
+        native.expect_char_key_map(native.make_char_key_map());
+    });
+
+    it('make_complex_key_map', () => {
+        const pairs = native.make_complex_key_map();
+        pairs.sort((a, b) => a[0].x - b[0].x);
+        expect(pairs).toEqual([
+            [{ x: 1, y: 2 }, 'a'],
+            [{ x: 3, y: 4 }, 'b'],
+        ]);
+        expect(native.make_simple_key_map()).toEqual({ 1: 'a', 2: 'b' });
+    });
+
+    it('uuid', () => {
+        if (!native.make_uuid_enabled()) {
+            return;
+        }
+        const uuid = '67e55044-10b1-426f-9247-bb680e5fe0c8';
+        const bytes = Buffer.from(uuid.replace(/-/g, ''), 'hex');
+
+        expect(native.make_uuid()).toBe(uuid);
+        expect(native.make_uuid_compact()).toEqual(bytes);
+        native.expect_uuid(native.make_uuid());
+        native.expect_uuid_compact(native.make_uuid_compact());
+        expect(() => native.expect_uuid_compact(uuid)).toThrow();
+    });
+
+    it('smallvec', () => {
+        if (!native.make_smallvec_enabled()) {
+            return;
+        }
+        expect(native.make_smallvec()).toEqual([[1, 2], [1, 2, 3, 4, 5, 6]]);
+        native.expect_smallvec(native.make_smallvec());
+    });
+
+    it('arrayvec', () => {
+        if (!native.make_arrayvec_enabled()) {
+            return;
+        }
+        expect(native.make_arrayvec()).toEqual([1, 2, 3]);
+        native.expect_arrayvec(native.make_arrayvec());
+        expect(() => native.expect_arrayvec([1, 2, 3, 4, 5])).toThrow(/invalid length 5/);
+    });
+
+    it('indexmap key order', () => {
+        if (!native.make_indexmap_enabled()) {
+            return;
+        }
+        const object = { b: 1, 10: 2, a: 3, 2: 4, '-1': 5, '01': 6, 1.5: 7 };
+        expect(native.read_key_order(object)).toEqual(Reflect.ownKeys(object));
+        expect(native.read_key_order(object)).toEqual(['2', '10', 'b', 'a', '-1', '01', '1.5']);
+    });
+
+    it('make generator', () => {
+        expect([...native.make_generator()]).toEqual([[1], [2, 2], [3, 3, 3]]);
+
+        const gen = native.make_generator();
+        expect(gen.next()).toEqual({ done: false, value: [1] });
+        gen.next();
+        gen.next();
+        expect(gen.next()).toEqual({ done: true });
+        expect(gen.next()).toEqual({ done: true });
+    });
+
     it('make object', () => {
         expect(native.make_object()).toEqual({
             a: 1,
@@ -95,9 +562,270 @@ describe('all values ok', () => {
         native.expect_num_array([0, 1, 2, 3]);
     });
 
+    it('expect_num_iterable', () => {
+        function* gen() {
+            for (let i = 0; i < 4; i++) {
+                yield i;
+            }
+        }
+        native.expect_num_iterable(gen());
+        native.expect_num_iterable(new Set([0, 1, 2, 3]));
+        native.expect_num_iterable([0, 1, 2, 3]);
+    });
+
+    it('expect_scalar', () => {
+        native.expect_scalar_bool(true);
+        native.expect_scalar_string('hello');
+        native.expect_scalar_f64(1.5);
+        native.expect_scalar_f32(1.5);
+        native.expect_scalar_i8(-128);
+        native.expect_scalar_i16(-32768);
+        native.expect_scalar_i32(-2147483648);
+        native.expect_scalar_i64(-Number.MAX_SAFE_INTEGER);
+        native.expect_scalar_u8(255);
+        native.expect_scalar_u16(65535);
+        native.expect_scalar_u32(4294967295);
+        native.expect_scalar_u64(Number.MAX_SAFE_INTEGER);
+    });
+
+    it('expect_null_as_default', () => {
+        const obj = { a: 1, b: null, c: null, constructor: null };
+        native.expect_null_as_default(obj);
+        native.expect_null_as_default_lookup(obj);
+        native.expect_null_as_default({ a: 1 });
+    });
+
+    it('expect_positional_tuple', () => {
+        const inner2 = { 0: -2, 1: true, 2: 'b' };
+        native.expect_positional_tuple([{ 0: 1, 1: 'a' }, inner2]);
+        native.expect_positional_tuple({ 0: [1, 'a'], 1: inner2 });
+        native.expect_positional_tuple([[1, 'a'], [-2, true, 'b']]);
+    });
+
+    it('expect_positional_variant', () => {
+        const serialized = native.make_object().j;
+        expect(serialized).toEqual({ Tuple: [27, 'hij'] });
+        native.expect_positional_variant(serialized);
+        native.expect_positional_variant({ Tuple: { ...serialized.Tuple } });
+    });
+
+    it('expect_date_string', () => {
+        native.expect_date_string({ at: new Date(Date.UTC(2020, 0, 2, 3, 4, 5, 678)) });
+        native.expect_date_string({ at: '2020-01-02T03:04:05.678Z' });
+    });
+
+    it('expect_reinterpreted', () => {
+        const [f64] = native.make_typed_arrays();
+        native.expect_reinterpreted(f64.buffer);
+        native.expect_reinterpreted(new Float64Array([1.5, 2.5]).buffer);
+        native.expect_reinterpreted([1.5, 2.5]);
+    });
+
+    it('expect_string_chars', () => {
+        native.expect_string_chars('abc😀');
+        native.expect_string_chars(['a', 'b', 'c', '😀']);
+    });
+
+    it('expect_buffer_json', () => {
+        native.expect_buffer_json(Buffer.from([1, 2, 255]));
+        native.expect_buffer_json([1, 2, 255]);
+        native.expect_buffer_base64(Buffer.from([1, 2, 255]));
+    });
+
+    it('expect_captured', () => {
+        native.expect_captured({ id: 1, b: 2, name: 'a', c: [true] });
+    });
+
+    it('expect_captured_nested', () => {
+        native.expect_captured_nested({
+            owner: { name: 'b' },
+            envelopes: [{ id: 1, name: 'a', extra: { b: 2, c: [true] } }],
+            note: 'hi',
+        });
+    });
+
+    it('expect_date_millis', () => {
+        const at = new Date(Date.UTC(2020, 0, 2, 3, 4, 5, 678));
+        native.expect_date_millis([at, at, at]);
+        native.expect_date_millis([at.getTime(), at, at.getTime()]);
+    });
+
+    it('expect_tuple_fill_defaults', () => {
+        native.expect_tuple_fill_defaults([[5], [1]]);
+        native.expect_tuple_fill_defaults([[5, 0], [1, '', null, []]]);
+    });
+
+    it('expect_bigint_string', () => {
+        native.expect_bigint_string({ amount: 123456789012345678901234567890n });
+        native.expect_bigint_string({ amount: '123456789012345678901234567890' });
+    });
+
+    it('expect_all_fields', () => {
+        native.expect_all_fields({ a: 1, b: null, c: 0, constructor: 2 });
+        native.expect_all_fields({ a: 1, b: undefined, c: 0, constructor: 2, extra: true });
+    });
+
+    it('expect_all_fields_aliased', () => {
+        native.expect_all_fields_aliased({ name: 'ann', age: 3 });
+        native.expect_all_fields_aliased({ nick: 'ann', years: 3 });
+        native.expect_all_fields_aliased({ handle: 'ann', age: 3, extra: true });
+    });
+
+    it('expect_numeric_strings', () => {
+        native.expect_numeric_strings(['3.5', ' -2 ', '1e3']);
+        native.expect_numeric_strings([3.5, -2, 1000]);
+    });
+
+    it('expect_decimal_comma', () => {
+        native.expect_decimal_comma(['3,14', '2.5', '1e3']);
+    });
+
+    it('expect_empty_object_unit', () => {
+        native.expect_empty_object_unit([{}, {}, {}]);
+        native.expect_empty_object_unit([null, undefined, {}]);
+    });
+
+    it('expect_safe_integer_bounds', () => {
+        // `2 ** 53` is exact, `2 ** 53 + 1` can't be a JS number and rounds to `2 ** 53`
+        native.expect_safe_integer_bounds([
+            Number.MAX_SAFE_INTEGER,
+            Number.MAX_SAFE_INTEGER,
+            Number.MIN_SAFE_INTEGER,
+            2 ** 53,
+            2 ** 53 + 1,
+        ]);
+    });
+
+    it('expect_int_widths', () => {
+        const widths = [-128, -32768, -(2 ** 31), -5, -7, 255, 65535, 2 ** 32 - 1, 7, 9];
+        native.expect_int_widths(widths);
+        native.expect_int_widths(widths.map(BigInt));
+        native.expect_bigint_extremes([
+            -(2n ** 63n),
+            2n ** 63n - 1n,
+            2n ** 64n - 1n,
+            -(2n ** 127n),
+            2n ** 127n - 1n,
+            2n ** 128n - 1n,
+        ]);
+    });
+
+    it('expect_aliased', () => {
+        for (const obj of [{ name: 'bob' }, { userName: 'bob' }, { user_name: 'bob' }]) {
+            native.expect_aliased(obj);
+            native.expect_aliased_lookup(obj);
+        }
+    });
+
+    it('getters are read once', () => {
+        function counting() {
+            const counts = { a: 0, b: 0, extra: 0 };
+            const obj = {};
+            for (const [key, value] of [['a', 1], ['b', 'b'], ['extra', true]]) {
+                Object.defineProperty(obj, key, {
+                    enumerable: true,
+                    get() {
+                        counts[key] += 1;
+                        return value;
+                    },
+                });
+            }
+            return [obj, counts];
+        }
+
+        let [obj, counts] = counting();
+        native.expect_sparse(obj);
+        expect(counts).toEqual({ a: 1, b: 1, extra: 1 });
+
+        [obj, counts] = counting();
+        native.expect_sparse_lookup(obj);
+        expect(counts).toEqual({ a: 1, b: 1, extra: 0 });
+    });
+
+    it('expect_lowercase_variants', () => {
+        native.expect_lowercase_variants(['red', { lightblue: 3 }]);
+        native.expect_lowercase_variants(['Red', { LightBlue: 3 }]);
+        native.expect_ambiguous_variant('Ok');
+    });
+
+    it('expect_screaming_variants', () => {
+        native.expect_screaming_variants(['RED', { LIGHT_BLUE: 3 }, { lightblue: 4 }]);
+    });
+
+    it('expect_expected_variants', () => {
+        native.expect_expected_variants(['Red', 'red']);
+    });
+
+    it('expect_nan_as_none', () => {
+        native.expect_nan_as_none([NaN, 1.5, NaN, Infinity]);
+        native.expect_nan_as_none([null, 1.5, undefined, Infinity]);
+    });
+
+    it('expect_undefined_as_default', () => {
+        native.expect_undefined_as_default([[1, undefined, 3], [undefined, 'b'], [undefined, 2]]);
+        native.expect_undefined_as_default([[1, , 3], [, 'b'], [null, 2]]);
+    });
+
+    it('expect_sparse_lookup', () => {
+        const wide = { z: 0, a: 1, b: 'b' };
+        for (let i = 0; i < 1000; i++) {
+            wide[`extra${i}`] = i;
+        }
+        native.expect_sparse(wide);
+        native.expect_sparse_lookup(wide);
+        native.expect_sparse_lookup({ a: 1, b: 'b', c: undefined });
+        native.expect_sparse_lookup(Object.assign(Object.create({ c: 5 }), { a: 1, b: 'b' }));
+        native.expect_sparse_lookup(Object.assign(Object.create(null), { a: 1, b: 'b' }));
+    });
+
+    it('key_allocations', () => {
+        const wide = { a: 1, b: 'b' };
+        for (let i = 0; i < 1000; i++) {
+            wide[`extra${i}`] = i;
+        }
+        const [asStruct, asMap] = native.key_allocations(wide);
+        expect(asMap).toBeGreaterThanOrEqual(1000);
+        if (native.make_key_buffer_enabled()) {
+            expect(asStruct).toBeLessThan(100);
+        } else {
+            expect(asStruct).toBeGreaterThanOrEqual(1000);
+        }
+    });
+
+    it('expect_presized_strings', () => {
+        const strings = Array.from({ length: 40000 }, (_, i) => `string ${i}`);
+        expect(native.expect_presized_strings(strings)).toBe(40000);
+    });
+
+    it('json_number_kind', () => {
+        expect(native.json_number_kind(5)).toBe('i64');
+        expect(native.json_number_kind(-5)).toBe('i64');
+        expect(native.json_number_kind(5.5)).toBe('f64');
+        expect(native.json_number_kind(1e-17)).toBe('f64');
+        expect(native.json_number_kind(2 ** 63)).toBe('u64');
+        expect(native.json_number_kind(1e300)).toBe('f64');
+    });
+
+    it('expect_strict_f32', () => {
+        native.expect_strict_f32(0.5);
+        native.expect_strict_f32_string('0.5');
+        native.expect_rounded_f32(0.1);
+    });
+
+    it('sum_reused_deserializer', () => {
+        expect(native.sum_reused_deserializer([1, 2, 3, 4])).toBe(10);
+        expect(native.sum_reused_deserializer([])).toBe(0);
+    });
+
+    it('expect_pair_map', () => {
+        native.expect_pair_map([['a', 1], ['b', 2]]);
+        native.expect_pair_map({ a: 1, b: 2 });
+    });
+
     it('expect_buffer', () => {
         native.expect_buffer(new Buffer([252, 251, 250]));
         native.expect_buffer(new Uint8Array([252, 251, 250]));
+        native.expect_buffer([252, 251, 250]);
 
         const version = Number(process.versions.modules);
 
@@ -106,11 +834,24 @@ describe('all values ok', () => {
         }
     });
 
+    it('expect_proto_map', () => {
+        const o = JSON.parse('{"__proto__": 1, "constructor": 2, "a": 3}');
+        expect(Object.getOwnPropertyNames(o)).toContain('__proto__');
+        native.expect_proto_map(o);
+    });
+
     it('rt_rust_js_rust', () => {
         const obj = native.make_object();
         native.expect_obj(obj);
     });
 
+    it('rt_slices', () => {
+        expect(native.make_boxed_slice()).toEqual([1, 2, 3]);
+        expect(native.make_rc_slice()).toEqual(['a', 'b']);
+        native.expect_boxed_slice(native.make_boxed_slice());
+        native.expect_rc_slice(native.make_rc_slice());
+    });
+
     it('rt_js_rust_js', () => {
         const o = {
             a: 1,
@@ -134,9 +875,146 @@ describe('all values ok', () => {
         const o2 = native.roundtrip_object(o);
         expect(o).toEqual(o2);
     });
+
+    it('roundtrip_nested_option', () => {
+        native.expect_nested_none(null);
+        native.expect_nested_none(undefined);
+        native.expect_nested_some(5);
+        const values = [null, 'a', '', null, 'b'];
+        expect(native.roundtrip_nested_option(values)).toEqual(values);
+        expect(native.roundtrip_nested_option([undefined])).toEqual([null]);
+    });
+
+    it('roundtrip_optional_bytes', () => {
+        const out = native.roundtrip_optional_bytes([
+            { name: 'a', data: Buffer.from([1, 2, 3]) },
+            { name: 'b', data: null },
+            { name: 'c' },
+        ]);
+        expect(Buffer.isBuffer(out[0].data)).toBe(true);
+        expect([...out[0].data]).toEqual([1, 2, 3]);
+        expect(out[1]).toEqual({ name: 'b', data: null });
+        expect(out[2]).toEqual({ name: 'c', data: null });
+
+        const omitted = native.make_optional_bytes_omit();
+        expect(Object.keys(omitted)).toEqual(['a']);
+        expect(Buffer.isBuffer(omitted.a)).toBe(true);
+        expect([...omitted.a]).toEqual([1, 2]);
+    });
+
+    it('make_collected', () => {
+        const [evens, empty, many, squares] = native.make_collected();
+        expect(evens).toEqual([0, 2, 4, 6, 8]);
+        expect(empty).toEqual([]);
+        expect(many.length).toBe(50000);
+        expect(many[49999]).toBe(99998);
+        expect(squares).toEqual({ 0: 0, 2: 4, 4: 16, 6: 36 });
+    });
+
+    it('expect_map_pairs', () => {
+        native.expect_map_pairs(new Map([['b', 2], ['a', 1], ['c', 3]]));
+        native.expect_map_pairs([['b', 2], ['a', 1], ['c', 3]]);
+    });
+
+    it('make_option_vecs', () => {
+        const values = native.make_option_vecs();
+        expect(values).toEqual([[1, 2], null, [], [3]]);
+        native.expect_option_vecs(values);
+        native.expect_option_vecs([[1, 2], undefined, [], [3]]);
+    });
+
+    it('parse_port', () => {
+        expect(native.parse_port('8080')).toBe(8080);
+    });
+
+    it('dump_failure', () => {
+        const valid = { a: 1, b: [1], c: 'x' };
+        expect(native.dump_failure([valid, { a: 2, b: [2, 'oops'], c: 'y' }])).toEqual(['"oops"']);
+        expect(native.dump_failure([valid, { a: 2, b: [2], c: null }])).toEqual(['null']);
+        expect(native.dump_failure([valid, { a: 2, b: [2], c: 'y', d: () => 1 }, 5])).toEqual(['5']);
+        const circular = { a: 'bad' };
+        circular.self = circular;
+        expect(native.dump_failure(circular)).toEqual([
+            '{a: "bad", self: {a: "bad", self: {a: "bad", self: {…}}}}',
+        ]);
+        const [huge] = native.dump_failure({ big: Array.from({ length: 1000 }, (_, i) => i) });
+        expect(huge.startsWith('{big: [0, 1, 2, 3')).toBe(true);
+        expect(huge.endsWith('…')).toBe(true);
+        expect(huge.length).toBe(257);
+    });
+
+    it('dynamic_shape_area', () => {
+        expect(native.dynamic_shape_area({ type: 'circle', r: 1 })).toBe(Math.PI);
+        expect(native.dynamic_shape_area({ type: 'square', side: 3 })).toBe(9);
+    });
+
+    it('roundtrip_unit_variants', () => {
+        const [external, typeValue] = native.roundtrip_unit_variants();
+        expect(external).toEqual(['Red', 'Green', 'Red', 'Amber']);
+        expect(typeValue).toEqual([{ type: 'Red' }, { type: 'Green' }, { type: 'Red' }, { type: 'Amber' }]);
+    });
+
+    it('roundtrip_type_value_tuples', () => {
+        expect(native.roundtrip_type_value_tuples()).toEqual([
+            [{ type: 'Empty', value: [] }, { type: 'Pair', value: [1, 2] }],
+            { type: 'Tuple', value: [3, 'x'] },
+        ]);
+    });
+
+    it('roundtrip_internally_tagged', () => {
+        const events = native.roundtrip_internally_tagged();
+        expect(events).toEqual([{ kind: 'Ping' }, { kind: 'Move', x: 1, y: -2 }, { kind: 'Named', name: 'a' }]);
+        expect(Object.keys(events[1])).toEqual(['kind', 'x', 'y']);
+        native.expect_internally_tagged([{ y: -2, kind: 'Move', x: 1, speed: 3 }, { kind: 'Ping', extra: true }]);
+        native.expect_internally_tagged([{ kind: 'Move', x: 1, y: -2 }, 'Ping']);
+    });
+
+    it('roundtrip_patch', () => {
+        const patch = native.roundtrip_patch({ name: 'a', age: null, tags: [1], skipped: undefined });
+        expect(Object.keys(patch)).toEqual(['age', 'name', 'tags']);
+        expect(patch.age).toBe(null);
+        expect(patch.name).toBe('a');
+        expect(patch.tags).toEqual([1]);
+        expect(native.roundtrip_patch({})).toEqual({});
+    });
+
+    it('scan_until_negative', () => {
+        // elements after the match aren't read, so the string doesn't fail
+        expect(native.scan_until_negative([3, 1, -2, 'not read', 5])).toEqual([3, 1, -2]);
+        expect(native.scan_until_negative([-1])).toEqual([-1]);
+        expect(native.scan_until_negative([1, 2])).toEqual([1, 2]);
+        expect(native.scan_until_negative([])).toEqual([]);
+    });
 });
 
 describe('throwing functions', () => {
+    it('make_merged_array', () => {
+        expect(() => native.make_merged_array()).toThrow(/Unable to coerce value to type: object/);
+    });
+
+    it('expect_undefined_as_default', () => {
+        expect(() => native.expect_undefined_as_default([[1, null, 3], [], []])).toThrow(/invalid type: unit value/);
+    });
+
+    it('expect_string_table', () => {
+        expect(() => native.expect_string_table([])).toThrow(/Invalid string table, expected a `strings` array/);
+        expect(() => native.expect_string_table({ strings: ['name'], data: [{ '#0': '1' }] })).toThrow(
+            /Invalid string table, expected the strings and property names in `data` to be indices in `strings`/,
+        );
+        expect(() => native.expect_string_table({ strings: ['name'], data: [{ 0: '0' }] })).toThrow(
+            /Invalid string table, expected the strings and property names in `data` to be indices in `strings`/,
+        );
+    });
+
+    it('append_struct', () => {
+        const target = [1];
+        expect(() => native.append_struct(target)).toThrow(/Unable to coerce value to type: array/);
+        expect(target).toEqual([1]);
+    });
+
+    it('make_too_deep', () => {
+        expect(() => native.make_too_deep()).toThrow(/nested deeper than 3 levels/);
+    });
 
     it('expect_hello_world', () => {
         expect(() => native.expect_hello_world("GoodBye World")).toThrow(/assertion failed:/);
@@ -152,6 +1030,110 @@ describe('throwing functions', () => {
 
     it('expect_buffer', () => {
         expect(() => native.expect_buffer()).toThrow(/not enough arguments/);
+        expect(() => native.expect_buffer([252, 256, 250])).toThrow(/Invalid byte 256, expected an integer from 0 to 255/);
+        expect(() => native.expect_buffer([252, 251.5, 250])).toThrow(/Invalid byte 251.5/);
+        expect(() => native.expect_buffer([252, -1, 250])).toThrow(/Invalid byte -1/);
+        expect(() => native.expect_buffer([252, '251', 250])).toThrow(/Invalid byte 251/);
+    });
+
+    it('make_named_tuple_mismatch', () => {
+        expect(() => native.make_named_tuple_mismatch()).toThrow(/Expected 1 tuple elements to match the names, got 2/);
+    });
+
+    it('fill_instance_shadowing', () => {
+        class Person {
+            greet() {
+                return 'hi';
+            }
+        }
+        const person = new Person();
+        expect(() => native.fill_instance_shadowing(person))
+            .toThrow(/Field 'greet' would shadow the method of the same name/);
+        expect(person.greet()).toBe('hi');
+        expect(Object.keys(person)).toEqual([]);
+    });
+
+    it('expect_hello_world_friendly', () => {
+        expect(() => native.expect_hello_world_friendly(5))
+            .toThrow(/expected a string but got a number$/);
+        expect(() => native.expect_hello_world(5))
+            .toThrow(/invalid type: integer `5`, expected a string/);
+    });
+
+    it('expect_num_iterable', () => {
+        function* gen() {
+            yield 0;
+            throw new Error('iterator failed');
+        }
+        expect(() => native.expect_num_iterable(gen())).toThrow(/iterator failed/);
+        expect(() => native.expect_num_array(new Set([0, 1, 2, 3]))).toThrow(/expected a sequence/);
+    });
+
+    it('expect_scalar', () => {
+        expect(() => native.expect_scalar_bool(1)).toThrow(/Unable to coerce value to type: bool/);
+        expect(() => native.expect_scalar_string(1)).toThrow(/Unable to coerce value to type: String/);
+        expect(() => native.expect_scalar_f64('1')).toThrow(/Unable to coerce value to type: f64/);
+        expect(() => native.expect_scalar_u8(256)).toThrow(/Unable to coerce value to type: u8/);
+        expect(() => native.expect_scalar_u8(-1)).toThrow(/Unable to coerce value to type: u8/);
+        expect(() => native.expect_scalar_i32(1.5)).toThrow(/Unable to coerce value to type: i32/);
+        expect(() => native.expect_scalar_u8(1e-17)).toThrow(/Unable to coerce value to type: u8/);
+        expect(() => native.expect_scalar_i64(-1e-300)).toThrow(/Unable to coerce value to type: i64/);
+        expect(() => native.expect_scalar_i64(NaN)).toThrow(/Unable to coerce value to type: i64/);
+    });
+
+    it('expect_strict_f32', () => {
+        expect(() => native.expect_strict_f32(0.1))
+            .toThrow(/0.1 can't be represented exactly as an f32/);
+        expect(() => native.expect_strict_f32(1e39))
+            .toThrow(/can't be represented exactly as an f32/);
+        expect(() => native.expect_strict_f32_string('0.1'))
+            .toThrow(/0.1 can't be represented exactly as an f32/);
+    });
+
+    it('expect_custom_message', () => {
+        expect(native.expect_custom_message(3)).toBe(3);
+        expect(() => native.expect_custom_message('three'))
+            .toThrow(/^Expected a count: Error occurred while \(de\)serializing: invalid type: string "three"/);
+    });
+
+    it('expect_lowercase_variants', () => {
+        expect(() => native.expect_lowercase_variants(['blue']))
+            .toThrow(/unknown variant `blue`, expected `Red` or `LightBlue`/);
+        expect(() => native.expect_ambiguous_variant('ok'))
+            .toThrow(/Variant 'ok' matches more than one of \["Ok", "OK"\] ignoring case/);
+        expect(() => native.expect_lowercase_variants([{ LIGHT_BLUE: 3 }]))
+            .toThrow(/unknown variant `LIGHT_BLUE`/);
+    });
+
+    it('expect_expected_variants', () => {
+        expect(() => native.expect_expected_variants([{ LightBlue: 3 }]))
+            .toThrow(/Unexpected variant 'LightBlue', expected one of \["Red"\]/);
+        expect(() => native.expect_expected_variants(['green']))
+            .toThrow(/Unexpected variant 'green', expected one of \["Red"\]/);
+    });
+
+    it('expect_sparse_lookup', () => {
+        expect(() => native.expect_sparse_lookup({ b: 'b' })).toThrow(/missing field `a`/);
+        expect(() => native.expect_sparse_lookup({ a: 'a' })).toThrow(/invalid type: string "a"/);
+    });
+
+    it('sum_reused_deserializer', () => {
+        expect(() => native.sum_reused_deserializer([1, 'a', 3])).toThrow(/invalid type: string "a"/);
+    });
+
+    it('expect_pair_map', () => {
+        expect(() => native.expect_pair_map([['a', 1], ['b']]))
+            .toThrow(/Expected a \[key, value\] pair at index 1/);
+        expect(() => native.expect_pair_map([['a', 1], 'b']))
+            .toThrow(/Expected a \[key, value\] pair at index 1/);
+    });
+
+    it('expect_num_array_all_errors', () => {
+        native.expect_num_array_all_errors([0, 1, 2, 3]);
+        expect(() => native.expect_num_array_all_errors([0, 'a', 2, 'b']))
+            .toThrow(/^2 error\(s\) occurred while deserializing: .*"a".*; .*"b"/);
+        expect(() => native.expect_num_array([0, 'a', 2, 'b']))
+            .not.toThrow(/error\(s\) occurred/);
     });
 
     it('getter that throws', () => {
@@ -171,4 +1153,173 @@ describe('throwing functions', () => {
         expect(() => native.expect_obj(obj))
             .toThrow(/Hi There prop c/);
     })
+
+    it('expect_null_as_default', () => {
+        expect(() => native.expect_null_as_default({ a: null })).toThrow(/missing field `a`/);
+        expect(() => native.expect_sparse({ a: 1, c: null })).toThrow();
+    });
+
+    it('expect_positional_tuple', () => {
+        const inner2 = [-2, true, 'b'];
+        expect(() => native.expect_positional_tuple([{ 0: 1 }, inner2])).toThrow(/invalid length 1/);
+        expect(() => native.expect_positional_tuple([{ 0: 1, 1: 'a', 2: 3 }, inner2]))
+            .toThrow(/invalid length 3/);
+        expect(() => native.expect_positional_tuple([{ 0: 1, x: 'a' }, inner2])).toThrow();
+    });
+
+    it('expect_positional_variant', () => {
+        expect(() => native.expect_positional_variant({ Tuple: { 0: 27 } })).toThrow(/invalid length 1/);
+        expect(() => native.expect_positional_variant({ Tuple: { 0: 27, 1: 'hij', 2: 0 } })).toThrow(/invalid length 3/);
+    });
+
+    it('expect_date_string', () => {
+        expect(() => native.expect_date_string({ at: new Date(NaN) })).toThrow(/Invalid Date/);
+    });
+
+    it('expect_reinterpreted', () => {
+        expect(() => native.expect_reinterpreted(new ArrayBuffer(12))).toThrow(/ArrayBuffer of 12 bytes isn't a multiple of 8/);
+        expect(() => native.expect_reinterpreted(new Float64Array([1.5, 2.5]))).toThrow();
+    });
+
+    it('expect_string_chars', () => {
+        expect(() => native.expect_string_chars('abc')).toThrow(/assertion/);
+        expect(() => native.expect_num_array('0123')).toThrow(/invalid type/);
+    });
+
+    it('expect_captured', () => {
+        const input = { id: 1, name: 'a', b: 2, c: [true] };
+        expect(() => native.expect_captured({ ...input, extra: 5 })).toThrow(/assertion/);
+        expect(() => native.expect_captured_into_string(input)).toThrow(/invalid type: map, expected a string/);
+        expect(() => native.expect_captured_without_field(input)).toThrow(/no field 'rest' to capture/);
+    });
+
+    it('expect_date_millis', () => {
+        const at = new Date(Date.UTC(2020, 0, 2, 3, 4, 5, 678));
+        expect(() => native.expect_date_millis([at, new Date(NaN), at])).toThrow(/Invalid Date/);
+        expect(() => native.expect_date_millis([at, new Date(-1000), at])).toThrow(/invalid value: integer `-1000`, expected u64/);
+        expect(() => native.expect_num_array([new Date(0), 1, 2, 3])).toThrow(/invalid type: map/);
+    });
+
+    it('expect_tuple_fill_defaults', () => {
+        expect(() => native.expect_tuple_fill_enum([1])).toThrow(/invalid type: missing tuple element/);
+        expect(() => native.expect_positional_tuple([[1], [-2, true, 'b']])).toThrow(/invalid length 1/);
+        expect(() => native.expect_variant_fill_defaults([{ Tuple: { 0: 1, 1: 'a', 2: 0 } }, { Tuple: [] }])).toThrow(
+            /invalid length 3/,
+        );
+    });
+
+    it('expect_bigint_string', () => {
+        expect(() => native.expect_date_string({ at: 1n })).toThrow(/invalid type: integer `1`, expected a string/);
+    });
+
+    it('expect_map_pairs', () => {
+        expect(() => native.expect_map_pairs(new Map([['b', 2], ['a', 'x'], ['c', 3]]))).toThrow(/invalid type/);
+        expect(() => native.expect_map_pairs(new Set([['b', 2], ['a', 1], ['c', 3]]))).toThrow();
+        expect(() => native.expect_map_pairs({ b: 2, a: 1, c: 3 })).toThrow();
+    });
+
+    it('roundtrip_internally_tagged', () => {
+        expect(() => native.make_tagged_tuple()).toThrow(/Variant 'Pair' must hold a struct or map to be internally tagged/);
+        expect(() => native.expect_internally_tagged([{ x: 1, y: -2 }, { kind: 'Ping' }])).toThrow(/object without a string `kind`/);
+        expect(() => native.expect_internally_tagged([{ kind: 'Jump' }, { kind: 'Ping' }])).toThrow(/unknown variant `Jump`/);
+        expect(() => native.expect_internally_tagged([{ kind: 'Move', x: 1 }, { kind: 'Ping' }])).toThrow(/missing field `y`/);
+    });
+
+    it('parse_port', () => {
+        expect(() => native.parse_port('80a')).toThrow(Error);
+        expect(() => native.parse_port('80a')).toThrow('invalid digit found in string');
+        expect(() => native.parse_port('65536')).toThrow('number too large to fit in target type');
+    });
+
+    it('make_query_params_nested', () => {
+        expect(() => native.make_query_params_nested(false)).toThrow(/Query parameter 'tags' isn't a primitive value/);
+    });
+
+    it('expect_all_fields', () => {
+        expect(() => native.expect_all_fields({ a: 1, b: null, constructor: 2 })).toThrow(/missing field `c`/);
+        expect(() => native.expect_all_fields({ a: 1, c: 0, constructor: 2 })).toThrow(/missing field `b`/);
+        expect(() => native.expect_all_fields(Object.create({ a: 1, b: null, c: 0, constructor: 2 }))).toThrow(/missing field `a`/);
+    });
+
+    it('expect_all_fields_aliased', () => {
+        expect(() => native.expect_all_fields_aliased({ nick: 'ann' })).toThrow(/missing field `age`/);
+        expect(() => native.expect_all_fields_aliased({ years: 3 })).toThrow(/missing field `handle`/);
+    });
+
+    it('expect_numeric_strings', () => {
+        expect(() => native.expect_numeric_strings(['3,14', '-2', '1e3'])).toThrow(/invalid type: string "3,14", expected f64/);
+        expect(() => native.expect_numeric_strings(['NaN', '-2', '1e3'])).toThrow(/invalid type: string "NaN", expected f64/);
+    });
+
+    it('expect_decimal_comma', () => {
+        expect(() => native.expect_decimal_comma(['1.234,5', '2.5', '1e3'])).toThrow(/invalid type: string "1.234,5", expected f64/);
+    });
+
+    it('expect_nonzero', () => {
+        expect(() => native.expect_nonzero([0, 1, 1])).toThrow(/nonzero/);
+    });
+
+    it('expect_empty_object_unit', () => {
+        expect(() => native.expect_unit({})).toThrow(/expected unit/);
+        expect(() => native.expect_empty_object_unit([{ a: 1 }, {}, {}])).toThrow(/expected unit/);
+        expect(() => native.expect_empty_object_unit([[], {}, {}])).toThrow();
+    });
+
+    it('make_non_ascii_keys_reject', () => {
+        expect(() => native.make_non_ascii_keys_reject()).toThrow(/Property name 'café' isn't ASCII/);
+    });
+
+    it('expect_type_value_enums', () => {
+        const rest = [
+            { type: 'Tuple', value: [27, 'hij'] },
+            { type: 'Struct', value: { a: 128, b: [9, 8, 7] } },
+            { type: 'Value', value: ['z', 'y'] },
+        ];
+        expect(() => native.expect_type_value_enums([{ type: 'Empty', extra: 1 }, ...rest]))
+            .toThrow(/extra, expected only `type` and `value`/);
+        expect(() => native.expect_type_value_enums([{ Empty: null }, ...rest])).toThrow();
+        expect(() => native.expect_type_value_enums([{ type: 'Empty' }, { type: 'Tuple' }, ...rest.slice(1)]))
+            .toThrow(/tuple variant/);
+    });
+
+    it('roundtrip_patch', () => {
+        expect(() => native.roundtrip_patch(5)).toThrow(/Unable to coerce value to type: object/);
+        expect(() => native.roundtrip_patch({ a: undefined, b: Symbol('b') })).toThrow();
+    });
+
+    it('scan_until_negative', () => {
+        expect(() => native.scan_until_negative({ 0: -1 })).toThrow(/Unable to coerce value to type: array/);
+        expect(() => native.scan_until_negative([1, 'a', -1])).toThrow(/invalid type: string "a"/);
+    });
+
+    it('expect_int_widths', () => {
+        const widths = [-128, -32768, -(2 ** 31), -5, -7, 255, 65535, 2 ** 32 - 1, 7, 9];
+        const over = [128, -32769, 2 ** 31, undefined, undefined, 256, -1, 2 ** 32, -1, -1];
+        for (let i = 0; i < widths.length; i++) {
+            if (over[i] === undefined) {
+                continue;
+            }
+            for (const value of [over[i], BigInt(over[i])]) {
+                const args = [...widths];
+                args[i] = value;
+                expect(() => native.expect_int_widths(args)).toThrow(/invalid value: integer/);
+            }
+        }
+        const bigints = widths.map(BigInt);
+        bigints[3] = 2n ** 63n;
+        expect(() => native.expect_int_widths(bigints)).toThrow(/invalid value: integer `9223372036854775808`, expected i64/);
+        bigints[3] = -5n;
+        bigints[4] = 2n ** 127n;
+        expect(() => native.expect_int_widths(bigints)).toThrow(/expected i128/);
+        bigints[4] = -7n;
+        bigints[9] = 2n ** 128n;
+        expect(() => native.expect_int_widths(bigints)).toThrow(/BigInt 340282366920938463463374607431768211456 doesn't fit in 128 bits/);
+    });
+
+    it('dynamic_shape_area', () => {
+        expect(() => native.dynamic_shape_area({ type: 'hexagon', side: 1 }))
+            .toThrow(/No deserializer is registered for type 'hexagon'/);
+        expect(() => native.dynamic_shape_area({ side: 1 })).toThrow(/object without a string `type`/);
+        expect(() => native.dynamic_shape_area({ type: 'square' })).toThrow(/missing field `side`/);
+    });
 });
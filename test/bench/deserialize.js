@@ -0,0 +1,58 @@
+const native = require('../native');
+
+const values = Array.from({ length: 1000000 }, (_, i) => i % 1000);
+const expected = values.reduce((a, b) => a + b, 0);
+
+function bench(name, f) {
+    const start = process.hrtime.bigint();
+    const sum = f(values);
+    const ms = Number(process.hrtime.bigint() - start) / 1e6;
+    if (sum !== expected) {
+        throw new Error(`${name}: expected ${expected}, got ${sum}`);
+    }
+    console.log(`${name}: ${ms.toFixed(1)}ms`);
+}
+
+const wide = { a: 1, b: 'b' };
+for (let i = 0; i < 1000; i++) {
+    wide[`extra${i}`] = i;
+}
+
+function benchKeyAllocations() {
+    const [asStruct, asMap] = native.key_allocations(wide);
+    const buffer = native.make_key_buffer_enabled() ? 'with' : 'without';
+    console.log(
+        `1000 keys struct ${buffer} key_buffer: ${asStruct} allocations, as a map of Strings: ${asMap}`,
+    );
+}
+
+function benchStruct(name, f) {
+    const start = process.hrtime.bigint();
+    for (let i = 0; i < 1000; i++) {
+        f(wide);
+    }
+    const ms = Number(process.hrtime.bigint() - start) / 1e6;
+    console.log(`${name}: ${ms.toFixed(1)}ms`);
+}
+
+const strings = Array.from({ length: 100000 }, (_, i) => `string ${i}`);
+
+function benchStrings(name, f) {
+    const start = process.hrtime.bigint();
+    const len = f(strings);
+    const ms = Number(process.hrtime.bigint() - start) / 1e6;
+    if (len !== strings.length) {
+        throw new Error(`${name}: expected ${strings.length}, got ${len}`);
+    }
+    console.log(`${name}: ${ms.toFixed(1)}ms`);
+}
+
+for (let i = 0; i < 3; i++) {
+    bench('from_value', native.sum_from_value);
+    bench('from_value_scalar', native.sum_from_value_scalar);
+    bench('ReusableDeserializer', native.sum_reused_deserializer);
+    benchStruct('1000 keys struct', native.expect_sparse);
+    benchStruct('1000 keys struct, lookup_struct_fields', native.expect_sparse_lookup);
+    benchStrings('100k strings', native.string_array_len);
+}
+benchKeyAllocations();
@@ -0,0 +1,40 @@
+const native = require('../native');
+
+const fields = Array.from({ length: 24 }, (_, i) => `f${i}`);
+
+function bench(name, f, len = 10000) {
+    const start = process.hrtime.bigint();
+    const values = f(len);
+    const ms = Number(process.hrtime.bigint() - start) / 1e6;
+    const keys = Object.keys(values[values.length - 1]);
+    if (keys.join() !== fields.join()) {
+        throw new Error(`${name}: unexpected keys ${keys}`);
+    }
+    console.log(`${name}: ${ms.toFixed(1)}ms`);
+}
+
+function benchNumbers(name, f, len = 1000000) {
+    const start = process.hrtime.bigint();
+    const values = f(len);
+    const ms = Number(process.hrtime.bigint() - start) / 1e6;
+    if (values.length !== len || values[len - 1] !== (len - 1) / 2) {
+        throw new Error(`${name}: unexpected values`);
+    }
+    console.log(`${name}: ${ms.toFixed(1)}ms`);
+}
+
+for (let i = 0; i < 3; i++) {
+    bench('10k structs of 24 fields', native.make_wide_structs);
+}
+for (let i = 0; i < 3; i++) {
+    bench('50k structs of 24 fields', native.make_wide_structs, 50000);
+    bench('50k structs of 24 fields, to_record_batch', native.make_record_batch, 50000);
+}
+for (let i = 0; i < 3; i++) {
+    bench('50k structs of 24 fields, one to_value each', native.make_wide_structs_each, 50000);
+    bench('50k structs of 24 fields, to_value_with_plan', native.make_wide_structs_planned, 50000);
+}
+for (let i = 0; i < 3; i++) {
+    benchNumbers('1M f64, element by element', native.make_f64s);
+    benchNumbers('1M f64, typed_arrays', native.make_f64s_typed);
+}
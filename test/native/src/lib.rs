@@ -1,10 +1,43 @@
 extern crate neon;
 extern crate neon_serde;
 extern crate serde_bytes;
+extern crate serde_json;
+#[cfg(feature = "uuid")]
+extern crate uuid;
+#[cfg(feature = "smallvec")]
+extern crate smallvec;
+#[cfg(feature = "arrayvec")]
+extern crate arrayvec;
+#[cfg(feature = "indexmap")]
+extern crate indexmap;
 #[macro_use]
 extern crate serde_derive;
 
 use neon::prelude::*;
+use neon_serde::ResultExt;
+use std::{
+    alloc::{GlobalAlloc, Layout, System},
+    sync::atomic::{AtomicUsize, Ordering},
+};
+
+/// Counts allocations, to compare how many reading a value takes
+struct CountingAllocator;
+
+static ALLOCATIONS: AtomicUsize = AtomicUsize::new(0);
+
+unsafe impl GlobalAlloc for CountingAllocator {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        ALLOCATIONS.fetch_add(1, Ordering::Relaxed);
+        System.alloc(layout)
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        System.dealloc(ptr, layout)
+    }
+}
+
+#[global_allocator]
+static ALLOCATOR: CountingAllocator = CountingAllocator;
 
 #[derive(Serialize, Debug, Deserialize)]
 struct AnObject {
@@ -27,6 +60,145 @@ enum TypeEnum {
     Value(Vec<char>),
 }
 
+#[derive(Serialize, Debug, Deserialize, Eq, PartialEq)]
+enum EmptyTuple {
+    Empty(),
+    Unit,
+    Pair(u8, u8),
+}
+
+#[derive(Deserialize, Debug, PartialEq)]
+enum Color {
+    Red,
+    LightBlue(u8),
+}
+
+#[derive(Deserialize, Debug, PartialEq)]
+enum Cased {
+    Ok,
+    OK,
+}
+
+#[derive(Deserialize, Debug, PartialEq)]
+struct Aliased {
+    #[serde(alias = "userName", alias = "user_name")]
+    name: String,
+}
+
+#[derive(Deserialize, Debug, PartialEq)]
+struct Empty {}
+
+#[derive(Deserialize, Debug, PartialEq)]
+struct Stamped {
+    at: String,
+}
+
+#[derive(Deserialize, Debug, PartialEq)]
+struct Balance {
+    amount: String,
+}
+
+#[derive(Deserialize, Debug, PartialEq)]
+struct Sparse {
+    a: u32,
+    b: Option<String>,
+    #[serde(default)]
+    c: u32,
+    constructor: Option<u32>,
+}
+
+#[derive(Deserialize, Debug, PartialEq)]
+struct AliasedFields {
+    #[serde(alias = "nick", alias = "handle")]
+    name: Option<String>,
+    #[serde(default, alias = "years")]
+    age: u32,
+}
+
+#[derive(Serialize, Debug)]
+#[serde(untagged)]
+enum Mixed {
+    Num(u32),
+    Str(String),
+}
+
+#[derive(Serialize, Debug)]
+#[serde(untagged)]
+enum Letter {
+    Char(char),
+    Num(u32),
+}
+
+#[derive(Serialize, Debug)]
+struct NoFields {}
+
+#[derive(Serialize, Debug)]
+struct Marker;
+
+#[derive(Serialize, Debug)]
+struct AllSkipped {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    a: Option<u32>,
+}
+
+#[derive(Serialize, Debug)]
+struct Unsorted {
+    zeta: u32,
+    alpha: u32,
+    mid: TypeEnum,
+}
+
+#[derive(Serialize, Debug)]
+struct Accented {
+    #[serde(rename = "café")]
+    cafe: u8,
+    plain: u8,
+}
+
+#[derive(Serialize, Debug)]
+struct OddKeys {
+    #[serde(rename = "__proto__")]
+    proto: u8,
+    #[serde(rename = "quote\" back\\slash\u{0}1 é😀")]
+    odd: u8,
+    #[serde(rename = "0")]
+    index: u8,
+}
+
+#[derive(Serialize, Debug)]
+struct Wide {
+    f0: String,
+    f1: u32,
+    f2: u32,
+    f3: String,
+    f4: u32,
+    f5: u32,
+    f6: String,
+    f7: u32,
+    f8: u32,
+    f9: String,
+    f10: u32,
+    f11: u32,
+    f12: String,
+    f13: u32,
+    f14: u32,
+    f15: String,
+    f16: u32,
+    f17: u32,
+    f18: String,
+    f19: u32,
+    f20: u32,
+    f21: String,
+    f22: u32,
+    f23: u32,
+}
+
+#[derive(Serialize, Debug, Hash, Eq, PartialEq)]
+struct Point {
+    x: i32,
+    y: i32,
+}
+
 #[derive(Serialize, Debug, Deserialize, PartialEq)]
 struct AnObjectTwo {
     a: u32,
@@ -63,6 +235,22 @@ macro_rules! make_test {
     };
 }
 
+macro_rules! make_test_with {
+    ($name:ident, $val:expr, $opts:expr) => {
+        fn $name(cx: FunctionContext) -> JsResult<JsValue> {
+            fn inner(mut cx: FunctionContext) -> neon_serde::errors::Result<Handle<JsValue>> {
+                let value = $val;
+                let options = $opts;
+
+                let handle = neon_serde::to_value_with(&mut cx, &value, &options)?;
+                Ok(handle)
+            }
+
+            Ok(inner(cx)?)
+        }
+    };
+}
+
 make_test!(make_num_77, 77i32);
 make_test!(make_num_32, 32u8);
 make_test!(make_str_hello, "Hello World");
@@ -84,6 +272,28 @@ make_test!(make_map, {
     map
 });
 
+make_test!(make_json_map, {
+    use std::collections::HashMap;
+    let mut map: HashMap<String, serde_json::Value> = HashMap::new();
+    map.insert("number".into(), serde_json::json!(1.5));
+    map.insert("string".into(), serde_json::json!("two"));
+    map.insert("bool".into(), serde_json::json!(true));
+    map.insert("null".into(), serde_json::Value::Null);
+    map.insert("array".into(), serde_json::json!([1, "a"]));
+    map.insert("object".into(), serde_json::json!({ "nested": { "deep": 3 } }));
+    map
+});
+
+make_test!(
+    make_option_vecs,
+    vec![Some(vec![1, 2]), None, Some(vec![]), Some(vec![3])]
+);
+
+make_test!(
+    make_collected,
+    (Evens(10), Evens(0), Evens(100_000), EvenSquares(7))
+);
+
 make_test!(make_object, {
     let value = AnObjectTwo {
         a: 1,
@@ -110,86 +320,2439 @@ make_test!(make_object, {
     value
 });
 
-const NUMBER_BYTES: &'static [u8] = &[255u8, 254, 253];
+make_test_with!(
+    make_obj_null_proto,
+    AnObject {
+        a: 1,
+        b: vec![0.1f64, 1.1, 2.2, 3.3],
+        c: "Hi".into(),
+    },
+    neon_serde::SerializeOptions {
+        null_prototype: true,
+        ..Default::default()
+    }
+);
 
-make_test!(make_buff, { serde_bytes::Bytes::new(NUMBER_BYTES) });
+make_test_with!(
+    make_sorted_keys,
+    {
+        use std::collections::HashMap;
+        let mut map = HashMap::new();
+        map.insert("b", 2);
+        map.insert("c", 3);
+        map.insert("a", 1);
+        let value = Unsorted {
+            zeta: 1,
+            alpha: 2,
+            mid: TypeEnum::Struct {
+                b: vec![],
+                a: 3,
+            },
+        };
+        (value, map)
+    },
+    neon_serde::SerializeOptions {
+        sort_keys: true,
+        ..Default::default()
+    }
+);
 
-macro_rules! make_expect {
-    ($name:ident, $val:expr, $val_type:ty) => {
-        fn $name(cx: FunctionContext) -> JsResult<JsValue> {
-            fn inner(mut cx: FunctionContext) -> neon_serde::errors::Result<Handle<JsValue>> {
-                let value = $val;
-                let arg0 = cx.argument::<JsValue>(0)?;
+make_test_with!(
+    make_sorted_map_keys,
+    {
+        let map: std::collections::HashMap<String, u32> =
+            (0..20).map(|i| (format!("key{:02}", i), i)).collect();
+        let value = Unsorted {
+            zeta: 1,
+            alpha: 2,
+            mid: TypeEnum::Struct {
+                b: vec![],
+                a: 3,
+            },
+        };
+        (value, map)
+    },
+    neon_serde::SerializeOptions {
+        sort_map_keys: true,
+        ..Default::default()
+    }
+);
 
-                let de_serialized: $val_type = neon_serde::from_value(&mut cx, arg0)?;
-                assert_eq!(value, de_serialized);
-                Ok(JsUndefined::new().upcast())
-            }
-            Ok(inner(cx)?)
-        }
-    };
+make_test!(make_no_fields, (NoFields {}, Marker));
+make_test_with!(
+    make_string_table_order,
+    (OnlyB { b: 1 }, AThenB { a: 1, b: 2 }),
+    neon_serde::SerializeOptions {
+        string_table: true,
+        ..Default::default()
+    }
+);
+make_test_with!(
+    make_string_table,
+    tags(),
+    neon_serde::SerializeOptions {
+        string_table: true,
+        ..Default::default()
+    }
+);
+make_test_with!(
+    make_no_fields_null,
+    (NoFields {}, Marker),
+    neon_serde::SerializeOptions {
+        empty_as_null: true,
+        ..Default::default()
+    }
+);
+
+make_test_with!(
+    make_empty_as_null,
+    {
+        let map: std::collections::HashMap<String, u32> = Default::default();
+        (Vec::<u32>::new(), map, AllSkipped { a: None }, vec![1])
+    },
+    neon_serde::SerializeOptions {
+        empty_as_null: true,
+        ..Default::default()
+    }
+);
+
+make_test_with!(
+    make_typed_arrays,
+    (
+        vec![1.5f64, 2.5],
+        vec![1u8, 2, 3],
+        vec![-1i32, 2],
+        Vec::<f32>::new(),
+        vec![1u64, 2],
+        vec![Mixed::Num(1), Mixed::Str("two".into()), Mixed::Num(3)],
+        vec![Mixed::Str("one".into()), Mixed::Num(2)],
+    ),
+    neon_serde::SerializeOptions {
+        typed_arrays: true,
+        ..Default::default()
+    }
+);
+
+make_test_with!(
+    make_particles,
+    {
+        let particles = Particles {
+            x: vec![0.0, 1.0],
+            y: vec![2.0, 3.0],
+            vx: vec![-0.5, 0.5],
+        };
+        let mut frames = std::collections::BTreeMap::new();
+        frames.insert(
+            "next",
+            vec![Particles {
+                x: vec![1.5],
+                y: vec![],
+                vx: vec![0.25],
+            }],
+        );
+        (particles, frames)
+    },
+    neon_serde::SerializeOptions {
+        typed_arrays: true,
+        ..Default::default()
+    }
+);
+
+make_test_with!(
+    make_validated_strings,
+    (
+        "a\u{0}é\u{301}ñ 日本語 مرحبا \u{200d}👩\u{200d}👩\u{200d}👧 🇨🇦 \u{ffff}\u{10000}\u{10ffff}",
+        vec!['\u{0}', 'é', '\u{301}', '日', '🤷', '\u{10ffff}'],
+        String::new(),
+    ),
+    neon_serde::SerializeOptions {
+        validate_output_strings: true,
+        ..Default::default()
+    }
+);
+
+make_test_with!(
+    make_bytes_with_length,
+    serde_bytes::ByteBuf::from(vec![1u8, 2, 3]),
+    neon_serde::SerializeOptions {
+        bytes_with_length: true,
+        ..Default::default()
+    }
+);
+
+make_test_with!(
+    make_map_none_null,
+    {
+        use std::collections::HashMap;
+        let mut map = HashMap::new();
+        map.insert("a", Some(1));
+        map.insert("b", None);
+        map
+    },
+    neon_serde::SerializeOptions::default()
+);
+
+make_test_with!(
+    make_map_none_omit,
+    {
+        use std::collections::HashMap;
+        let mut map = HashMap::new();
+        map.insert("a", Some(1));
+        map.insert("b", None);
+        map
+    },
+    neon_serde::SerializeOptions {
+        map_none_policy: neon_serde::NonePolicy::Omit,
+        ..Default::default()
+    }
+);
+
+make_test_with!(
+    make_map_null_values_omit,
+    {
+        use std::collections::BTreeMap;
+        let mut map = BTreeMap::new();
+        map.insert("none", None);
+        map.insert("unit", Some(()));
+        map
+    },
+    neon_serde::SerializeOptions {
+        map_none_policy: neon_serde::NonePolicy::Omit,
+        ..Default::default()
+    }
+);
+
+make_test_with!(
+    make_json_null_omit,
+    serde_json::json!({ "a": null, "b": 1 }),
+    neon_serde::SerializeOptions {
+        map_none_policy: neon_serde::NonePolicy::Omit,
+        ..Default::default()
+    }
+);
+
+make_test_with!(
+    make_optional_bytes_omit,
+    {
+        use std::collections::BTreeMap;
+        let mut map = BTreeMap::new();
+        map.insert("a", Some(serde_bytes::ByteBuf::from(vec![1u8, 2])));
+        map.insert("b", None);
+        map
+    },
+    neon_serde::SerializeOptions {
+        map_none_policy: neon_serde::NonePolicy::Omit,
+        ..Default::default()
+    }
+);
+
+#[derive(Serialize, Debug)]
+struct Link {
+    id: u32,
+    tags: Vec<&'static str>,
+    next: Option<Box<Link>>,
 }
 
-make_expect!(expect_hello_world, "hello world", String);
+fn chain(len: u32) -> Option<Box<Link>> {
+    (1..=len).rev().fold(None, |next, id| {
+        Some(Box::new(Link {
+            id,
+            tags: vec!["x"],
+            next,
+        }))
+    })
+}
 
-make_expect!(
-    expect_obj,
-    AnObjectTwo {
-        a: 1,
-        b: vec![1, 2],
-        c: "abc".into(),
-        d: Some(false),
-        e: None,
-        f: Inner,
-        g: Inner2(9, false, "efg".into()),
-        h: '🤷',
-        i: TypeEnum::Empty,
-        j: TypeEnum::Tuple(27, "hij".into()),
-        k: TypeEnum::Struct {
+make_test_with!(
+    make_truncated,
+    (chain(4), serde_json::json!([1, [2, [3, [4]]]])),
+    neon_serde::SerializeOptions {
+        max_depth: Some(3),
+        ..Default::default()
+    }
+);
+
+make_test_with!(
+    make_too_deep,
+    chain(4),
+    neon_serde::SerializeOptions {
+        max_depth: Some(3),
+        on_depth_exceeded: neon_serde::DepthPolicy::Error,
+        ..Default::default()
+    }
+);
+
+make_test_with!(
+    make_with_metadata,
+    chain(2),
+    neon_serde::SerializeOptions {
+        metadata_symbol: Some(("neon-serde.meta", |name| {
+            Some(vec![("type", name.to_string()), ("version", "1".into())])
+        })),
+        ..Default::default()
+    }
+);
+
+make_test_with!(
+    make_char_vec_string,
+    (
+        vec!['a', 'b', 'c', '😀'],
+        Vec::<char>::new(),
+        vec![Letter::Char('a'), Letter::Char('b'), Letter::Num(3)],
+        vec![1.5f64],
+        vec!['z', 'y'],
+    ),
+    neon_serde::SerializeOptions {
+        char_vec_as_string: true,
+        ..Default::default()
+    }
+);
+
+make_test_with!(
+    make_char_vec_string_typed,
+    (
+        vec![Letter::Char('a'), Letter::Num(3)],
+        vec![1u8, 2],
+        vec!['z', 'y'],
+    ),
+    neon_serde::SerializeOptions {
+        char_vec_as_string: true,
+        typed_arrays: true,
+        ..Default::default()
+    }
+);
+make_test!(make_char_vec, vec!['z', 'y']);
+make_test!(make_reply, reply());
+
+make_test_with!(
+    make_boxed_numbers,
+    {
+        use std::collections::BTreeMap;
+        let mut map = BTreeMap::new();
+        map.insert(1u8, 2.5f64);
+        (42u32, -1.5f64, map)
+    },
+    neon_serde::SerializeOptions {
+        boxed_numbers: true,
+        ..Default::default()
+    }
+);
+
+make_test_with!(
+    make_type_value_enums,
+    vec![
+        TypeEnum::Empty,
+        TypeEnum::Tuple(27, "hij".into()),
+        TypeEnum::Struct {
             a: 128,
             b: vec![9, 8, 7],
         },
-        l: "jkl".into(),
-        m: vec![0, 1, 2, 3, 4],
-        o: TypeEnum::Value(vec!['z', 'y', 'x']),
-        p: vec![1., 2., 3.5],
-        q: 999,
-        r: 333
+        TypeEnum::Value(vec!['z', 'y']),
+    ],
+    neon_serde::SerializeOptions {
+        enum_style: neon_serde::EnumStyle::TypeValue,
+        ..Default::default()
+    }
+);
+
+make_test!(
+    make_empty_tuple_variant,
+    vec![EmptyTuple::Empty(), EmptyTuple::Unit, EmptyTuple::Pair(1, 2)]
+);
+
+make_test!(
+    make_odd_keys,
+    OddKeys {
+        proto: 1,
+        odd: 2,
+        index: 3,
+    }
+);
+
+make_test_with!(
+    make_odd_keys_null_proto,
+    OddKeys {
+        proto: 1,
+        odd: 2,
+        index: 3,
     },
-    AnObjectTwo
+    neon_serde::SerializeOptions {
+        null_prototype: true,
+        ..Default::default()
+    }
 );
 
-make_expect!(expect_num_array, vec![0, 1, 2, 3], Vec<i32>);
+make_test_with!(
+    make_flat_newtype_variants,
+    vec![
+        TypeEnum::Value(vec!['a', 'b']),
+        TypeEnum::Empty,
+        TypeEnum::Tuple(1, "x".into()),
+    ],
+    neon_serde::SerializeOptions {
+        flatten_newtype_variants: true,
+        ..Default::default()
+    }
+);
 
-make_expect!(
-    expect_buffer,
-    serde_bytes::ByteBuf::from(vec![252u8, 251, 250]),
-    serde_bytes::ByteBuf
+macro_rules! make_non_ascii_keys {
+    ($name:ident, $policy:ident) => {
+        make_test_with!(
+            $name,
+            {
+                let mut map = std::collections::BTreeMap::new();
+                map.insert("naïve", 2);
+                (Accented { cafe: 1, plain: 0 }, map)
+            },
+            neon_serde::SerializeOptions {
+                on_non_ascii_key: neon_serde::NonAsciiKeyPolicy::$policy,
+                ..Default::default()
+            }
+        );
+    };
+}
+
+make_non_ascii_keys!(make_non_ascii_keys_allow, Allow);
+make_non_ascii_keys!(make_non_ascii_keys_reject, Reject);
+make_non_ascii_keys!(make_non_ascii_keys_escape, EscapeUnicode);
+
+make_test_with!(
+    make_float_decimals,
+    (3.14159f64, 2.71828f32, 1e300f64, 7u32, vec![1.23456f64, -0.5]),
+    neon_serde::SerializeOptions {
+        float_decimals: Some(2),
+        typed_arrays: true,
+        ..Default::default()
+    }
+);
+make_test_with!(
+    make_tuple_bytes,
+    (7u32, &[1u8, 2, 3][..], vec![1u16, 2], Vec::<u8>::new()),
+    neon_serde::SerializeOptions {
+        typed_arrays: true,
+        ..Default::default()
+    }
 );
+#[derive(Serialize)]
+struct Account {
+    name: String,
+    password: String,
+    internal: u32,
+    settings: std::collections::BTreeMap<String, u32>,
+}
 
-fn roundtrip_object(mut cx: FunctionContext) -> JsResult<JsValue> {
-    let arg0 = cx.argument::<JsValue>(0)?;
+#[derive(Debug)]
+struct ShoutAndRedact;
 
-    let de_serialized: AnObjectTwo = neon_serde::from_value(&mut cx, arg0)?;
-    let handle = neon_serde::to_value(&mut cx, &de_serialized)?;
-    Ok(handle)
+impl neon_serde::SerializeTransform for ShoutAndRedact {
+    fn transform_key<'k>(&self, key: &'k str) -> std::borrow::Cow<'k, str> {
+        key.to_uppercase().into()
+    }
+
+    fn transform_value(&self, key: &str) -> neon_serde::ValueTransform {
+        match key {
+            "password" => neon_serde::ValueTransform::Replace("[redacted]".into()),
+            "internal" => neon_serde::ValueTransform::Omit,
+            _ => neon_serde::ValueTransform::Keep,
+        }
+    }
 }
 
-register_module!(mut m, {
-    m.export_function("make_num_77", make_num_77)?;
-    m.export_function("make_num_32", make_num_32)?;
-    m.export_function("make_str_hello", make_str_hello)?;
-    m.export_function("make_num_array", make_num_array)?;
-    m.export_function("make_buff", make_buff)?;
-    m.export_function("make_obj", make_obj)?;
-    m.export_function("make_object", make_object)?;
-    m.export_function("make_map", make_map)?;
+make_test_with!(
+    make_transformed,
+    Account {
+        name: "bob".into(),
+        password: "hunter2".into(),
+        internal: 7,
+        settings: std::iter::once(("theme".to_string(), 1)).collect(),
+    },
+    neon_serde::SerializeOptions {
+        transform: Some(std::sync::Arc::new(ShoutAndRedact)),
+        ..Default::default()
+    }
+);
 
-    m.export_function("expect_hello_world", expect_hello_world)?;
-    m.export_function("expect_obj", expect_obj)?;
-    m.export_function("expect_num_array", expect_num_array)?;
-    m.export_function("expect_buffer", expect_buffer)?;
+make_test_with!(
+    make_all_ints_as_bigint,
+    (1u8, -2i32, u64::MAX, i128::MIN, 1.5f64, vec![1u16, 2]),
+    neon_serde::SerializeOptions {
+        all_ints_as_bigint: true,
+        typed_arrays: true,
+        ..Default::default()
+    }
+);
+make_test_with!(
+    make_js_maps,
+    (
+        [(1u64, "one"), ((1 << 60) + 1, "big"), (u64::MAX, "max")]
+            .into_iter()
+            .collect::<std::collections::HashMap<_, _>>(),
+        [(i64::MIN, 1u8), (-1, 2)]
+            .into_iter()
+            .collect::<std::collections::BTreeMap<_, _>>(),
+        Profile {
+            name: "bob".into(),
+            age: 3,
+        },
+    ),
+    neon_serde::SerializeOptions {
+        maps_as_js_map: true,
+        ..Default::default()
+    }
+);
+make_test_with!(
+    make_large_floats_bigint,
+    (1e20f64, -1e20f64, 1e300f64, 9007199254740992f64, 9007199254740991f64, 1e20f64 + 0.5, 2.5f64),
+    neon_serde::SerializeOptions {
+        integral_floats_as_bigint_above: Some(9007199254740991.0),
+        ..Default::default()
+    }
+);
+make_test!(
+    make_infinities,
+    (f64::INFINITY, f64::NEG_INFINITY, f32::NEG_INFINITY)
+);
+make_test_with!(
+    make_infinities_typed,
+    (vec![f64::NEG_INFINITY, 1.0], vec![f32::NEG_INFINITY]),
+    neon_serde::SerializeOptions {
+        typed_arrays: true,
+        float_decimals: Some(2),
+        ..Default::default()
+    }
+);
+make_test_with!(
+    make_json_number_grammar,
+    (
+        f64::NAN,
+        f64::INFINITY,
+        f32::NEG_INFINITY,
+        -0.0f64,
+        -0.0f32,
+        -1.5f64,
+        vec![1.0f64, -0.0],
+        vec![1.0f64, f64::NAN],
+    ),
+    neon_serde::SerializeOptions {
+        json_number_grammar: true,
+        typed_arrays: true,
+        ..Default::default()
+    }
+);
 
-    m.export_function("roundtrip_object", roundtrip_object)?;
+make_test!(make_boxed_slice, vec![1, 2, 3].into_boxed_slice());
+make_test!(
+    make_rc_slice,
+    std::rc::Rc::<[String]>::from(vec!["a".to_string(), "b".to_string()])
+);
+
+make_test!(
+    make_path,
+    neon_serde::JsPath(std::path::Path::new("dir").join("file.txt"))
+);
+make_test_with!(
+    make_path_forward_slash,
+    neon_serde::JsPath(std::path::Path::new("dir").join("file.txt")),
+    neon_serde::SerializeOptions {
+        paths_forward_slash: true,
+        ..Default::default()
+    }
+);
+
+make_test!(make_char_key_map, {
+    use std::collections::HashMap;
+    let mut map = HashMap::new();
+    map.insert('a', 1u32);
+    map.insert('🤷', 2);
+    map
+});
+
+make_test_with!(
+    make_complex_key_map,
+    {
+        use std::collections::HashMap;
+        let mut map = HashMap::new();
+        map.insert(Point { x: 1, y: 2 }, "a");
+        map.insert(Point { x: 3, y: 4 }, "b");
+        map
+    },
+    neon_serde::SerializeOptions {
+        complex_keys_as_pairs: true,
+        ..Default::default()
+    }
+);
+
+make_test_with!(
+    make_simple_key_map,
+    {
+        use std::collections::HashMap;
+        let mut map = HashMap::new();
+        map.insert(1, "a");
+        map.insert(2, "b");
+        map
+    },
+    neon_serde::SerializeOptions {
+        complex_keys_as_pairs: true,
+        ..Default::default()
+    }
+);
+
+make_test!(make_bigint_enabled, cfg!(feature = "bigint"));
+make_test!(make_key_buffer_enabled, cfg!(feature = "key_buffer"));
+make_test!(make_u128_max, u128::MAX);
+make_test!(make_i128_min, i128::MIN);
+make_test!(
+    make_nonzero,
+    (
+        std::num::NonZeroU32::new(7),
+        std::num::NonZeroU64::new((1 << 53) - 1),
+        std::num::NonZeroI64::new(-(1 << 53) + 1),
+        std::num::NonZeroU128::new(u128::MAX),
+    )
+);
+
+#[cfg(feature = "uuid")]
+const UUID: &str = "67e55044-10b1-426f-9247-bb680e5fe0c8";
+
+make_test!(make_uuid_enabled, cfg!(feature = "uuid"));
+#[cfg(feature = "uuid")]
+make_test!(make_uuid, uuid::Uuid::parse_str(UUID).unwrap());
+#[cfg(feature = "uuid")]
+make_test_with!(
+    make_uuid_compact,
+    uuid::Uuid::parse_str(UUID).unwrap(),
+    neon_serde::SerializeOptions {
+        human_readable: false,
+        ..Default::default()
+    }
+);
+
+make_test!(make_smallvec_enabled, cfg!(feature = "smallvec"));
+#[cfg(feature = "smallvec")]
+make_test!(
+    make_smallvec,
+    (
+        smallvec::SmallVec::<[u8; 4]>::from_slice(&[1, 2]),
+        smallvec::SmallVec::<[u8; 4]>::from_slice(&[1, 2, 3, 4, 5, 6])
+    )
+);
+
+make_test!(make_arrayvec_enabled, cfg!(feature = "arrayvec"));
+#[cfg(feature = "arrayvec")]
+make_test!(
+    make_arrayvec,
+    arrayvec::ArrayVec::<u8, 4>::from_iter([1, 2, 3])
+);
+
+make_test!(make_indexmap_enabled, cfg!(feature = "indexmap"));
+
+fn make_named_tuple(cx: FunctionContext) -> JsResult<JsValue> {
+    fn inner(mut cx: FunctionContext) -> neon_serde::errors::Result<Handle<JsValue>> {
+        let value = (7i32, String::from("seven"));
+
+        let handle = neon_serde::to_value_named_tuple(&mut cx, &value, &["x", "y"])?;
+        Ok(handle)
+    }
+
+    Ok(inner(cx)?)
+}
+
+fn make_generator(cx: FunctionContext) -> JsResult<JsValue> {
+    fn inner(mut cx: FunctionContext) -> neon_serde::errors::Result<Handle<JsValue>> {
+        let values = (1..=3).map(|n| vec![n; n]);
+
+        let handle = neon_serde::to_generator(&mut cx, values)?;
+        Ok(handle.upcast())
+    }
+
+    Ok(inner(cx)?)
+}
+
+#[derive(Serialize, Deserialize, Debug, Eq, PartialEq)]
+struct Profile {
+    name: String,
+    age: u32,
+}
+
+#[derive(Serialize, Deserialize, Debug, PartialEq)]
+struct Reply {
+    cursor: neon_serde::AsUndefined<u32>,
+    error: neon_serde::AsNull<String>,
+    count: neon_serde::AsUndefined<u32>,
+}
+
+fn reply() -> Reply {
+    Reply {
+        cursor: neon_serde::AsUndefined(None),
+        error: neon_serde::AsNull(None),
+        count: neon_serde::AsUndefined(Some(2)),
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, PartialEq)]
+struct Tag {
+    name: String,
+    color: String,
+    uses: u32,
+}
+
+fn tags() -> Vec<Tag> {
+    ["red", "red", "blue"]
+        .into_iter()
+        .zip(1..)
+        .map(|(color, uses)| Tag {
+            name: format!("tag{}", uses % 2),
+            color: color.into(),
+            uses,
+        })
+        .collect()
+}
+
+#[derive(Serialize)]
+struct OnlyB {
+    b: u32,
+}
+
+#[derive(Serialize)]
+struct AThenB {
+    a: u32,
+    b: u32,
+}
+
+/// The keys of an object in the order they're read
+struct KeyOrder(Vec<String>);
+
+impl<'de> serde::Deserialize<'de> for KeyOrder {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        struct KeyOrderVisitor;
+
+        impl<'de> serde::de::Visitor<'de> for KeyOrderVisitor {
+            type Value = KeyOrder;
+
+            fn expecting(&self, formatter: &mut std::fmt::Formatter) -> std::fmt::Result {
+                formatter.write_str("an object")
+            }
+
+            fn visit_map<A>(self, mut map: A) -> Result<KeyOrder, A::Error>
+            where
+                A: serde::de::MapAccess<'de>,
+            {
+                let mut keys = vec![];
+                while let Some((key, _)) = map.next_entry::<String, serde::de::IgnoredAny>()? {
+                    keys.push(key);
+                }
+                Ok(KeyOrder(keys))
+            }
+        }
+
+        deserializer.deserialize_map(KeyOrderVisitor)
+    }
+}
+
+fn read_string_table_order(cx: FunctionContext) -> JsResult<JsValue> {
+    fn inner(mut cx: FunctionContext) -> neon_serde::errors::Result<Handle<JsValue>> {
+        let arg0 = cx.argument::<JsValue>(0)?;
+        let options = neon_serde::DeserializeOptions {
+            string_table: true,
+            ..Default::default()
+        };
+        let objects: Vec<KeyOrder> = neon_serde::from_value_with(&mut cx, arg0, &options)?;
+        let keys: Vec<Vec<String>> = objects.into_iter().map(|object| object.0).collect();
+        let handle = neon_serde::to_value(&mut cx, &keys)?;
+        Ok(handle)
+    }
+
+    Ok(inner(cx)?)
+}
+
+#[derive(Serialize)]
+struct Greeting {
+    name: String,
+    greet: String,
+}
+
+fn fill_instance(cx: FunctionContext) -> JsResult<JsValue> {
+    fn inner(mut cx: FunctionContext) -> neon_serde::errors::Result<Handle<JsValue>> {
+        let instance = cx.argument::<JsObject>(0)?;
+        let value = Profile {
+            name: "bob".into(),
+            age: 3,
+        };
+
+        neon_serde::to_instance(&mut cx, &value, instance)?;
+        Ok(instance.upcast())
+    }
+
+    Ok(inner(cx)?)
+}
+
+fn make_rooted(cx: FunctionContext) -> JsResult<JsValue> {
+    fn inner(mut cx: FunctionContext) -> neon_serde::errors::Result<Handle<JsValue>> {
+        let value = Profile {
+            name: "bob".into(),
+            age: 3,
+        };
+
+        let rooted = neon_serde::to_rooted(&mut cx, &value)?;
+        Ok(cx.boxed(rooted).upcast())
+    }
+
+    Ok(inner(cx)?)
+}
+
+#[derive(Serialize)]
+struct Audit {
+    age: u32,
+    checked: bool,
+}
+
+fn make_merged(cx: FunctionContext) -> JsResult<JsValue> {
+    fn inner(mut cx: FunctionContext) -> neon_serde::errors::Result<Handle<JsValue>> {
+        let base = Profile {
+            name: "bob".into(),
+            age: 3,
+        };
+        let extra = Audit {
+            age: 4,
+            checked: true,
+        };
+        let merged = neon_serde::to_value_merged(&mut cx, &base, &extra)?;
+        Ok(merged.upcast())
+    }
+
+    Ok(inner(cx)?)
+}
+
+fn make_merged_array(cx: FunctionContext) -> JsResult<JsValue> {
+    fn inner(mut cx: FunctionContext) -> neon_serde::errors::Result<Handle<JsValue>> {
+        let base = Profile {
+            name: "bob".into(),
+            age: 3,
+        };
+        let merged = neon_serde::to_value_merged(&mut cx, &base, &vec![1])?;
+        Ok(merged.upcast())
+    }
+
+    Ok(inner(cx)?)
+}
+
+fn append_numbers(cx: FunctionContext) -> JsResult<JsValue> {
+    fn inner(mut cx: FunctionContext) -> neon_serde::errors::Result<Handle<JsValue>> {
+        let target = cx.argument::<JsArray>(0)?;
+        let arg1 = cx.argument::<JsValue>(1)?;
+        let start: u32 = neon_serde::from_value(&mut cx, arg1)?;
+        neon_serde::serialize_append(&mut cx, target, &vec![start, start + 1])?;
+        Ok(target.upcast())
+    }
+
+    Ok(inner(cx)?)
+}
+
+fn append_struct(cx: FunctionContext) -> JsResult<JsValue> {
+    fn inner(mut cx: FunctionContext) -> neon_serde::errors::Result<Handle<JsValue>> {
+        let target = cx.argument::<JsArray>(0)?;
+        let value = Profile {
+            name: "bob".into(),
+            age: 3,
+        };
+        neon_serde::serialize_append(&mut cx, target, &value)?;
+        Ok(target.upcast())
+    }
+
+    Ok(inner(cx)?)
+}
+
+fn read_rooted(cx: FunctionContext) -> JsResult<JsValue> {
+    fn inner(mut cx: FunctionContext) -> neon_serde::errors::Result<Handle<JsValue>> {
+        let rooted = cx.argument::<JsBox<neon_serde::RootedValue>>(0)?;
+        rooted.to_inner(&mut cx)
+    }
+
+    Ok(inner(cx)?)
+}
+
+fn expect_rooted(cx: FunctionContext) -> JsResult<JsValue> {
+    fn inner(mut cx: FunctionContext) -> neon_serde::errors::Result<Handle<JsValue>> {
+        let rooted = cx.argument::<JsBox<neon_serde::RootedValue>>(0)?;
+        let value: Profile = neon_serde::from_root(&mut cx, &rooted)?;
+        assert_eq!(
+            value,
+            Profile {
+                name: "bob".into(),
+                age: 3,
+            }
+        );
+        Ok(cx.undefined().upcast())
+    }
+
+    Ok(inner(cx)?)
+}
+
+/// Even numbers below `.0`, from an iterator without an exact length
+struct Evens(u32);
+
+impl serde::Serialize for Evens {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_seq((0..self.0).filter(|i| i % 2 == 0))
+    }
+}
+
+/// Squares of the even numbers below `.0`, keyed by the number
+struct EvenSquares(u32);
+
+impl serde::Serialize for EvenSquares {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_map(
+            (0..self.0)
+                .filter(|i| i % 2 == 0)
+                .map(|i| (i.to_string(), i * i)),
+        )
+    }
+}
+
+#[derive(Serialize)]
+struct Particles {
+    x: Vec<f64>,
+    y: Vec<f64>,
+    vx: Vec<f64>,
+}
+
+#[derive(Serialize)]
+struct Search {
+    q: String,
+    page: u32,
+    exact: bool,
+    ratio: f64,
+    lang: Option<String>,
+}
+
+#[derive(Serialize)]
+struct NestedSearch {
+    q: String,
+    tags: Vec<String>,
+}
+
+fn make_query_params(cx: FunctionContext) -> JsResult<JsValue> {
+    fn inner(mut cx: FunctionContext) -> neon_serde::errors::Result<Handle<JsValue>> {
+        let value = Search {
+            q: "a b&c".into(),
+            page: 2,
+            exact: true,
+            ratio: 0.5,
+            lang: None,
+        };
+        let nested = neon_serde::NestedParamPolicy::Reject;
+
+        let params = neon_serde::to_query_params(&mut cx, &value, nested)?;
+        Ok(params.upcast())
+    }
+
+    Ok(inner(cx)?)
+}
+
+fn make_query_params_nested(cx: FunctionContext) -> JsResult<JsValue> {
+    fn inner(mut cx: FunctionContext) -> neon_serde::errors::Result<Handle<JsValue>> {
+        let json = cx.argument::<JsBoolean>(0)?.value(&mut cx);
+        let value = NestedSearch {
+            q: "x".into(),
+            tags: vec!["a".into(), "b".into()],
+        };
+        let nested = if json {
+            neon_serde::NestedParamPolicy::Json
+        } else {
+            neon_serde::NestedParamPolicy::Reject
+        };
+
+        let params = neon_serde::to_query_params(&mut cx, &value, nested)?;
+        Ok(params.upcast())
+    }
+
+    Ok(inner(cx)?)
+}
+
+fn fill_instance_shadowing(cx: FunctionContext) -> JsResult<JsValue> {
+    fn inner(mut cx: FunctionContext) -> neon_serde::errors::Result<Handle<JsValue>> {
+        let instance = cx.argument::<JsObject>(0)?;
+        let value = Greeting {
+            name: "bob".into(),
+            greet: "hi".into(),
+        };
+
+        neon_serde::to_instance(&mut cx, &value, instance)?;
+        Ok(instance.upcast())
+    }
+
+    Ok(inner(cx)?)
+}
+
+fn make_named_tuple_mismatch(cx: FunctionContext) -> JsResult<JsValue> {
+    fn inner(mut cx: FunctionContext) -> neon_serde::errors::Result<Handle<JsValue>> {
+        let value = (7i32, String::from("seven"));
+
+        let handle = neon_serde::to_value_named_tuple(&mut cx, &value, &["x"])?;
+        Ok(handle)
+    }
+
+    Ok(inner(cx)?)
+}
+
+const NUMBER_BYTES: &'static [u8] = &[255u8, 254, 253];
+
+make_test!(make_buff, { serde_bytes::Bytes::new(NUMBER_BYTES) });
+
+macro_rules! make_expect {
+    ($name:ident, $val:expr, $val_type:ty) => {
+        fn $name(cx: FunctionContext) -> JsResult<JsValue> {
+            fn inner(mut cx: FunctionContext) -> neon_serde::errors::Result<Handle<JsValue>> {
+                let value = $val;
+                let arg0 = cx.argument::<JsValue>(0)?;
+
+                let de_serialized: $val_type = neon_serde::from_value(&mut cx, arg0)?;
+                assert_eq!(value, de_serialized);
+                Ok(JsUndefined::new().upcast())
+            }
+            Ok(inner(cx)?)
+        }
+    };
+}
+
+macro_rules! make_expect_with {
+    ($name:ident, $val:expr, $val_type:ty, $opts:expr) => {
+        fn $name(cx: FunctionContext) -> JsResult<JsValue> {
+            fn inner(mut cx: FunctionContext) -> neon_serde::errors::Result<Handle<JsValue>> {
+                let value = $val;
+                let options = $opts;
+                let arg0 = cx.argument::<JsValue>(0)?;
+
+                let de_serialized: $val_type =
+                    neon_serde::from_value_with(&mut cx, arg0, &options)?;
+                assert_eq!(value, de_serialized);
+                Ok(JsUndefined::new().upcast())
+            }
+            Ok(inner(cx)?)
+        }
+    };
+}
+
+make_expect!(expect_hello_world, "hello world", String);
+make_expect!(expect_reply, reply(), Reply);
+make_expect_with!(
+    expect_extra_tuple_elements,
+    (1, 2),
+    (i32, i32),
+    neon_serde::DeserializeOptions {
+        numeric_key_objects_as_seq: true,
+        ignore_extra_tuple_elements: true,
+        ..Default::default()
+    }
+);
+
+make_expect_with!(
+    expect_extra_variant_elements,
+    TypeEnum::Tuple(1, "a".into()),
+    TypeEnum,
+    neon_serde::DeserializeOptions {
+        numeric_key_objects_as_seq: true,
+        ignore_extra_tuple_elements: true,
+        ..Default::default()
+    }
+);
+
+make_expect_with!(
+    expect_variant_fill_defaults,
+    vec![TypeEnum::Tuple(1, String::new()), TypeEnum::Tuple(0, String::new())],
+    Vec<TypeEnum>,
+    neon_serde::DeserializeOptions {
+        numeric_key_objects_as_seq: true,
+        tuple_fill_defaults: true,
+        ..Default::default()
+    }
+);
+
+make_expect!(
+    expect_map_pairs,
+    vec![("b".to_string(), 2), ("a".to_string(), 1), ("c".to_string(), 3)],
+    Vec<(String, i32)>
+);
+
+make_expect!(
+    expect_option_vecs,
+    vec![Some(vec![1, 2]), None, Some(vec![]), Some(vec![3])],
+    Vec<Option<Vec<i32>>>
+);
+
+make_expect!(
+    expect_obj,
+    AnObjectTwo {
+        a: 1,
+        b: vec![1, 2],
+        c: "abc".into(),
+        d: Some(false),
+        e: None,
+        f: Inner,
+        g: Inner2(9, false, "efg".into()),
+        h: '🤷',
+        i: TypeEnum::Empty,
+        j: TypeEnum::Tuple(27, "hij".into()),
+        k: TypeEnum::Struct {
+            a: 128,
+            b: vec![9, 8, 7],
+        },
+        l: "jkl".into(),
+        m: vec![0, 1, 2, 3, 4],
+        o: TypeEnum::Value(vec!['z', 'y', 'x']),
+        p: vec![1., 2., 3.5],
+        q: 999,
+        r: 333
+    },
+    AnObjectTwo
+);
+
+make_expect!(expect_num_array, vec![0, 1, 2, 3], Vec<i32>);
+
+make_expect!(
+    expect_char_key_map,
+    {
+        use std::collections::HashMap;
+        let mut map = HashMap::new();
+        map.insert('a', 1u32);
+        map.insert('🤷', 2);
+        map
+    },
+    std::collections::HashMap<char, u32>
+);
+
+make_expect!(
+    expect_sparse,
+    Sparse {
+        a: 1,
+        b: Some("b".into()),
+        c: 0,
+        constructor: None,
+    },
+    Sparse
+);
+
+/// The allocations made reading the object as a `Sparse`, whose property names are
+/// matched without allocating, and as a map, which needs a `String` for each name
+fn key_allocations(cx: FunctionContext) -> JsResult<JsValue> {
+    fn inner(mut cx: FunctionContext) -> neon_serde::errors::Result<Handle<JsValue>> {
+        let arg0 = cx.argument::<JsValue>(0)?;
+
+        let before = ALLOCATIONS.load(Ordering::Relaxed);
+        let _: Sparse = neon_serde::from_value(&mut cx, arg0)?;
+        let as_struct = ALLOCATIONS.load(Ordering::Relaxed) - before;
+
+        let before = ALLOCATIONS.load(Ordering::Relaxed);
+        let _: std::collections::HashMap<String, serde::de::IgnoredAny> =
+            neon_serde::from_value(&mut cx, arg0)?;
+        let as_map = ALLOCATIONS.load(Ordering::Relaxed) - before;
+
+        let handle = neon_serde::to_value(&mut cx, &(as_struct, as_map))?;
+        Ok(handle)
+    }
+
+    Ok(inner(cx)?)
+}
+
+make_expect_with!(
+    expect_sparse_lookup,
+    Sparse {
+        a: 1,
+        b: Some("b".into()),
+        c: 0,
+        constructor: None,
+    },
+    Sparse,
+    neon_serde::DeserializeOptions {
+        lookup_struct_fields: true,
+        ..Default::default()
+    }
+);
+
+make_expect_with!(
+    expect_lowercase_variants,
+    vec![Color::Red, Color::LightBlue(3)],
+    Vec<Color>,
+    neon_serde::DeserializeOptions {
+        case_insensitive_variants: true,
+        ..Default::default()
+    }
+);
+
+make_expect_with!(
+    expect_screaming_variants,
+    vec![Color::Red, Color::LightBlue(3), Color::LightBlue(4)],
+    Vec<Color>,
+    neon_serde::DeserializeOptions {
+        case_insensitive_variants: true,
+        screaming_snake_variants: true,
+        ..Default::default()
+    }
+);
+
+make_expect_with!(
+    expect_expected_variants,
+    vec![Color::Red, Color::Red],
+    Vec<Color>,
+    neon_serde::DeserializeOptions {
+        case_insensitive_variants: true,
+        expected_variants: Some(vec!["Red".into()]),
+        ..Default::default()
+    }
+);
+
+make_expect_with!(
+    expect_nan_as_none,
+    (None, Some(1.5), None::<u8>, f64::INFINITY),
+    (Option<f64>, Option<f64>, Option<u8>, f64),
+    neon_serde::DeserializeOptions {
+        nan_as_none: true,
+        ..Default::default()
+    }
+);
+
+make_expect_with!(
+    expect_string_table,
+    tags(),
+    Vec<Tag>,
+    neon_serde::DeserializeOptions {
+        string_table: true,
+        ..Default::default()
+    }
+);
+
+make_expect_with!(
+    expect_undefined_as_default,
+    (vec![1, 0, 3], vec![String::new(), "b".into()], vec![None, Some(2)]),
+    (Vec<i32>, Vec<String>, Vec<Option<u8>>),
+    neon_serde::DeserializeOptions {
+        undefined_as_default: true,
+        ..Default::default()
+    }
+);
+
+make_expect_with!(
+    expect_ambiguous_variant,
+    Cased::Ok,
+    Cased,
+    neon_serde::DeserializeOptions {
+        case_insensitive_variants: true,
+        ..Default::default()
+    }
+);
+
+make_expect!(
+    expect_boxed_slice,
+    vec![1, 2, 3].into_boxed_slice(),
+    Box<[i32]>
+);
+make_expect!(
+    expect_rc_slice,
+    std::rc::Rc::<[String]>::from(vec!["a".to_string(), "b".to_string()]),
+    std::rc::Rc<[String]>
+);
+
+make_expect_with!(
+    expect_null_as_default,
+    Sparse {
+        a: 1,
+        b: None,
+        c: 0,
+        constructor: None,
+    },
+    Sparse,
+    neon_serde::DeserializeOptions {
+        null_as_default: true,
+        ..Default::default()
+    }
+);
+
+make_expect_with!(
+    expect_null_as_default_lookup,
+    Sparse {
+        a: 1,
+        b: None,
+        c: 0,
+        constructor: None,
+    },
+    Sparse,
+    neon_serde::DeserializeOptions {
+        null_as_default: true,
+        lookup_struct_fields: true,
+        ..Default::default()
+    }
+);
+
+make_expect!(
+    expect_empty_tuple_variant,
+    vec![EmptyTuple::Empty(), EmptyTuple::Unit, EmptyTuple::Pair(1, 2)],
+    Vec<EmptyTuple>
+);
+
+make_expect_with!(
+    expect_positional_tuple,
+    ((1, "a".to_string()), Inner2(-2, true, "b".into())),
+    ((i32, String), Inner2),
+    neon_serde::DeserializeOptions {
+        numeric_key_objects_as_seq: true,
+        ..Default::default()
+    }
+);
+
+make_expect_with!(
+    expect_positional_variant,
+    TypeEnum::Tuple(27, "hij".into()),
+    TypeEnum,
+    neon_serde::DeserializeOptions {
+        numeric_key_objects_as_seq: true,
+        ..Default::default()
+    }
+);
+
+make_expect_with!(
+    expect_date_string,
+    Stamped {
+        at: "2020-01-02T03:04:05.678Z".into()
+    },
+    Stamped,
+    neon_serde::DeserializeOptions {
+        date_to_iso_string: true,
+        ..Default::default()
+    }
+);
+
+make_expect_with!(
+    expect_date_millis,
+    (1577934245678i64, 1577934245678u64, 1577934245678f64),
+    (i64, u64, f64),
+    neon_serde::DeserializeOptions {
+        dates_as_millis: true,
+        ..Default::default()
+    }
+);
+
+#[derive(Deserialize, Debug, PartialEq)]
+struct Envelope {
+    id: u32,
+    #[serde(default)]
+    name: String,
+    extra: std::collections::HashMap<String, serde_json::Value>,
+}
+
+fn envelope() -> Envelope {
+    Envelope {
+        id: 1,
+        name: "a".into(),
+        extra: [
+            ("b".to_string(), serde_json::json!(2)),
+            ("c".to_string(), serde_json::json!([true])),
+        ]
+        .into_iter()
+        .collect(),
+    }
+}
+
+make_expect_with!(
+    expect_captured,
+    envelope(),
+    Envelope,
+    neon_serde::DeserializeOptions {
+        capture_unknown_into: Some("extra"),
+        ..Default::default()
+    }
+);
+
+#[derive(Deserialize, Debug, PartialEq)]
+struct Owner {
+    name: String,
+}
+
+#[derive(Deserialize, Debug, PartialEq)]
+struct Mailbag {
+    owner: Owner,
+    envelopes: Vec<Envelope>,
+    rest: std::collections::HashMap<String, serde_json::Value>,
+}
+
+make_expect_with!(
+    expect_captured_nested,
+    Mailbag {
+        owner: Owner { name: "b".into() },
+        envelopes: vec![envelope()],
+        rest: [("note".to_string(), serde_json::json!("hi"))]
+            .into_iter()
+            .collect(),
+    },
+    Mailbag,
+    neon_serde::DeserializeOptions {
+        capture_unknown_into: Some("rest"),
+        ..Default::default()
+    }
+);
+
+make_expect_with!(
+    expect_captured_into_string,
+    envelope(),
+    Envelope,
+    neon_serde::DeserializeOptions {
+        capture_unknown_into: Some("name"),
+        ..Default::default()
+    }
+);
+
+make_expect_with!(
+    expect_captured_without_field,
+    envelope(),
+    Envelope,
+    neon_serde::DeserializeOptions {
+        capture_unknown_into: Some("rest"),
+        ..Default::default()
+    }
+);
+
+make_expect_with!(
+    expect_reinterpreted,
+    vec![1.5f64, 2.5],
+    Vec<f64>,
+    neon_serde::DeserializeOptions {
+        reinterpret_buffers: true,
+        ..Default::default()
+    }
+);
+
+make_expect_with!(
+    expect_string_chars,
+    vec!['a', 'b', 'c', '😀'],
+    Vec<char>,
+    neon_serde::DeserializeOptions {
+        string_as_chars: true,
+        ..Default::default()
+    }
+);
+
+make_expect!(
+    expect_buffer_json,
+    serde_json::Value::from(vec![1u8, 2, 255]),
+    serde_json::Value
+);
+
+make_expect_with!(
+    expect_buffer_base64,
+    serde_json::Value::from("AQL/"),
+    serde_json::Value,
+    neon_serde::DeserializeOptions {
+        buffers_as_base64: true,
+        ..Default::default()
+    }
+);
+
+make_expect_with!(
+    expect_bigint_string,
+    Balance {
+        amount: "123456789012345678901234567890".into()
+    },
+    Balance,
+    neon_serde::DeserializeOptions {
+        bigint_as_string: true,
+        ..Default::default()
+    }
+);
+
+make_expect_with!(
+    expect_tuple_fill_defaults,
+    ((5i32, 0i32), (1u8, String::new(), None::<bool>, Vec::<u8>::new())),
+    ((i32, i32), (u8, String, Option<bool>, Vec<u8>)),
+    neon_serde::DeserializeOptions {
+        tuple_fill_defaults: true,
+        ..Default::default()
+    }
+);
+
+make_expect_with!(
+    expect_tuple_fill_enum,
+    (1i32, Color::Red),
+    (i32, Color),
+    neon_serde::DeserializeOptions {
+        tuple_fill_defaults: true,
+        ..Default::default()
+    }
+);
+
+make_expect_with!(
+    expect_all_fields,
+    Sparse {
+        a: 1,
+        b: None,
+        c: 0,
+        constructor: Some(2),
+    },
+    Sparse,
+    neon_serde::DeserializeOptions {
+        require_all_fields: true,
+        ..Default::default()
+    }
+);
+
+make_expect_with!(
+    expect_all_fields_aliased,
+    AliasedFields {
+        name: Some("ann".into()),
+        age: 3,
+    },
+    AliasedFields,
+    neon_serde::DeserializeOptions {
+        require_all_fields: true,
+        ..Default::default()
+    }
+);
+
+make_expect_with!(
+    expect_numeric_strings,
+    vec![3.5f64, -2.0, 1000.0],
+    Vec<f64>,
+    neon_serde::DeserializeOptions {
+        coerce_numeric_strings: true,
+        ..Default::default()
+    }
+);
+
+make_expect_with!(
+    expect_decimal_comma,
+    vec![3.14f64, 2.5, 1000.0],
+    Vec<f64>,
+    neon_serde::DeserializeOptions {
+        coerce_numeric_strings: true,
+        decimal_comma: true,
+        ..Default::default()
+    }
+);
+
+make_expect!(
+    expect_nonzero,
+    (
+        std::num::NonZeroU32::new(7).unwrap(),
+        std::num::NonZeroU64::new((1 << 53) - 1).unwrap(),
+        std::num::NonZeroI64::new(-(1 << 53) + 1).unwrap(),
+    ),
+    (
+        std::num::NonZeroU32,
+        std::num::NonZeroU64,
+        std::num::NonZeroI64
+    )
+);
+
+make_expect_with!(
+    expect_empty_object_unit,
+    ((), Inner, Empty {}),
+    ((), Inner, Empty),
+    neon_serde::DeserializeOptions {
+        empty_object_as_unit: true,
+        ..Default::default()
+    }
+);
+
+make_expect!(expect_unit, (), ());
+
+make_expect!(
+    expect_safe_integer_bounds,
+    (
+        (1i64 << 53) - 1,
+        (1u64 << 53) - 1,
+        -(1i64 << 53) + 1,
+        1i64 << 53,
+        1i64 << 53
+    ),
+    (i64, u64, i64, i64, i64)
+);
+
+make_expect!(
+    expect_int_widths,
+    (-128i8, -32768i16, i32::MIN, -5i64, -7i128, 255u8, 65535u16, u32::MAX, 7u64, 9u128),
+    (i8, i16, i32, i64, i128, u8, u16, u32, u64, u128)
+);
+
+make_expect!(
+    expect_bigint_extremes,
+    (i64::MIN, i64::MAX, u64::MAX, i128::MIN, i128::MAX, u128::MAX),
+    (i64, i64, u64, i128, i128, u128)
+);
+
+make_expect!(
+    expect_aliased,
+    Aliased {
+        name: "bob".into()
+    },
+    Aliased
+);
+
+make_expect_with!(
+    expect_aliased_lookup,
+    Aliased {
+        name: "bob".into()
+    },
+    Aliased,
+    neon_serde::DeserializeOptions {
+        lookup_struct_fields: true,
+        ..Default::default()
+    }
+);
+
+make_expect!(expect_nested_none, None, Option<Option<Option<u32>>>);
+make_expect!(
+    expect_nested_some,
+    Some(Some(Some(5))),
+    Option<Option<Option<u32>>>
+);
+
+make_expect_with!(
+    expect_type_value_enums,
+    vec![
+        TypeEnum::Empty,
+        TypeEnum::Tuple(27, "hij".into()),
+        TypeEnum::Struct {
+            a: 128,
+            b: vec![9, 8, 7],
+        },
+        TypeEnum::Value(vec!['z', 'y']),
+    ],
+    Vec<TypeEnum>,
+    neon_serde::DeserializeOptions {
+        enum_style: neon_serde::EnumStyle::TypeValue,
+        ..Default::default()
+    }
+);
+
+make_expect!(expect_rounded_f32, 0.1f32, f32);
+make_expect_with!(
+    expect_strict_f32,
+    0.5f32,
+    f32,
+    neon_serde::DeserializeOptions {
+        strict_f32: true,
+        ..Default::default()
+    }
+);
+make_expect_with!(
+    expect_strict_f32_string,
+    0.5f32,
+    f32,
+    neon_serde::DeserializeOptions {
+        strict_f32: true,
+        coerce_numeric_strings: true,
+        ..Default::default()
+    }
+);
+
+make_expect_with!(
+    expect_pair_map,
+    {
+        use std::collections::HashMap;
+        let mut map = HashMap::new();
+        map.insert("a".to_string(), 1);
+        map.insert("b".to_string(), 2);
+        map
+    },
+    std::collections::HashMap<String, i32>,
+    neon_serde::DeserializeOptions {
+        pair_arrays_as_maps: true,
+        ..Default::default()
+    }
+);
+
+#[cfg(feature = "uuid")]
+make_expect!(expect_uuid, uuid::Uuid::parse_str(UUID).unwrap(), uuid::Uuid);
+#[cfg(feature = "uuid")]
+make_expect_with!(
+    expect_uuid_compact,
+    uuid::Uuid::parse_str(UUID).unwrap(),
+    uuid::Uuid,
+    neon_serde::DeserializeOptions {
+        human_readable: false,
+        ..Default::default()
+    }
+);
+
+#[cfg(feature = "smallvec")]
+make_expect!(
+    expect_smallvec,
+    (
+        smallvec::SmallVec::<[u8; 4]>::from_slice(&[1, 2]),
+        smallvec::SmallVec::<[u8; 4]>::from_slice(&[1, 2, 3, 4, 5, 6])
+    ),
+    (smallvec::SmallVec<[u8; 4]>, smallvec::SmallVec<[u8; 4]>)
+);
+
+#[cfg(feature = "arrayvec")]
+make_expect!(
+    expect_arrayvec,
+    arrayvec::ArrayVec::<u8, 4>::from_iter([1, 2, 3]),
+    arrayvec::ArrayVec<u8, 4>
+);
+
+#[cfg(feature = "indexmap")]
+fn read_key_order(cx: FunctionContext) -> JsResult<JsValue> {
+    fn inner(mut cx: FunctionContext) -> neon_serde::errors::Result<Handle<JsValue>> {
+        let arg0 = cx.argument::<JsValue>(0)?;
+        let map: indexmap::IndexMap<String, u32> = neon_serde::from_value(&mut cx, arg0)?;
+        let keys: Vec<&String> = map.keys().collect();
+        let handle = neon_serde::to_value(&mut cx, &keys)?;
+        Ok(handle)
+    }
+
+    Ok(inner(cx)?)
+}
+
+make_expect!(
+    expect_proto_map,
+    {
+        use std::collections::HashMap;
+        let mut map = HashMap::new();
+        map.insert("__proto__".to_string(), 1);
+        map.insert("constructor".to_string(), 2);
+        map.insert("a".to_string(), 3);
+        map
+    },
+    std::collections::HashMap<String, i32>
+);
+
+make_expect_with!(
+    expect_num_array_all_errors,
+    vec![0, 1, 2, 3],
+    Vec<i32>,
+    neon_serde::DeserializeOptions {
+        fail_fast: false,
+        ..Default::default()
+    }
+);
+
+make_expect_with!(
+    expect_hello_world_friendly,
+    "hello world",
+    String,
+    neon_serde::DeserializeOptions {
+        friendly_errors: true,
+        ..Default::default()
+    }
+);
+
+make_expect_with!(
+    expect_num_iterable,
+    vec![0, 1, 2, 3],
+    Vec<i32>,
+    neon_serde::DeserializeOptions {
+        accept_iterables: true,
+        ..Default::default()
+    }
+);
+
+make_expect!(
+    expect_buffer,
+    serde_bytes::ByteBuf::from(vec![252u8, 251, 250]),
+    serde_bytes::ByteBuf
+);
+
+macro_rules! make_expect_scalar {
+    ($name:ident, $val:expr, $val_type:ty) => {
+        fn $name(cx: FunctionContext) -> JsResult<JsValue> {
+            fn inner(mut cx: FunctionContext) -> neon_serde::errors::Result<Handle<JsValue>> {
+                let value = $val;
+                let arg0 = cx.argument::<JsValue>(0)?;
+
+                let de_serialized: $val_type = neon_serde::from_value_scalar(&mut cx, arg0)?;
+                assert_eq!(value, de_serialized);
+                Ok(JsUndefined::new().upcast())
+            }
+            Ok(inner(cx)?)
+        }
+    };
+}
+
+make_expect_scalar!(expect_scalar_bool, true, bool);
+make_expect_scalar!(expect_scalar_string, String::from("hello"), String);
+make_expect_scalar!(expect_scalar_f64, 1.5f64, f64);
+make_expect_scalar!(expect_scalar_f32, 1.5f32, f32);
+make_expect_scalar!(expect_scalar_i8, -128i8, i8);
+make_expect_scalar!(expect_scalar_i16, -32768i16, i16);
+make_expect_scalar!(expect_scalar_i32, -2147483648i32, i32);
+make_expect_scalar!(expect_scalar_i64, -9007199254740991i64, i64);
+make_expect_scalar!(expect_scalar_u8, 255u8, u8);
+make_expect_scalar!(expect_scalar_u16, 65535u16, u16);
+make_expect_scalar!(expect_scalar_u32, 4294967295u32, u32);
+make_expect_scalar!(expect_scalar_u64, 9007199254740991u64, u64);
+
+fn wide_records(len: u32) -> Vec<Wide> {
+    (0..len)
+        .map(|i| Wide {
+            f0: i.to_string(),
+            f1: i,
+            f2: i,
+            f3: i.to_string(),
+            f4: i,
+            f5: i,
+            f6: i.to_string(),
+            f7: i,
+            f8: i,
+            f9: i.to_string(),
+            f10: i,
+            f11: i,
+            f12: i.to_string(),
+            f13: i,
+            f14: i,
+            f15: i.to_string(),
+            f16: i,
+            f17: i,
+            f18: i.to_string(),
+            f19: i,
+            f20: i,
+            f21: i.to_string(),
+            f22: i,
+            f23: i,
+        })
+        .collect()
+}
+
+fn make_wide_structs(cx: FunctionContext) -> JsResult<JsValue> {
+    fn inner(mut cx: FunctionContext) -> neon_serde::errors::Result<Handle<JsValue>> {
+        let arg0 = cx.argument::<JsValue>(0)?;
+        let len: u32 = neon_serde::from_value(&mut cx, arg0)?;
+        let values = wide_records(len);
+        neon_serde::to_value(&mut cx, &values)
+    }
+
+    Ok(inner(cx)?)
+}
+
+const WIDE_FIELDS: [&str; 24] = [
+    "f0", "f1", "f2", "f3", "f4", "f5", "f6", "f7", "f8", "f9", "f10", "f11", "f12", "f13", "f14",
+    "f15", "f16", "f17", "f18", "f19", "f20", "f21", "f22", "f23",
+];
+
+fn make_wide_structs_each(cx: FunctionContext) -> JsResult<JsValue> {
+    fn inner(mut cx: FunctionContext) -> neon_serde::errors::Result<Handle<JsValue>> {
+        let arg0 = cx.argument::<JsValue>(0)?;
+        let len: u32 = neon_serde::from_value(&mut cx, arg0)?;
+        let values = wide_records(len);
+        let array = JsArray::new(&mut cx, values.len());
+        for (i, value) in values.iter().enumerate() {
+            let value = neon_serde::to_value(&mut cx, value)?;
+            array.set(&mut cx, i as u32, value)?;
+        }
+        Ok(array.upcast())
+    }
+
+    Ok(inner(cx)?)
+}
+
+fn make_wide_structs_planned(cx: FunctionContext) -> JsResult<JsValue> {
+    fn inner(mut cx: FunctionContext) -> neon_serde::errors::Result<Handle<JsValue>> {
+        let arg0 = cx.argument::<JsValue>(0)?;
+        let len: u32 = neon_serde::from_value(&mut cx, arg0)?;
+        let values = wide_records(len);
+        let plan = neon_serde::SerializePlan::for_struct(&mut cx, &WIDE_FIELDS);
+        let options = neon_serde::SerializeOptions::default();
+        let array = JsArray::new(&mut cx, values.len());
+        for (i, value) in values.iter().enumerate() {
+            let value = neon_serde::to_value_with_plan(&mut cx, &plan, value, &options)?;
+            array.set(&mut cx, i as u32, value)?;
+        }
+        plan.drop(&mut cx);
+        Ok(array.upcast())
+    }
+
+    Ok(inner(cx)?)
+}
+
+fn make_record_batch(cx: FunctionContext) -> JsResult<JsValue> {
+    fn inner(mut cx: FunctionContext) -> neon_serde::errors::Result<Handle<JsValue>> {
+        let arg0 = cx.argument::<JsValue>(0)?;
+        let len: u32 = neon_serde::from_value(&mut cx, arg0)?;
+        let values = wide_records(len);
+        let batch = neon_serde::to_record_batch(&mut cx, &values)?;
+        Ok(batch.upcast())
+    }
+
+    Ok(inner(cx)?)
+}
+
+fn make_f64s(cx: FunctionContext) -> JsResult<JsValue> {
+    fn inner(mut cx: FunctionContext) -> neon_serde::errors::Result<Handle<JsValue>> {
+        let arg0 = cx.argument::<JsValue>(0)?;
+        let len: u32 = neon_serde::from_value(&mut cx, arg0)?;
+        let values: Vec<f64> = (0..len).map(|i| f64::from(i) / 2.0).collect();
+        neon_serde::to_value(&mut cx, &values)
+    }
+
+    Ok(inner(cx)?)
+}
+
+fn make_f64s_typed(cx: FunctionContext) -> JsResult<JsValue> {
+    fn inner(mut cx: FunctionContext) -> neon_serde::errors::Result<Handle<JsValue>> {
+        let arg0 = cx.argument::<JsValue>(0)?;
+        let len: u32 = neon_serde::from_value(&mut cx, arg0)?;
+        let values: Vec<f64> = (0..len).map(|i| f64::from(i) / 2.0).collect();
+        let options = neon_serde::SerializeOptions {
+            typed_arrays: true,
+            ..Default::default()
+        };
+        neon_serde::to_value_with(&mut cx, &values, &options)
+    }
+
+    Ok(inner(cx)?)
+}
+
+fn sum_from_value(cx: FunctionContext) -> JsResult<JsValue> {
+    fn inner(mut cx: FunctionContext) -> neon_serde::errors::Result<Handle<JsValue>> {
+        let arg0 = cx.argument::<JsArray>(0)?;
+        let mut sum = 0i64;
+        for value in arg0.to_vec(&mut cx)? {
+            sum += neon_serde::from_value::<_, i64>(&mut cx, value)?;
+        }
+        Ok(cx.number(sum as f64).upcast())
+    }
+
+    Ok(inner(cx)?)
+}
+
+fn sum_from_value_scalar(cx: FunctionContext) -> JsResult<JsValue> {
+    fn inner(mut cx: FunctionContext) -> neon_serde::errors::Result<Handle<JsValue>> {
+        let arg0 = cx.argument::<JsArray>(0)?;
+        let mut sum = 0i64;
+        for value in arg0.to_vec(&mut cx)? {
+            sum += neon_serde::from_value_scalar::<_, i64>(&mut cx, value)?;
+        }
+        Ok(cx.number(sum as f64).upcast())
+    }
+
+    Ok(inner(cx)?)
+}
+
+fn sum_reused_deserializer(cx: FunctionContext) -> JsResult<JsValue> {
+    fn inner(mut cx: FunctionContext) -> neon_serde::errors::Result<Handle<JsValue>> {
+        let arg0 = cx.argument::<JsArray>(0)?;
+        let values = arg0.to_vec(&mut cx)?;
+        let options = neon_serde::DeserializeOptions::default();
+        let mut de = neon_serde::ReusableDeserializer::new(&mut cx, &options);
+        let mut sum = 0i64;
+        for value in values {
+            de.reset(value);
+            sum += de.deserialize::<i64>()?;
+        }
+        Ok(cx.number(sum as f64).upcast())
+    }
+
+    Ok(inner(cx)?)
+}
+
+fn expect_presized_strings(cx: FunctionContext) -> JsResult<JsValue> {
+    fn inner(mut cx: FunctionContext) -> neon_serde::errors::Result<Handle<JsValue>> {
+        let arg0 = cx.argument::<JsValue>(0)?;
+        let strings: Vec<String> = neon_serde::from_value(&mut cx, arg0)?;
+        assert_eq!(strings.capacity(), strings.len());
+        for (i, s) in strings.iter().enumerate() {
+            assert_eq!(s, &format!("string {}", i));
+        }
+        Ok(cx.number(strings.len() as f64).upcast())
+    }
+
+    Ok(inner(cx)?)
+}
+
+fn string_array_len(cx: FunctionContext) -> JsResult<JsValue> {
+    fn inner(mut cx: FunctionContext) -> neon_serde::errors::Result<Handle<JsValue>> {
+        let arg0 = cx.argument::<JsValue>(0)?;
+        let strings: Vec<String> = neon_serde::from_value(&mut cx, arg0)?;
+        Ok(cx.number(strings.len() as f64).upcast())
+    }
+
+    Ok(inner(cx)?)
+}
+
+fn expect_custom_message(mut cx: FunctionContext) -> JsResult<JsValue> {
+    let arg0 = cx.argument::<JsValue>(0)?;
+    let result: neon_serde::errors::Result<u32> = neon_serde::from_value(&mut cx, arg0);
+    let value = result.or_throw_with(&mut cx, |e| format!("Expected a count: {}", e))?;
+    Ok(cx.number(value).upcast())
+}
+
+fn json_number_kind(cx: FunctionContext) -> JsResult<JsValue> {
+    fn inner(mut cx: FunctionContext) -> neon_serde::errors::Result<Handle<JsValue>> {
+        let arg0 = cx.argument::<JsValue>(0)?;
+        let value: serde_json::Value = neon_serde::from_value(&mut cx, arg0)?;
+        let number = match value {
+            serde_json::Value::Number(number) => number,
+            other => panic!("expected a number, got {:?}", other),
+        };
+        let kind = if number.is_i64() {
+            "i64"
+        } else if number.is_u64() {
+            "u64"
+        } else {
+            "f64"
+        };
+        Ok(cx.string(kind).upcast())
+    }
+
+    Ok(inner(cx)?)
+}
+
+#[derive(Serialize, Deserialize, Debug, PartialEq)]
+enum Light {
+    Red,
+    Amber,
+    Green,
+}
+
+#[derive(Serialize, Deserialize, Debug, PartialEq)]
+struct Label {
+    name: String,
+}
+
+#[derive(Serialize, Deserialize, Debug, PartialEq)]
+#[serde(deny_unknown_fields)]
+enum Event {
+    Ping,
+    Move { x: i32, y: i32 },
+    Named(Label),
+    Pair(u8, u8),
+}
+
+const KIND_TAG: neon_serde::EnumStyle = neon_serde::EnumStyle::InternallyTagged { tag: "kind" };
+
+fn roundtrip_internally_tagged(cx: FunctionContext) -> JsResult<JsValue> {
+    fn inner(mut cx: FunctionContext) -> neon_serde::errors::Result<Handle<JsValue>> {
+        let value = vec![
+            Event::Ping,
+            Event::Move { x: 1, y: -2 },
+            Event::Named(Label { name: "a".into() }),
+        ];
+        let ser_opts = neon_serde::SerializeOptions {
+            enum_style: KIND_TAG,
+            ..Default::default()
+        };
+        let de_opts = neon_serde::DeserializeOptions {
+            enum_style: KIND_TAG,
+            ..Default::default()
+        };
+
+        let handle = neon_serde::to_value_with(&mut cx, &value, &ser_opts)?;
+        let back: Vec<Event> = neon_serde::from_value_with(&mut cx, handle, &de_opts)?;
+        assert_eq!(value, back);
+        Ok(handle)
+    }
+
+    Ok(inner(cx)?)
+}
+
+fn make_tagged_tuple(cx: FunctionContext) -> JsResult<JsValue> {
+    fn inner(mut cx: FunctionContext) -> neon_serde::errors::Result<Handle<JsValue>> {
+        let options = neon_serde::SerializeOptions {
+            enum_style: KIND_TAG,
+            ..Default::default()
+        };
+
+        let handle = neon_serde::to_value_with(&mut cx, &Event::Pair(1, 2), &options)?;
+        Ok(handle)
+    }
+
+    Ok(inner(cx)?)
+}
+
+make_expect_with!(
+    expect_internally_tagged,
+    vec![Event::Move { x: 1, y: -2 }, Event::Ping],
+    Vec<Event>,
+    neon_serde::DeserializeOptions {
+        enum_style: KIND_TAG,
+        ..Default::default()
+    }
+);
+
+fn roundtrip_type_value_tuples(cx: FunctionContext) -> JsResult<JsValue> {
+    fn inner(mut cx: FunctionContext) -> neon_serde::errors::Result<Handle<JsValue>> {
+        let value = (
+            vec![EmptyTuple::Empty(), EmptyTuple::Pair(1, 2)],
+            TypeEnum::Tuple(3, "x".into()),
+        );
+        let ser_opts = neon_serde::SerializeOptions {
+            enum_style: neon_serde::EnumStyle::TypeValue,
+            ..Default::default()
+        };
+        let de_opts = neon_serde::DeserializeOptions {
+            enum_style: neon_serde::EnumStyle::TypeValue,
+            ..Default::default()
+        };
+        let handle = neon_serde::to_value_with(&mut cx, &value, &ser_opts)?;
+        let back: (Vec<EmptyTuple>, TypeEnum) = neon_serde::from_value_with(&mut cx, handle, &de_opts)?;
+        assert_eq!(value, back);
+        Ok(handle)
+    }
+
+    Ok(inner(cx)?)
+}
+
+fn roundtrip_unit_variants(cx: FunctionContext) -> JsResult<JsValue> {
+    fn inner(mut cx: FunctionContext) -> neon_serde::errors::Result<Handle<JsValue>> {
+        let value = vec![Light::Red, Light::Green, Light::Red, Light::Amber];
+        let shapes = JsArray::new(&mut cx, 2);
+        for (i, enum_style) in [neon_serde::EnumStyle::External, neon_serde::EnumStyle::TypeValue]
+            .into_iter()
+            .enumerate()
+        {
+            let ser_opts = neon_serde::SerializeOptions {
+                enum_style,
+                ..Default::default()
+            };
+            let de_opts = neon_serde::DeserializeOptions {
+                enum_style,
+                ..Default::default()
+            };
+            let handle = neon_serde::to_value_with(&mut cx, &value, &ser_opts)?;
+            let back: Vec<Light> = neon_serde::from_value_with(&mut cx, handle, &de_opts)?;
+            assert_eq!(value, back);
+            shapes.set(&mut cx, i as u32, handle)?;
+        }
+        Ok(shapes.upcast())
+    }
+
+    Ok(inner(cx)?)
+}
+
+fn roundtrip_patch(mut cx: FunctionContext) -> JsResult<JsValue> {
+    let arg0 = cx.argument::<JsValue>(0)?;
+
+    let patch: std::collections::HashMap<String, Option<serde_json::Value>> =
+        neon_serde::from_value_patch(&mut cx, arg0)?;
+    let sorted: std::collections::BTreeMap<_, _> = patch.into_iter().collect();
+    let handle = neon_serde::to_value(&mut cx, &sorted)?;
+    Ok(handle)
+}
+
+fn scan_until_negative(mut cx: FunctionContext) -> JsResult<JsValue> {
+    let arg0 = cx.argument::<JsValue>(0)?;
+
+    let prefix: Vec<i32> = neon_serde::from_value_seq_until(&mut cx, arg0, |n: &i32| *n < 0)?;
+    let handle = neon_serde::to_value(&mut cx, &prefix)?;
+    Ok(handle)
+}
+
+trait Shape {
+    fn area(&self) -> f64;
+}
+
+#[derive(Deserialize)]
+struct Circle {
+    r: f64,
+}
+
+impl Shape for Circle {
+    fn area(&self) -> f64 {
+        std::f64::consts::PI * self.r * self.r
+    }
+}
+
+#[derive(Deserialize)]
+struct Square {
+    side: f64,
+}
+
+impl Shape for Square {
+    fn area(&self) -> f64 {
+        self.side * self.side
+    }
+}
+
+fn dynamic_shape_area(cx: FunctionContext) -> JsResult<JsValue> {
+    fn inner(mut cx: FunctionContext) -> neon_serde::errors::Result<Handle<JsValue>> {
+        let arg0 = cx.argument::<JsValue>(0)?;
+        let mut registry: std::collections::HashMap<
+            String,
+            neon_serde::DynamicDeserializer<_, dyn Shape>,
+        > = std::collections::HashMap::new();
+        registry.insert("circle".into(), |cx, value| {
+            Ok(Box::new(neon_serde::from_value::<_, Circle>(cx, value)?))
+        });
+        registry.insert("square".into(), |cx, value| {
+            Ok(Box::new(neon_serde::from_value::<_, Square>(cx, value)?))
+        });
+
+        let shape = neon_serde::from_value_dynamic(&mut cx, arg0, &registry)?;
+        Ok(cx.number(shape.area()).upcast())
+    }
+
+    Ok(inner(cx)?)
+}
+
+fn roundtrip_object(mut cx: FunctionContext) -> JsResult<JsValue> {
+    let arg0 = cx.argument::<JsValue>(0)?;
+
+    let de_serialized: AnObjectTwo = neon_serde::from_value(&mut cx, arg0)?;
+    let handle = neon_serde::to_value(&mut cx, &de_serialized)?;
+    Ok(handle)
+}
+
+static DUMPS: std::sync::Mutex<Vec<String>> = std::sync::Mutex::new(Vec::new());
+
+fn record_dump(dump: &str) {
+    DUMPS.lock().unwrap().push(dump.into());
+}
+
+fn dump_failure(cx: FunctionContext) -> JsResult<JsValue> {
+    fn inner(mut cx: FunctionContext) -> neon_serde::errors::Result<Handle<JsValue>> {
+        let arg0 = cx.argument::<JsValue>(0)?;
+        let options = neon_serde::DeserializeOptions {
+            on_error_dump: Some(record_dump),
+            ..Default::default()
+        };
+
+        let result: neon_serde::errors::Result<Vec<AnObject>> =
+            neon_serde::from_value_with(&mut cx, arg0, &options);
+        assert!(result.is_err());
+        let dumps = std::mem::take(&mut *DUMPS.lock().unwrap());
+        let handle = neon_serde::to_value(&mut cx, &dumps)?;
+        Ok(handle)
+    }
+
+    Ok(inner(cx)?)
+}
+
+fn parse_port(mut cx: FunctionContext) -> JsResult<JsValue> {
+    let port = cx.argument::<JsString>(0)?.value(&mut cx);
+    let port: Result<u16, _> = port.parse();
+    neon_serde::to_value_or_throw(&mut cx, &port)
+}
+
+#[derive(Serialize, Deserialize, Debug, PartialEq)]
+struct Attachment {
+    name: String,
+    #[serde(default, with = "serde_bytes")]
+    data: Option<Vec<u8>>,
+}
+
+fn roundtrip_optional_bytes(mut cx: FunctionContext) -> JsResult<JsValue> {
+    let arg0 = cx.argument::<JsValue>(0)?;
+
+    let de_serialized: Vec<Attachment> = neon_serde::from_value(&mut cx, arg0)?;
+    let handle = neon_serde::to_value(&mut cx, &de_serialized)?;
+    Ok(handle)
+}
+
+fn roundtrip_nested_option(mut cx: FunctionContext) -> JsResult<JsValue> {
+    let arg0 = cx.argument::<JsValue>(0)?;
+
+    let de_serialized: Vec<Option<Option<Option<Option<String>>>>> =
+        neon_serde::from_value(&mut cx, arg0)?;
+    let handle = neon_serde::to_value(&mut cx, &de_serialized)?;
+    Ok(handle)
+}
+
+register_module!(mut m, {
+    m.export_function("make_num_77", make_num_77)?;
+    m.export_function("make_num_32", make_num_32)?;
+    m.export_function("make_str_hello", make_str_hello)?;
+    m.export_function("make_num_array", make_num_array)?;
+    m.export_function("make_buff", make_buff)?;
+    m.export_function("make_obj", make_obj)?;
+    m.export_function("make_object", make_object)?;
+    m.export_function("make_map", make_map)?;
+    m.export_function("make_obj_null_proto", make_obj_null_proto)?;
+    m.export_function("make_sorted_keys", make_sorted_keys)?;
+    m.export_function("make_sorted_map_keys", make_sorted_map_keys)?;
+    m.export_function("make_json_map", make_json_map)?;
+    m.export_function("make_option_vecs", make_option_vecs)?;
+    m.export_function("make_collected", make_collected)?;
+    m.export_function("make_empty_as_null", make_empty_as_null)?;
+    m.export_function("make_no_fields", make_no_fields)?;
+    m.export_function("make_no_fields_null", make_no_fields_null)?;
+    m.export_function("make_string_table", make_string_table)?;
+    m.export_function("make_string_table_order", make_string_table_order)?;
+    m.export_function("read_string_table_order", read_string_table_order)?;
+    m.export_function("make_typed_arrays", make_typed_arrays)?;
+    m.export_function("make_particles", make_particles)?;
+    m.export_function("make_validated_strings", make_validated_strings)?;
+    m.export_function("make_bytes_with_length", make_bytes_with_length)?;
+    m.export_function("make_map_none_null", make_map_none_null)?;
+    m.export_function("make_map_none_omit", make_map_none_omit)?;
+    m.export_function("make_map_null_values_omit", make_map_null_values_omit)?;
+    m.export_function("make_json_null_omit", make_json_null_omit)?;
+    m.export_function("make_optional_bytes_omit", make_optional_bytes_omit)?;
+    m.export_function("make_truncated", make_truncated)?;
+    m.export_function("make_too_deep", make_too_deep)?;
+    m.export_function("make_with_metadata", make_with_metadata)?;
+    m.export_function("make_char_vec_string", make_char_vec_string)?;
+    m.export_function("make_char_vec_string_typed", make_char_vec_string_typed)?;
+    m.export_function("make_char_vec", make_char_vec)?;
+    m.export_function("make_reply", make_reply)?;
+    m.export_function("expect_reply", expect_reply)?;
+    m.export_function("expect_extra_tuple_elements", expect_extra_tuple_elements)?;
+    m.export_function("expect_extra_variant_elements", expect_extra_variant_elements)?;
+    m.export_function("expect_variant_fill_defaults", expect_variant_fill_defaults)?;
+    m.export_function("make_boxed_numbers", make_boxed_numbers)?;
+    m.export_function("make_float_decimals", make_float_decimals)?;
+    m.export_function("make_json_number_grammar", make_json_number_grammar)?;
+    m.export_function("make_infinities", make_infinities)?;
+    m.export_function("make_infinities_typed", make_infinities_typed)?;
+    m.export_function("make_all_ints_as_bigint", make_all_ints_as_bigint)?;
+    m.export_function("make_large_floats_bigint", make_large_floats_bigint)?;
+    m.export_function("make_js_maps", make_js_maps)?;
+    m.export_function("make_transformed", make_transformed)?;
+    m.export_function("make_tuple_bytes", make_tuple_bytes)?;
+    m.export_function("make_non_ascii_keys_allow", make_non_ascii_keys_allow)?;
+    m.export_function("make_non_ascii_keys_reject", make_non_ascii_keys_reject)?;
+    m.export_function("make_non_ascii_keys_escape", make_non_ascii_keys_escape)?;
+    m.export_function("make_flat_newtype_variants", make_flat_newtype_variants)?;
+    m.export_function("make_odd_keys", make_odd_keys)?;
+    m.export_function("make_odd_keys_null_proto", make_odd_keys_null_proto)?;
+    m.export_function("make_empty_tuple_variant", make_empty_tuple_variant)?;
+    m.export_function("make_type_value_enums", make_type_value_enums)?;
+    m.export_function("make_boxed_slice", make_boxed_slice)?;
+    m.export_function("make_rc_slice", make_rc_slice)?;
+    m.export_function("make_path", make_path)?;
+    m.export_function("make_path_forward_slash", make_path_forward_slash)?;
+    m.export_function("make_char_key_map", make_char_key_map)?;
+    m.export_function("make_complex_key_map", make_complex_key_map)?;
+    m.export_function("make_simple_key_map", make_simple_key_map)?;
+    m.export_function("make_bigint_enabled", make_bigint_enabled)?;
+    m.export_function("make_key_buffer_enabled", make_key_buffer_enabled)?;
+    m.export_function("make_u128_max", make_u128_max)?;
+    m.export_function("make_i128_min", make_i128_min)?;
+    m.export_function("make_nonzero", make_nonzero)?;
+    m.export_function("make_named_tuple", make_named_tuple)?;
+    m.export_function("make_named_tuple_mismatch", make_named_tuple_mismatch)?;
+    m.export_function("fill_instance", fill_instance)?;
+    m.export_function("make_query_params", make_query_params)?;
+    m.export_function("make_query_params_nested", make_query_params_nested)?;
+    m.export_function("make_rooted", make_rooted)?;
+    m.export_function("read_rooted", read_rooted)?;
+    m.export_function("append_numbers", append_numbers)?;
+    m.export_function("make_merged", make_merged)?;
+    m.export_function("make_merged_array", make_merged_array)?;
+    m.export_function("append_struct", append_struct)?;
+    m.export_function("expect_rooted", expect_rooted)?;
+    m.export_function("fill_instance_shadowing", fill_instance_shadowing)?;
+    m.export_function("make_generator", make_generator)?;
+    m.export_function("make_uuid_enabled", make_uuid_enabled)?;
+    #[cfg(feature = "uuid")]
+    m.export_function("make_uuid", make_uuid)?;
+    #[cfg(feature = "uuid")]
+    m.export_function("make_uuid_compact", make_uuid_compact)?;
+    m.export_function("make_smallvec_enabled", make_smallvec_enabled)?;
+    #[cfg(feature = "smallvec")]
+    m.export_function("make_smallvec", make_smallvec)?;
+    m.export_function("make_arrayvec_enabled", make_arrayvec_enabled)?;
+    m.export_function("make_indexmap_enabled", make_indexmap_enabled)?;
+    #[cfg(feature = "arrayvec")]
+    m.export_function("make_arrayvec", make_arrayvec)?;
+
+    m.export_function("expect_hello_world", expect_hello_world)?;
+    m.export_function("expect_obj", expect_obj)?;
+    m.export_function("expect_num_array", expect_num_array)?;
+    m.export_function("expect_buffer", expect_buffer)?;
+    m.export_function("expect_proto_map", expect_proto_map)?;
+    m.export_function("expect_num_array_all_errors", expect_num_array_all_errors)?;
+    m.export_function("expect_hello_world_friendly", expect_hello_world_friendly)?;
+    m.export_function("expect_num_iterable", expect_num_iterable)?;
+    m.export_function("expect_pair_map", expect_pair_map)?;
+    m.export_function("expect_char_key_map", expect_char_key_map)?;
+    m.export_function("expect_sparse", expect_sparse)?;
+    m.export_function("key_allocations", key_allocations)?;
+    m.export_function("expect_sparse_lookup", expect_sparse_lookup)?;
+    m.export_function("expect_lowercase_variants", expect_lowercase_variants)?;
+    m.export_function("expect_ambiguous_variant", expect_ambiguous_variant)?;
+    m.export_function("expect_nan_as_none", expect_nan_as_none)?;
+    m.export_function("expect_undefined_as_default", expect_undefined_as_default)?;
+    m.export_function("expect_string_table", expect_string_table)?;
+    m.export_function("expect_expected_variants", expect_expected_variants)?;
+    m.export_function("expect_screaming_variants", expect_screaming_variants)?;
+    m.export_function("expect_boxed_slice", expect_boxed_slice)?;
+    m.export_function("expect_rc_slice", expect_rc_slice)?;
+    m.export_function("expect_null_as_default", expect_null_as_default)?;
+    m.export_function("expect_null_as_default_lookup", expect_null_as_default_lookup)?;
+    m.export_function("expect_empty_tuple_variant", expect_empty_tuple_variant)?;
+    m.export_function("expect_positional_tuple", expect_positional_tuple)?;
+    m.export_function("expect_map_pairs", expect_map_pairs)?;
+    m.export_function("expect_option_vecs", expect_option_vecs)?;
+    m.export_function("expect_positional_variant", expect_positional_variant)?;
+    m.export_function("expect_date_string", expect_date_string)?;
+    m.export_function("expect_date_millis", expect_date_millis)?;
+    m.export_function("expect_captured", expect_captured)?;
+    m.export_function("expect_captured_nested", expect_captured_nested)?;
+    m.export_function("expect_string_chars", expect_string_chars)?;
+    m.export_function("expect_buffer_json", expect_buffer_json)?;
+    m.export_function("expect_buffer_base64", expect_buffer_base64)?;
+    m.export_function("expect_reinterpreted", expect_reinterpreted)?;
+    m.export_function("expect_captured_into_string", expect_captured_into_string)?;
+    m.export_function("expect_captured_without_field", expect_captured_without_field)?;
+    m.export_function("expect_bigint_string", expect_bigint_string)?;
+    m.export_function("expect_tuple_fill_defaults", expect_tuple_fill_defaults)?;
+    m.export_function("expect_tuple_fill_enum", expect_tuple_fill_enum)?;
+    m.export_function("expect_all_fields", expect_all_fields)?;
+    m.export_function("expect_all_fields_aliased", expect_all_fields_aliased)?;
+    m.export_function("expect_numeric_strings", expect_numeric_strings)?;
+    m.export_function("expect_decimal_comma", expect_decimal_comma)?;
+    m.export_function("expect_nonzero", expect_nonzero)?;
+    m.export_function("expect_empty_object_unit", expect_empty_object_unit)?;
+    m.export_function("expect_unit", expect_unit)?;
+    m.export_function("expect_safe_integer_bounds", expect_safe_integer_bounds)?;
+    m.export_function("expect_int_widths", expect_int_widths)?;
+    m.export_function("expect_bigint_extremes", expect_bigint_extremes)?;
+    m.export_function("expect_aliased", expect_aliased)?;
+    m.export_function("expect_aliased_lookup", expect_aliased_lookup)?;
+    m.export_function("expect_nested_none", expect_nested_none)?;
+    m.export_function("expect_nested_some", expect_nested_some)?;
+    m.export_function("expect_type_value_enums", expect_type_value_enums)?;
+    m.export_function("expect_rounded_f32", expect_rounded_f32)?;
+    m.export_function("expect_strict_f32", expect_strict_f32)?;
+    m.export_function("expect_strict_f32_string", expect_strict_f32_string)?;
+    m.export_function("expect_scalar_bool", expect_scalar_bool)?;
+    m.export_function("expect_scalar_string", expect_scalar_string)?;
+    m.export_function("expect_scalar_f64", expect_scalar_f64)?;
+    m.export_function("expect_scalar_f32", expect_scalar_f32)?;
+    m.export_function("expect_scalar_i8", expect_scalar_i8)?;
+    m.export_function("expect_scalar_i16", expect_scalar_i16)?;
+    m.export_function("expect_scalar_i32", expect_scalar_i32)?;
+    m.export_function("expect_scalar_i64", expect_scalar_i64)?;
+    m.export_function("expect_scalar_u8", expect_scalar_u8)?;
+    m.export_function("expect_scalar_u16", expect_scalar_u16)?;
+    m.export_function("expect_scalar_u32", expect_scalar_u32)?;
+    m.export_function("expect_scalar_u64", expect_scalar_u64)?;
+    #[cfg(feature = "uuid")]
+    m.export_function("expect_uuid", expect_uuid)?;
+    #[cfg(feature = "uuid")]
+    m.export_function("expect_uuid_compact", expect_uuid_compact)?;
+    #[cfg(feature = "smallvec")]
+    m.export_function("expect_smallvec", expect_smallvec)?;
+    #[cfg(feature = "arrayvec")]
+    m.export_function("expect_arrayvec", expect_arrayvec)?;
+    #[cfg(feature = "indexmap")]
+    m.export_function("read_key_order", read_key_order)?;
+
+    m.export_function("roundtrip_object", roundtrip_object)?;
+    m.export_function("dynamic_shape_area", dynamic_shape_area)?;
+    m.export_function("roundtrip_patch", roundtrip_patch)?;
+    m.export_function("scan_until_negative", scan_until_negative)?;
+    m.export_function("roundtrip_unit_variants", roundtrip_unit_variants)?;
+    m.export_function("roundtrip_type_value_tuples", roundtrip_type_value_tuples)?;
+    m.export_function("roundtrip_internally_tagged", roundtrip_internally_tagged)?;
+    m.export_function("make_tagged_tuple", make_tagged_tuple)?;
+    m.export_function("expect_internally_tagged", expect_internally_tagged)?;
+    m.export_function("roundtrip_nested_option", roundtrip_nested_option)?;
+    m.export_function("roundtrip_optional_bytes", roundtrip_optional_bytes)?;
+    m.export_function("dump_failure", dump_failure)?;
+    m.export_function("parse_port", parse_port)?;
+    m.export_function("json_number_kind", json_number_kind)?;
+    m.export_function("expect_custom_message", expect_custom_message)?;
+    m.export_function("expect_presized_strings", expect_presized_strings)?;
+
+    m.export_function("make_wide_structs", make_wide_structs)?;
+    m.export_function("make_record_batch", make_record_batch)?;
+    m.export_function("make_wide_structs_each", make_wide_structs_each)?;
+    m.export_function("make_wide_structs_planned", make_wide_structs_planned)?;
+    m.export_function("make_f64s", make_f64s)?;
+    m.export_function("make_f64s_typed", make_f64s_typed)?;
+    m.export_function("sum_from_value", sum_from_value)?;
+    m.export_function("sum_from_value_scalar", sum_from_value_scalar)?;
+    m.export_function("sum_reused_deserializer", sum_reused_deserializer)?;
+    m.export_function("string_array_len", string_array_len)?;
     Ok(())
 });